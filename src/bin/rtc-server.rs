@@ -0,0 +1,33 @@
+use clap::{App, Arg};
+
+use rust_todo_cli::{Config, RtcResult, Server};
+
+fn main() -> RtcResult<()> {
+    let matches = App::new("Rust TODO server")
+        .version("0.1")
+        .author("Lorenz Leitner")
+        .about("JSON API for managing TODOs")
+        .arg(
+            Arg::with_name("addr")
+                .help("Address to listen on")
+                .long("addr")
+                .default_value("127.0.0.1:8080")
+                .value_name("ADDR"),
+        )
+        .arg(
+            Arg::with_name("db")
+                .help("Database file, defaults to the one of rtc")
+                .long("db")
+                .takes_value(true)
+                .value_name("FILE"),
+        )
+        .get_matches();
+
+    let session = Config::session(matches.value_of("db"))?;
+    let server = Server::bind(matches.value_of("addr").unwrap(), session)?;
+    if let Some(addr) = server.local_addr() {
+        println!("Serving on http://{}", addr);
+    }
+    server.serve();
+    Ok(())
+}
@@ -1,27 +1,41 @@
+use crate::util::journal::Change;
 use crate::{CrudHandler, DbDriver, RtcError, RtcResult, RunReturn, TodoItem};
 use colored::*;
+use std::io::Write;
 
 impl<T: DbDriver> CrudHandler<T> {
     pub fn add(&mut self, name: &str) -> RtcResult<RunReturn> {
-        println!("Running add for item with name: '{}'", name.green());
+        writeln!(
+            self.out(),
+            "Running add for item with name: '{}'",
+            name.green()
+        )?;
 
-        let new_key = self.get_new_key();
-        let todo_item = TodoItem::new(new_key, name);
+        // The counter and the item are only kept together
+        self.transaction(|crud_handler| {
+            let new_key = crud_handler.get_new_key()?;
+            let todo_item = TodoItem::new(new_key, name);
 
-        match self
-            .db_wrapper
-            .driver
-            .set(&(new_key.to_string()), &todo_item)
-        {
-            Ok(_) => {
-                println!("Created item with ID: '{}'", new_key.to_string().blue());
-                Ok(RunReturn::Addition(new_key))
+            match crud_handler
+                .db_wrapper
+                .driver
+                .set(&new_key.to_string(), &todo_item)
+            {
+                Ok(_) => {
+                    crud_handler.record(Change::Added(todo_item))?;
+                    writeln!(
+                        crud_handler.out(),
+                        "Created item with ID: '{}'",
+                        new_key.to_string().blue()
+                    )?;
+                    Ok(RunReturn::Addition(new_key))
+                }
+                Err(e) => Err(RtcError::Creation(format!("Error: {}", e))),
             }
-            Err(e) => Err(RtcError::Creation(format!("Error: {}", e))),
-        }
+        })
     }
 
-    fn get_new_key(&mut self) -> usize {
+    pub(crate) fn get_new_key(&mut self) -> RtcResult<usize> {
         // Keep total creations as extra db entry instead of getting it via
         // .total_keys, so that deletions do no mess up new additions
         let ktc = &self.db_wrapper.key_total_creations().clone();
@@ -31,7 +45,23 @@ impl<T: DbDriver> CrudHandler<T> {
             .driver
             .get::<usize>(ktc)
             .map_or_else(|| 1, |x| x + 1);
-        self.db_wrapper.driver.set::<usize>(ktc, &new_key).unwrap();
-        new_key
+        match self.db_wrapper.driver.set::<usize>(ktc, &new_key) {
+            Ok(_) => Ok(new_key),
+            Err(e) => Err(RtcError::Creation(format!("Error: {}", e))),
+        }
+    }
+
+    /// Makes sure keys up to `id` are not assigned anymore, as it was taken over from elsewhere
+    pub(crate) fn reserve_keys_up_to(&mut self, id: usize) -> RtcResult<()> {
+        let ktc = &self.db_wrapper.key_total_creations().clone();
+
+        let total = self.db_wrapper.driver.get::<usize>(ktc).unwrap_or(0);
+        if total >= id {
+            return Ok(());
+        }
+        self.db_wrapper
+            .driver
+            .set::<usize>(ktc, &id)
+            .map_err(|e| RtcError::Creation(format!("Error: {}", e)))
     }
 }
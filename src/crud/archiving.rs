@@ -0,0 +1,110 @@
+use crate::util::journal::Change;
+use crate::{CrudHandler, DbDriver, RtcError, RtcResult, RunReturn, Status, TodoItem};
+use chrono::{DateTime, Duration, Utc};
+use colored::*;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::Write;
+
+/// Archived items by ID
+type Archive = BTreeMap<usize, TodoItem>;
+
+impl<T: DbDriver> CrudHandler<T> {
+    /// Moves done items that were completed before `before_date` into the archive
+    pub fn archive(&mut self, before_date: Option<&str>) -> RtcResult<RunReturn> {
+        writeln!(
+            self.out(),
+            "Running archive for done items before: '{}'",
+            before_date.unwrap_or_default().green()
+        )?;
+
+        let before = before_date
+            .map(CrudHandler::<T>::get_date_from_str)
+            .transpose()?;
+        let mut ids = vec![];
+        for item in self.archive_done_items(before)? {
+            ids.push(*item.id());
+            self.record(Change::Archived(item))?;
+        }
+
+        if ids.is_empty() {
+            writeln!(self.out(), "Found no items to archive")?;
+        } else {
+            writeln!(
+                self.out(),
+                "Archived {} items",
+                ids.len().to_string().blue()
+            )?;
+        }
+        Ok(RunReturn::Archived(ids))
+    }
+
+    /// Archives done items older than `days`, as configured in the settings
+    pub fn auto_archive(&mut self, days: i64) -> RtcResult<()> {
+        let items = self.archive_done_items(Some(Utc::now() - Duration::days(days)))?;
+
+        if !items.is_empty() {
+            writeln!(
+                self.out(),
+                "Auto-archived {} items",
+                items.len().to_string().blue()
+            )?;
+        }
+        // Any command archives, and even read-only ones must not lose what can be redone
+        for item in items {
+            self.record_keeping_redo(Change::Archived(item))?;
+        }
+        Ok(())
+    }
+
+    /// Returns all archived items
+    pub(crate) fn archived_items(&self) -> Vec<TodoItem> {
+        self.archive_map().into_values().collect()
+    }
+
+    /// Moves done items into the archive, returning them in the order of their IDs
+    fn archive_done_items(&mut self, before: Option<DateTime<Utc>>) -> RtcResult<Vec<TodoItem>> {
+        let mut to_archive = self
+            .db_wrapper
+            .driver
+            .get_all::<TodoItem>()
+            .into_iter()
+            .filter(|item| item.status == Status::Done)
+            .filter(|item| match before {
+                // Items completed before completion dates were recorded count as completed
+                // when they were created
+                Some(b) => item.completed_at().unwrap_or(item.creation_date()) < &b,
+                None => true,
+            })
+            .collect::<Vec<TodoItem>>();
+
+        to_archive.sort_by_key(|item| *item.id());
+        for item in &to_archive {
+            self.move_to_archive(item)
+                .map_err(|e| RtcError::Update(format!("Error: {}", e)))?;
+        }
+        Ok(to_archive)
+    }
+
+    pub(crate) fn move_to_archive(&mut self, item: &TodoItem) -> Result<(), Box<dyn Error>> {
+        let key = item.id().to_string();
+        self.db_wrapper.archive.set(&key, item)?;
+        self.db_wrapper.driver.del(&key)?;
+        Ok(())
+    }
+
+    pub(crate) fn move_from_archive(&mut self, item: &TodoItem) -> Result<(), Box<dyn Error>> {
+        let key = item.id().to_string();
+        self.db_wrapper.archive.del(&key)?;
+        self.db_wrapper.driver.set(&key, item)
+    }
+
+    pub(crate) fn archive_map(&self) -> Archive {
+        self.db_wrapper
+            .archive
+            .get_all::<TodoItem>()
+            .into_iter()
+            .map(|item| (*item.id(), item))
+            .collect()
+    }
+}
@@ -0,0 +1,176 @@
+use crate::util::db::backup::Backup;
+use crate::util::db::migration;
+use crate::util::journal::Change;
+use crate::{CrudHandler, DbDriver, RtcError, RtcResult, RunReturn, TodoItem};
+use colored::*;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+
+/// How a backup is brought back
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RestoreMode {
+    /// The database becomes exactly what was backed up
+    Replace,
+    /// Items are added to the database, under new IDs if theirs are taken
+    Merge,
+}
+
+impl<T: DbDriver> CrudHandler<T> {
+    /// Returns every record of the database
+    pub fn backup(&self) -> Backup {
+        Backup::of(&self.db_wrapper)
+    }
+
+    /// Restores `backup`, upgrading it to the current schema first.
+    /// Returns the IDs of the items that were restored.
+    pub fn restore_backup(&mut self, backup: Backup, mode: RestoreMode) -> RtcResult<RunReturn> {
+        writeln!(self.out(), "Running restore of backup, {:?}", mode)?;
+
+        let version = backup.schema_version();
+        let mut records = backup.into_records();
+        migration::migrate_records(&self.db_wrapper, &mut records, version)?;
+
+        let ids = match mode {
+            RestoreMode::Replace => self.replace_records(records)?,
+            RestoreMode::Merge => self.merge_records(records)?,
+        };
+        writeln!(
+            self.out(),
+            "Restored {} items",
+            ids.len().to_string().blue()
+        )?;
+        Ok(RunReturn::BackupRestored(ids))
+    }
+
+    fn replace_records(&mut self, mut records: BTreeMap<String, Value>) -> RtcResult<Vec<usize>> {
+        let to_error = |e| RtcError::Update(format!("Error: {}", e));
+
+        for key in self.db_wrapper.driver.keys() {
+            self.db_wrapper.driver.del(&key).map_err(to_error)?;
+        }
+        for key in self.db_wrapper.archive.keys() {
+            self.db_wrapper.archive.del(&key).map_err(to_error)?;
+        }
+        if let Some(Value::Object(archive)) = records.remove(self.db_wrapper.key_archive()) {
+            for (id, item) in &archive {
+                self.db_wrapper.archive.set(id, item).map_err(to_error)?;
+            }
+        }
+        for (key, value) in &records {
+            self.db_wrapper.driver.set(key, value).map_err(to_error)?;
+        }
+        Ok(records
+            .keys()
+            .filter_map(|key| key.parse::<usize>().ok())
+            .collect())
+    }
+
+    /// Adds the items that are not in the database yet. Trashed and archived ones are only
+    /// added if their IDs are free, and views only if their names are.
+    fn merge_records(&mut self, mut records: BTreeMap<String, Value>) -> RtcResult<Vec<usize>> {
+        let to_error = |e| RtcError::Update(format!("Error: {}", e));
+
+        // New IDs are assigned after the ones used in either database
+        let counter = records
+            .get(self.db_wrapper.key_total_creations())
+            .and_then(Value::as_u64)
+            .unwrap_or(0);
+        self.reserve_keys_up_to(counter as usize)?;
+
+        let mut used = self
+            .db_wrapper
+            .driver
+            .keys()
+            .iter()
+            .filter_map(|k| k.parse::<usize>().ok())
+            .chain(self.trash().into_keys())
+            .chain(self.archive_map().into_keys())
+            .collect::<BTreeSet<usize>>();
+
+        // Items are the same if they only differ in their IDs, so that merging twice adds nothing
+        let without_id = |value: &Value| {
+            let mut value = value.clone();
+            if let Value::Object(item) = &mut value {
+                item.remove("id");
+            }
+            value
+        };
+        let existing = self
+            .db_wrapper
+            .driver
+            .keys()
+            .iter()
+            .filter(|k| k.parse::<usize>().is_ok())
+            .filter_map(|k| self.db_wrapper.driver.get::<Value>(k))
+            .map(|v| without_id(&v))
+            .collect::<Vec<Value>>();
+
+        let mut ids = vec![];
+        for (key, value) in records.iter().filter(|(k, _)| k.parse::<usize>().is_ok()) {
+            if existing.contains(&without_id(value)) {
+                continue;
+            }
+            let item = serde_json::from_value::<TodoItem>(value.clone())
+                .map_err(|e| RtcError::Parse(format!("Backed up item '{}', {}", key, e)))?;
+            let id = match *item.id() {
+                id if used.insert(id) => {
+                    self.reserve_keys_up_to(id)?;
+                    id
+                }
+                _ => self.get_new_key()?,
+            };
+            let item = item.with_id(id);
+            self.db_wrapper
+                .driver
+                .set(&id.to_string(), &item)
+                .map_err(to_error)?;
+            writeln!(self.out(), "Restored {}", item)?;
+            self.record(Change::Added(item))?;
+            ids.push(id);
+        }
+
+        let mut entries = |key: &String| {
+            let entries = match records.remove(key) {
+                Some(Value::Object(m)) => m,
+                _ => Map::new(),
+            };
+            entries
+                .into_iter()
+                .filter(|(id, _)| id.parse::<usize>().is_ok_and(|id| used.insert(id)))
+                .collect::<Vec<(String, Value)>>()
+        };
+
+        let key = self.db_wrapper.key_trash().clone();
+        let mut trash = self
+            .db_wrapper
+            .driver
+            .get::<Map<String, Value>>(&key)
+            .unwrap_or_default();
+        trash.extend(entries(&key));
+        self.db_wrapper.driver.set(&key, &trash).map_err(to_error)?;
+
+        let key = self.db_wrapper.key_archive().clone();
+        for (id, item) in entries(&key) {
+            self.db_wrapper.archive.set(&id, &item).map_err(to_error)?;
+        }
+
+        let key = self.db_wrapper.key_views().clone();
+        if let Some(Value::Object(views)) = records.remove(&key) {
+            let mut current = self
+                .db_wrapper
+                .driver
+                .get::<Map<String, Value>>(&key)
+                .unwrap_or_default();
+            for (name, filter) in views {
+                current.entry(name).or_insert(filter);
+            }
+            self.db_wrapper
+                .driver
+                .set(&key, &current)
+                .map_err(to_error)?;
+        }
+
+        Ok(ids)
+    }
+}
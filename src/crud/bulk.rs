@@ -0,0 +1,96 @@
+use crate::{CrudHandler, DbDriver, Filters, RtcResult, RunReturn, Status, TodoItem};
+use colored::*;
+use std::collections::BTreeSet;
+use std::io::Write;
+
+/// Outcome of a bulk operation for a single ID
+pub type BulkResult = (usize, RtcResult<RunReturn>);
+
+impl<T: DbDriver> CrudHandler<T> {
+    /// Deletes every item in `ids`, continuing past those that can not be deleted
+    pub fn delete_many(&mut self, ids: &[usize]) -> RtcResult<RunReturn> {
+        writeln!(
+            self.out(),
+            "Running delete for {} items",
+            ids.len().to_string().green()
+        )?;
+        self.for_each_id(ids, |crud_handler, id| crud_handler.delete(&id.to_string()))
+    }
+
+    /// Sets the status of every item in `ids`, continuing past those that can not be updated
+    pub fn update_many(&mut self, ids: &[usize], status: Status) -> RtcResult<RunReturn> {
+        writeln!(
+            self.out(),
+            "Running update for {} items to: '{}'",
+            ids.len().to_string().green(),
+            status.to_string().green()
+        )?;
+        self.for_each_id(ids, |crud_handler, id| {
+            crud_handler.update(&id.to_string(), status.clone())
+        })
+    }
+
+    /// IDs of all live items
+    pub fn ids(&self) -> BTreeSet<usize> {
+        self.db_wrapper
+            .driver
+            .keys()
+            .iter()
+            .filter_map(|key| key.parse::<usize>().ok())
+            .collect()
+    }
+
+    /// IDs of the items matching `filters`, in ascending order
+    pub fn matching_ids(&self, filters: &Filters) -> RtcResult<Vec<usize>> {
+        let mut ids = self
+            .matching_items(filters, false)?
+            .iter()
+            .map(|item| *item.id())
+            .collect::<Vec<usize>>();
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    /// Prints the items a bulk operation is about to change, so that it can be confirmed
+    pub fn print_bulk_summary(&self, action: &str, ids: &[usize]) -> RtcResult<()> {
+        writeln!(
+            self.out(),
+            "About to {} {} items:",
+            action,
+            ids.len().to_string().yellow()
+        )?;
+        for id in ids {
+            match self.db_wrapper.driver.get::<TodoItem>(&id.to_string()) {
+                Some(item) => writeln!(self.out(), "{}", item)?,
+                None => writeln!(self.out(), "{} (does not exist)", id.to_string().blue())?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs `op` for every ID in its own transaction, so that one failure does not undo the rest
+    fn for_each_id<F>(&mut self, ids: &[usize], op: F) -> RtcResult<RunReturn>
+    where
+        F: Fn(&mut CrudHandler<T>, usize) -> RtcResult<RunReturn>,
+    {
+        let results = ids
+            .iter()
+            .map(|&id| (id, self.transaction(|crud_handler| op(crud_handler, id))))
+            .collect::<Vec<BulkResult>>();
+
+        let failures = results.iter().filter(|(_, r)| r.is_err()).count();
+        for (id, result) in &results {
+            if let Err(e) = result {
+                writeln!(self.out(), "{} for ID '{}': {}", "Failed".red(), id, e)?;
+            }
+        }
+        writeln!(
+            self.out(),
+            "Succeeded for {} of {} items",
+            (results.len() - failures).to_string().blue(),
+            results.len().to_string().blue()
+        )?;
+
+        Ok(RunReturn::Bulk(results))
+    }
+}
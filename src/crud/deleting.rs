@@ -1,9 +1,11 @@
+use crate::util::journal::Change;
 use crate::{CrudHandler, DbDriver, RtcError, RtcResult, RunReturn, TodoItem};
 use colored::*;
+use std::io::Write;
 
 impl<T: DbDriver> CrudHandler<T> {
     pub fn delete(&mut self, id: &str) -> RtcResult<RunReturn> {
-        println!("Running delete for id: '{}'", id.green());
+        writeln!(self.out(), "Running delete for id: '{}'", id.green())?;
 
         self.db_wrapper
             .driver
@@ -18,19 +20,17 @@ impl<T: DbDriver> CrudHandler<T> {
                 id
             )))?;
 
-        match self.db_wrapper.driver.del(id) {
-            Ok(true) => {
-                println!(
-                    "Deleted item with ID: '{}' ('{}')",
+        match self.move_to_trash(&item) {
+            Ok(_) => {
+                writeln!(
+                    self.out(),
+                    "Moved item with ID: '{}' ('{}') to trash",
                     id.blue(),
                     item.name().green()
-                );
+                )?;
+                self.record(Change::Deleted(item))?;
                 Ok(RunReturn::Deletion(true))
             }
-            Ok(false) => Err(RtcError::ItemNotFound(format!(
-                "ID '{}' does not exist.",
-                id
-            ))),
             Err(e) => Err(RtcError::Deletion(format!("Error: {}", e))),
         }
     }
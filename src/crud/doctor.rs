@@ -0,0 +1,362 @@
+use crate::util::journal::Journal;
+use crate::{CrudHandler, DbDriver, RtcError, RtcResult, RunReturn, TodoItem, TrashedItem, Views};
+use chrono::Utc;
+use colored::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Something wrong with the stored data, found by [`CrudHandler::doctor`]
+#[derive(Debug, Clone, Serialize)]
+pub enum Problem {
+    /// The database file can not be loaded at all, e.g. because it was cut off
+    Unloadable(String),
+    /// A record that can not be read as what its key says it is
+    Undeserializable(String),
+    /// An item stored under a key that differs from its ID
+    IdMismatch { key: String, id: usize },
+    /// An item whose ID is already used by another item
+    DuplicateId { key: String, id: usize },
+    /// A key that is neither an item nor known metadata
+    UnknownKey(String),
+    /// The counter of created items is missing or lower than the highest ID, so IDs would be reused
+    Counter {
+        counter: Option<usize>,
+        highest: usize,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Unloadable(error) => write!(f, "Database file can not be loaded: {}", error),
+            Problem::Undeserializable(key) => {
+                write!(f, "Record '{}' can not be read", key.blue())
+            }
+            Problem::IdMismatch { key, id } => write!(
+                f,
+                "Record '{}' holds item with ID '{}'",
+                key.blue(),
+                id.to_string().blue()
+            ),
+            Problem::DuplicateId { key, id } => write!(
+                f,
+                "Record '{}' reuses ID '{}'",
+                key.blue(),
+                id.to_string().blue()
+            ),
+            Problem::UnknownKey(key) => write!(f, "Record '{}' is unknown", key.blue()),
+            Problem::Counter { counter, highest } => write!(
+                f,
+                "Counter of created items is '{}', but the highest ID is '{}'",
+                counter
+                    .map_or(String::from("missing"), |c| c.to_string())
+                    .blue(),
+                highest.to_string().blue()
+            ),
+        }
+    }
+}
+
+/// Records found to be bad, keyed by where they were stored, e.g. `5` or `trash/5`
+type Quarantine = BTreeMap<String, Value>;
+
+/// Doctor for database files that can not be loaded, given with the error loading them failed
+/// with. With `fix`, the files are moved next to `quarantine_file` as they are, and the next
+/// change starts them anew.
+pub fn doctor_file(
+    out: &mut dyn Write,
+    unloadable: &[(String, String)],
+    fix: bool,
+    quarantine_file: &Path,
+) -> RtcResult<RunReturn> {
+    writeln!(out, "Running doctor{}", if fix { " with fix" } else { "" })?;
+    let problems = unloadable
+        .iter()
+        .map(|(_, error)| Problem::Unloadable(error.clone()))
+        .collect::<Vec<Problem>>();
+    for problem in &problems {
+        writeln!(out, "{}", problem)?;
+    }
+    writeln!(
+        out,
+        "Found {} problems",
+        problems.len().to_string().yellow()
+    )?;
+
+    if fix {
+        let now = Utc::now().format("%Y%m%dT%H%M%S%3f");
+        for (file, _) in unloadable {
+            // Named after the file, so that the database and the archive are told apart
+            let name = Path::new(file).file_name().unwrap_or_default();
+            let raw_file =
+                quarantine_file.with_extension(format!("{}.{}.raw", now, name.to_string_lossy()));
+            fs::rename(file, &raw_file)?;
+            writeln!(out, "Quarantined database file into {}", raw_file.display())?;
+        }
+    }
+    Ok(RunReturn::Doctor(problems))
+}
+
+impl<T: DbDriver> CrudHandler<T> {
+    /// Checks every record, and with `fix` moves bad ones into `quarantine_file`
+    /// and repairs the counter of created items
+    pub fn doctor(&mut self, fix: bool, quarantine_file: &Path) -> RtcResult<RunReturn> {
+        writeln!(
+            self.out(),
+            "Running doctor{}",
+            if fix { " with fix" } else { "" }
+        )?;
+
+        let mut problems = vec![];
+        let mut quarantine = Quarantine::new();
+        let mut seen_ids = BTreeSet::new();
+        let mut removed_keys = vec![];
+        let mut rewritten = vec![];
+
+        let mut keys = self.db_wrapper.driver.keys();
+        // Items first and in order, so that the oldest record of an ID is kept
+        keys.sort_by_key(|k| (k.parse::<usize>().unwrap_or(usize::MAX), k.clone()));
+
+        for key in keys {
+            let value = self.db_wrapper.driver.get::<Value>(&key);
+
+            let problem = if let Ok(id) = key.parse::<usize>() {
+                seen_ids.insert(id);
+                match value
+                    .as_ref()
+                    .map(|v| CrudHandler::<T>::read::<TodoItem>(v))
+                {
+                    Some(Some(item)) if *item.id() != id => Some(Problem::IdMismatch {
+                        key: key.clone(),
+                        id: *item.id(),
+                    }),
+                    Some(Some(_)) => None,
+                    _ => Some(Problem::Undeserializable(key.clone())),
+                }
+            } else if &key == self.db_wrapper.key_trash() {
+                match value.as_ref().and_then(Value::as_object) {
+                    Some(map) => {
+                        let kept = CrudHandler::<T>::check_namespace(
+                            &key,
+                            map,
+                            true,
+                            &mut seen_ids,
+                            &mut problems,
+                            &mut quarantine,
+                        );
+                        if kept.len() != map.len() {
+                            rewritten.push((key.clone(), Value::Object(kept)));
+                        }
+                        None
+                    }
+                    None => Some(Problem::Undeserializable(key.clone())),
+                }
+            } else if &key == self.db_wrapper.key_total_creations() {
+                CrudHandler::<T>::check_record::<usize>(&key, &value)
+            } else if &key == self.db_wrapper.key_schema_version() {
+                CrudHandler::<T>::check_record::<u32>(&key, &value)
+            } else if &key == self.db_wrapper.key_views() {
+                CrudHandler::<T>::check_record::<Views>(&key, &value)
+            } else if &key == self.db_wrapper.key_journal() {
+                CrudHandler::<T>::check_record::<Journal>(&key, &value)
+            } else {
+                Some(Problem::UnknownKey(key.clone()))
+            };
+
+            if let Some(p) = problem {
+                problems.push(p);
+                quarantine.insert(
+                    key.clone(),
+                    value.unwrap_or_else(|| Value::String(String::from("<unreadable>"))),
+                );
+                removed_keys.push(key);
+            }
+        }
+
+        // The archive has its own file, with archived items under their ID
+        let archive = self
+            .db_wrapper
+            .archive
+            .keys()
+            .into_iter()
+            .map(|key| {
+                let value = self.db_wrapper.archive.get::<Value>(&key);
+                (key, value.unwrap_or(Value::Null))
+            })
+            .collect::<Map<String, Value>>();
+        let kept = CrudHandler::<T>::check_namespace(
+            self.db_wrapper.key_archive(),
+            &archive,
+            false,
+            &mut seen_ids,
+            &mut problems,
+            &mut quarantine,
+        );
+        let removed_archived = archive
+            .keys()
+            .filter(|key| !kept.contains_key(*key))
+            .cloned()
+            .collect::<Vec<String>>();
+
+        let highest = seen_ids.iter().next_back().cloned().unwrap_or(0);
+        let counter = self
+            .db_wrapper
+            .driver
+            .get::<usize>(self.db_wrapper.key_total_creations());
+        let counter_ok = counter.map_or(highest == 0, |c| c >= highest);
+        if !counter_ok {
+            problems.push(Problem::Counter { counter, highest });
+        }
+
+        for problem in &problems {
+            writeln!(self.out(), "{}", problem)?;
+        }
+        if problems.is_empty() {
+            writeln!(self.out(), "Found no problems")?;
+        } else {
+            writeln!(
+                self.out(),
+                "Found {} problems",
+                problems.len().to_string().yellow()
+            )?;
+        }
+
+        if fix && !problems.is_empty() {
+            self.fix(
+                quarantine,
+                quarantine_file,
+                &removed_keys,
+                &removed_archived,
+                &rewritten,
+                if counter_ok { None } else { Some(highest) },
+            )?;
+        }
+
+        Ok(RunReturn::Doctor(problems))
+    }
+
+    fn fix(
+        &mut self,
+        quarantine: Quarantine,
+        quarantine_file: &Path,
+        removed_keys: &[String],
+        removed_archived: &[String],
+        rewritten: &[(String, Value)],
+        counter: Option<usize>,
+    ) -> RtcResult<()> {
+        let to_err = |e: Box<dyn std::error::Error>| RtcError::Update(format!("Error: {}", e));
+
+        if !quarantine.is_empty() {
+            // Keep what earlier runs quarantined. A file that can not be read is left as it is,
+            // and a new one is started next to it.
+            let existing = match fs::read_to_string(quarantine_file) {
+                Ok(s) => serde_json::from_str::<Quarantine>(&s).ok(),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Some(Quarantine::new()),
+                Err(_) => None,
+            };
+            let (file, mut existing) = match existing {
+                Some(existing) => (quarantine_file.to_path_buf(), existing),
+                None => (
+                    quarantine_file
+                        .with_extension(format!("{}.json", Utc::now().format("%Y%m%dT%H%M%S%3f"))),
+                    Quarantine::new(),
+                ),
+            };
+            let n = quarantine.len();
+            existing.extend(quarantine);
+
+            let contents = serde_json::to_string_pretty(&existing)
+                .map_err(|e| RtcError::Io(format!("Could not serialize quarantine: {}", e)))?;
+            fs::write(&file, contents)?;
+            writeln!(
+                self.out(),
+                "Quarantined {} records into {}",
+                n.to_string().blue(),
+                file.display()
+            )?;
+        }
+
+        // Only remove records once they are safely quarantined
+        for key in removed_keys {
+            self.db_wrapper.driver.del(key).map_err(to_err)?;
+        }
+        for key in removed_archived {
+            self.db_wrapper.archive.del(key).map_err(to_err)?;
+        }
+        for (key, value) in rewritten {
+            self.db_wrapper.driver.set(key, value).map_err(to_err)?;
+        }
+        if let Some(highest) = counter {
+            let key = self.db_wrapper.key_total_creations().clone();
+            self.db_wrapper.driver.set(&key, &highest).map_err(to_err)?;
+            writeln!(
+                self.out(),
+                "Set counter of created items to {}",
+                highest.to_string().blue()
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Checks the entries of the trash or archive, returning the good ones
+    fn check_namespace(
+        namespace: &str,
+        map: &Map<String, Value>,
+        is_trash: bool,
+        seen_ids: &mut BTreeSet<usize>,
+        problems: &mut Vec<Problem>,
+        quarantine: &mut Quarantine,
+    ) -> Map<String, Value> {
+        let mut kept = Map::new();
+
+        for (key, value) in map {
+            let location = format!("{}/{}", namespace, key);
+            let item = if is_trash {
+                CrudHandler::<T>::read::<TrashedItem>(value).map(|t| t.item().clone())
+            } else {
+                CrudHandler::<T>::read::<TodoItem>(value)
+            };
+
+            let problem = match (item, key.parse::<usize>()) {
+                (Some(item), Ok(id)) if *item.id() != id => Some(Problem::IdMismatch {
+                    key: location.clone(),
+                    id: *item.id(),
+                }),
+                (Some(_), Ok(id)) if !seen_ids.insert(id) => Some(Problem::DuplicateId {
+                    key: location.clone(),
+                    id,
+                }),
+                (Some(_), Ok(_)) => None,
+                _ => Some(Problem::Undeserializable(location.clone())),
+            };
+
+            match problem {
+                Some(p) => {
+                    problems.push(p);
+                    quarantine.insert(location, value.clone());
+                }
+                None => {
+                    kept.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        kept
+    }
+
+    fn check_record<V: DeserializeOwned>(key: &str, value: &Option<Value>) -> Option<Problem> {
+        match value.as_ref().and_then(CrudHandler::<T>::read::<V>) {
+            Some(_) => None,
+            None => Some(Problem::Undeserializable(String::from(key))),
+        }
+    }
+
+    fn read<V: DeserializeOwned>(value: &Value) -> Option<V> {
+        serde_json::from_value(value.clone()).ok()
+    }
+}
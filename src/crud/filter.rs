@@ -1,72 +1,172 @@
-use crate::{CrudHandler, DbDriver, RtcError, RtcResult, RunReturn, TodoItem};
+use crate::{CrudHandler, DbDriver, ListOptions, RtcError, RtcResult, RunReturn, TodoItem};
 use chrono::{DateTime, Utc};
 use colored::*;
+use std::fmt;
+use std::io::Write;
+
+/// Criteria an item has to match all of, unset ones match everything
+#[derive(Debug, Default, Clone)]
+pub struct Filters<'a> {
+    pub name: Option<&'a str>,
+    pub status: Option<&'a str>,
+    pub before: Option<&'a str>,
+    pub after: Option<&'a str>,
+    pub completed_before: Option<&'a str>,
+    pub completed_after: Option<&'a str>,
+    pub modified_since: Option<&'a str>,
+    pub tag: Option<&'a str>,
+    pub project: Option<&'a str>,
+}
+
+impl<'a> Filters<'a> {
+    /// Keys of the `key=value` filter arguments
+    pub const KEYS: [&'static str; 9] = [
+        "name",
+        "status",
+        "before",
+        "after",
+        "completed_before",
+        "completed_after",
+        "modified_since",
+        "tag",
+        "project",
+    ];
+
+    pub fn get(&self, key: &str) -> Option<&'a str> {
+        match key {
+            "name" => self.name,
+            "status" => self.status,
+            "before" => self.before,
+            "after" => self.after,
+            "completed_before" => self.completed_before,
+            "completed_after" => self.completed_after,
+            "modified_since" => self.modified_since,
+            "tag" => self.tag,
+            "project" => self.project,
+            _ => None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        Filters::KEYS.iter().all(|k| self.get(k).is_none())
+    }
+}
+
+impl fmt::Display for Filters<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let set = Filters::KEYS
+            .iter()
+            .filter_map(|k| self.get(k).map(|v| format!("{}: '{}'", k, v.green())))
+            .collect::<Vec<String>>();
+        write!(f, "{}", set.join(", "))
+    }
+}
 
 impl<T: DbDriver> CrudHandler<T> {
-    pub fn filter(
-        &self,
-        name_substr: Option<&str>,
-        status: Option<&str>,
-        before_date: Option<&str>,
-        after_date: Option<&str>,
-    ) -> RtcResult<RunReturn> {
-        if name_substr.is_none()
-            && status.is_none()
-            && before_date.is_none()
-            && after_date.is_none()
-        {
-            println!("Running getall");
+    pub fn filter(&self, filters: &Filters, options: &ListOptions) -> RtcResult<RunReturn> {
+        if filters.is_empty() {
+            writeln!(self.out(), "Running getall")?;
+        } else {
+            writeln!(self.out(), "Running filter with: {}", filters)?;
+        }
+
+        let mut results = self.matching_items(filters, options.include_archived())?;
+        let total = results.len();
+        options.sort(&mut results);
+        let page = options.paginate(results);
+
+        if total == 0 {
+            writeln!(self.out(), "Found no items")?;
+        } else if page.len() < total {
+            writeln!(self.out(), "Found {} items, showing {}:", total, page.len())?;
         } else {
-            print!("Running filter with: ");
-            println!(
-                "name: '{}', status: '{}', before_date: '{}', after_date: '{}'",
-                name_substr.unwrap_or_default().green(),
-                status.unwrap_or_default().green(),
-                before_date.unwrap_or_default().green(),
-                after_date.unwrap_or_default().green(),
-            );
+            writeln!(self.out(), "Found {} items:", total)?;
         }
 
-        self.db_wrapper
-            .driver
-            .error_if_db_empty("Filter process stopping")?;
+        for item in &page {
+            writeln!(self.out(), "{}", item)?;
+        }
+        Ok(RunReturn::Filter(page, total))
+    }
 
+    /// Returns all items matching `filters`, in no particular order
+    pub(crate) fn matching_items(
+        &self,
+        filters: &Filters,
+        include_archived: bool,
+    ) -> RtcResult<Vec<TodoItem>> {
         let mut results: Vec<TodoItem> = self.db_wrapper.driver.get_all::<TodoItem>();
 
-        if let Some(n) = name_substr {
+        if include_archived {
+            results.extend(self.archived_items());
+        } else {
+            self.db_wrapper
+                .driver
+                .error_if_db_empty("Filter process stopping")?;
+        }
+
+        if let Some(n) = filters.name {
             results = CrudHandler::<T>::filter_items(results, |item| item.name().contains(n))
         };
 
-        if let Some(s) = status {
+        if let Some(s) = filters.status {
             results = CrudHandler::<T>::filter_items(results, |item| {
                 item.status.to_string().to_lowercase() == s.to_lowercase()
             })
         };
 
-        if let Some(d) = before_date {
+        if let Some(d) = filters.before {
             let before_date = CrudHandler::<T>::get_date_from_str(d)?;
             results =
                 CrudHandler::<T>::filter_items(results, |item| item.creation_date() < &before_date)
         };
 
-        if let Some(d) = after_date {
+        if let Some(d) = filters.after {
             let after_date = CrudHandler::<T>::get_date_from_str(d)?;
             results =
                 CrudHandler::<T>::filter_items(results, |item| item.creation_date() > &after_date)
         };
 
-        if results.is_empty() {
-            println!("Found no items");
-        } else {
-            println!("Found {} items:", results.len());
-        }
+        if let Some(d) = filters.completed_before {
+            let before_date = CrudHandler::<T>::get_date_from_str(d)?;
+            results = CrudHandler::<T>::filter_items(
+                results,
+                |item| matches!(item.completed_at(), Some(c) if c < &before_date),
+            )
+        };
+
+        if let Some(d) = filters.completed_after {
+            let after_date = CrudHandler::<T>::get_date_from_str(d)?;
+            results = CrudHandler::<T>::filter_items(
+                results,
+                |item| matches!(item.completed_at(), Some(c) if c > &after_date),
+            )
+        };
+
+        if let Some(d) = filters.modified_since {
+            let since_date = CrudHandler::<T>::get_date_from_str(d)?;
+            results =
+                CrudHandler::<T>::filter_items(results, |item| item.last_modified() >= &since_date)
+        };
+
+        if let Some(t) = filters.tag {
+            results = CrudHandler::<T>::filter_items(results, |item| {
+                item.tags.iter().any(|tag| tag.eq_ignore_ascii_case(t))
+            })
+        };
+
+        if let Some(p) = filters.project {
+            results = CrudHandler::<T>::filter_items(results, |item| {
+                item.projects
+                    .iter()
+                    .any(|project| project.eq_ignore_ascii_case(p))
+            })
+        };
 
-        results.sort_by(|a, b| a.id().cmp(&b.id()));
-        TodoItem::print_multiple(&results);
-        Ok(RunReturn::Filter(results))
+        Ok(results)
     }
 
-    fn get_date_from_str(date_str: &str) -> RtcResult<DateTime<Utc>> {
+    pub(crate) fn get_date_from_str(date_str: &str) -> RtcResult<DateTime<Utc>> {
         let iso_date_str = String::from(date_str) + " 00:00:00.000 +0000";
         match iso_date_str.parse::<DateTime<Utc>>() {
             Ok(d) => Ok(d),
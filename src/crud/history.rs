@@ -0,0 +1,116 @@
+use crate::util::audit_log::{AuditAction, AuditEntry};
+use crate::{CrudHandler, DbDriver, RtcError, RtcResult, RunReturn, TodoItem};
+use colored::*;
+use std::io::Write;
+
+impl<T: DbDriver> CrudHandler<T> {
+    /// Shows the most recent `n` mutations, most recent last
+    pub fn log(&self, n: usize) -> RtcResult<RunReturn> {
+        writeln!(
+            self.out(),
+            "Running log for last {} changes",
+            n.to_string().green()
+        )?;
+
+        let mut entries = self.audit_entries()?;
+        entries.drain(..entries.len().saturating_sub(n));
+
+        if entries.is_empty() {
+            writeln!(self.out(), "Found no changes")?;
+        }
+        for entry in &entries {
+            writeln!(self.out(), "{}", entry)?;
+        }
+        Ok(RunReturn::Log(entries))
+    }
+
+    /// Shows a live, archived or trashed item, and optionally how it changed over time
+    pub fn show(&self, id: &str, with_history: bool) -> RtcResult<RunReturn> {
+        writeln!(self.out(), "Running show for id: '{}'", id.green())?;
+
+        let not_found = || RtcError::ItemNotFound(format!("ID '{}' does not exist.", id));
+        let numeric_id = id.parse::<usize>().map_err(|_| not_found())?;
+        let item = self.find_item(numeric_id);
+
+        let history = if with_history {
+            self.audit_entries()?
+                .into_iter()
+                .filter(|entry| entry.id == numeric_id)
+                .collect()
+        } else {
+            vec![]
+        };
+
+        if item.is_none() && history.is_empty() {
+            return Err(not_found());
+        }
+
+        match &item {
+            Some(i) => writeln!(self.out(), "{}", i)?,
+            None => writeln!(self.out(), "Item was permanently deleted")?,
+        }
+        if with_history {
+            writeln!(self.out(), "History:")?;
+            for entry in &history {
+                writeln!(self.out(), "{}", entry)?;
+            }
+        }
+        Ok(RunReturn::Show(item, history))
+    }
+
+    /// Finds an item that is neither archived nor in the trash, returned like by `show`
+    pub fn get(&self, id: &str) -> RtcResult<RunReturn> {
+        let not_found = |why: &str| RtcError::ItemNotFound(format!("ID '{}' {}.", id, why));
+        let numeric_id = id
+            .parse::<usize>()
+            .map_err(|_| not_found("does not exist"))?;
+
+        match self
+            .db_wrapper
+            .driver
+            .get::<TodoItem>(&numeric_id.to_string())
+        {
+            Some(item) => Ok(RunReturn::Show(Some(item), vec![])),
+            None if self.trash().contains_key(&numeric_id) => Err(not_found("is in the trash")),
+            None if self.archive_map().contains_key(&numeric_id) => Err(not_found("is archived")),
+            None => Err(not_found("does not exist")),
+        }
+    }
+
+    /// Appends a mutation to the audit log, if there is one. Mutations made in a transaction are
+    /// only logged once it commits.
+    pub(crate) fn audit(
+        &self,
+        action: AuditAction,
+        before: Option<&TodoItem>,
+        after: Option<&TodoItem>,
+    ) -> RtcResult<()> {
+        let log = match &self.audit_log {
+            Some(log) => log,
+            None => return Ok(()),
+        };
+        let entry = AuditEntry::new(log.user(), action, before, after);
+        match self.depth {
+            0 => log.append(&entry),
+            _ => {
+                self.audit_entries.borrow_mut().push(entry);
+                Ok(())
+            }
+        }
+    }
+
+    fn audit_entries(&self) -> RtcResult<Vec<AuditEntry>> {
+        match &self.audit_log {
+            Some(log) => log.read(),
+            None => Ok(vec![]),
+        }
+    }
+
+    fn find_item(&self, id: usize) -> Option<TodoItem> {
+        self.db_wrapper
+            .driver
+            .get::<TodoItem>(&id.to_string())
+            .or_else(|| self.archive_map().remove(&id))
+            .or_else(|| self.trash().remove(&id).map(|t| t.item().clone()))
+    }
+}
@@ -0,0 +1,222 @@
+use crate::formats::Format;
+use crate::util::journal::Change;
+use crate::{
+    CrudHandler, DbDriver, Filters, ImportOptions, RtcError, RtcResult, RunReturn, Status, TodoItem,
+};
+use chrono::Utc;
+use colored::*;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::io::Write;
+
+impl<T: DbDriver> CrudHandler<T> {
+    /// Adds the items parsed from `text`, under new IDs unless they are kept, or updates them
+    pub fn import(
+        &mut self,
+        format: &Format,
+        text: &str,
+        options: &ImportOptions,
+    ) -> RtcResult<RunReturn> {
+        let items = format.import(text)?;
+        writeln!(
+            self.out(),
+            "Running import for {} items from {}",
+            items.len().to_string().green(),
+            format
+        )?;
+
+        if options.dry_run {
+            // Adding the items for real shows the IDs they would get
+            let result = self.rolled_back(|crud_handler| crud_handler.add_imported(items, options));
+            writeln!(self.out(), "{}, nothing was changed", "Dry run".yellow())?;
+            return result;
+        }
+        self.add_imported(items, options)
+    }
+
+    fn add_imported(
+        &mut self,
+        items: Vec<TodoItem>,
+        options: &ImportOptions,
+    ) -> RtcResult<RunReturn> {
+        let (synced, added): (Vec<TodoItem>, Vec<TodoItem>) = items.into_iter().partition(|item| {
+            options.sync
+                && self
+                    .db_wrapper
+                    .driver
+                    .get::<TodoItem>(&item.id().to_string())
+                    .is_some()
+        });
+        // Kept IDs tell items apart already, and fail if they are taken
+        let added = if options.keep_ids {
+            self.check_kept_ids(&added)?;
+            added
+        } else {
+            self.not_stored(added)?
+        };
+
+        let mut ids = vec![];
+        let mut updated = 0;
+        for item in synced {
+            let id = *item.id();
+            if self.sync_item(item, options.dry_run)? {
+                ids.push(id);
+                updated += 1;
+            }
+        }
+
+        for item in added {
+            let id = match *item.id() {
+                0 => self.get_new_key()?,
+                id if options.keep_ids => id,
+                _ => self.get_new_key()?,
+            };
+            let item = item.with_id(id);
+            if let Err(e) = self.db_wrapper.driver.set(&id.to_string(), &item) {
+                return Err(RtcError::Creation(format!("Error: {}", e)));
+            }
+            if options.dry_run {
+                writeln!(self.out(), "Would import {}", item)?;
+            } else {
+                writeln!(self.out(), "Imported {}", item)?;
+                self.record(Change::Added(item))?;
+            }
+            ids.push(id);
+        }
+
+        writeln!(
+            self.out(),
+            "{} {} items{}",
+            if options.dry_run {
+                "Would import"
+            } else {
+                "Imported"
+            },
+            (ids.len() - updated).to_string().blue(),
+            if options.sync {
+                format!(" and update {}", updated.to_string().blue())
+            } else {
+                String::new()
+            }
+        )?;
+        Ok(RunReturn::Imported(ids))
+    }
+
+    /// Replaces the stored item with the ID of `item`, unless nothing changed.
+    /// Returns whether it was updated.
+    fn sync_item(&mut self, mut item: TodoItem, dry_run: bool) -> RtcResult<bool> {
+        let id = item.id().to_string();
+        let before = match self.db_wrapper.driver.get::<TodoItem>(&id) {
+            Some(b) => b,
+            None => return Ok(false),
+        };
+
+        // Files may hold dates at a lower precision, or not at all, so the known ones are kept
+        let completed_at = match item.status {
+            Status::Done => before
+                .completed_at()
+                .or_else(|| item.completed_at())
+                .copied()
+                .or_else(|| Some(Utc::now())),
+            Status::Open => None,
+        };
+        let created_at = *before.creation_date();
+        item.set_dates(created_at, completed_at, before.modified_at().copied());
+        let stored = |item: &TodoItem| serde_json::to_value(item).ok();
+        if stored(&item) == stored(&before) {
+            return Ok(false);
+        }
+        item.set_dates(created_at, completed_at, Some(Utc::now()));
+
+        if let Err(e) = self.db_wrapper.driver.set(&id, &item) {
+            return Err(RtcError::Update(format!("Error: {}", e)));
+        }
+        if dry_run {
+            writeln!(self.out(), "Would update {}", item)?;
+        } else {
+            writeln!(self.out(), "Updated {}", item)?;
+            self.record(Change::Updated {
+                before,
+                after: item,
+            })?;
+        }
+        Ok(true)
+    }
+
+    /// Leaves out the items that are stored already, so that importing a file twice adds nothing.
+    /// Items are the same if they only differ in their IDs and dates, as files that lack dates get
+    /// them when imported.
+    fn not_stored(&mut self, items: Vec<TodoItem>) -> RtcResult<Vec<TodoItem>> {
+        let content = |item: &TodoItem| {
+            let mut value = serde_json::to_value(item).ok();
+            if let Some(Value::Object(fields)) = &mut value {
+                for field in &["id", "creation_date", "completed_at", "modified_at"] {
+                    fields.remove(*field);
+                }
+            }
+            value
+        };
+        let stored = self
+            .db_wrapper
+            .driver
+            .get_all::<TodoItem>()
+            .iter()
+            .map(content)
+            .collect::<Vec<Option<Value>>>();
+
+        let (skipped, added): (Vec<TodoItem>, Vec<TodoItem>) = items
+            .into_iter()
+            .partition(|item| stored.contains(&content(item)));
+        for item in skipped {
+            writeln!(
+                self.out(),
+                "Skipped '{}', it is stored already",
+                item.name().blue()
+            )?;
+        }
+        Ok(added)
+    }
+
+    /// Fails if kept IDs are used by other items, including trashed and archived ones, or appear
+    /// more than once. Otherwise makes sure new IDs are assigned after them.
+    fn check_kept_ids(&mut self, items: &[TodoItem]) -> RtcResult<()> {
+        let mut used = self
+            .db_wrapper
+            .driver
+            .keys()
+            .iter()
+            .filter_map(|k| k.parse::<usize>().ok())
+            .chain(self.trash().into_keys())
+            .chain(self.archive_map().into_keys())
+            .collect::<BTreeSet<usize>>();
+
+        let kept = items.iter().map(|item| *item.id()).filter(|&id| id != 0);
+        let duplicates = kept
+            .clone()
+            .filter(|&id| !used.insert(id))
+            .map(|id| id.to_string())
+            .collect::<Vec<String>>();
+        if !duplicates.is_empty() {
+            return Err(RtcError::Creation(format!(
+                "IDs already in use: {}",
+                duplicates.join(", ")
+            )));
+        }
+
+        match kept.max() {
+            Some(highest) => self.reserve_keys_up_to(highest),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the items matching `filters` in `format`, ordered by ID
+    pub fn export(&self, format: &Format, filters: &Filters) -> RtcResult<RunReturn> {
+        let mut items = if filters.is_empty() {
+            self.db_wrapper.driver.get_all::<TodoItem>()
+        } else {
+            self.matching_items(filters, false)?
+        };
+        items.sort_by_key(|item| *item.id());
+        Ok(RunReturn::Exported(format.export(&items)?))
+    }
+}
@@ -0,0 +1,112 @@
+use crate::util::git::Reference;
+use crate::util::journal::Change;
+use crate::{CrudHandler, DbDriver, RtcError, RtcResult, RunReturn, Status, TodoItem};
+use chrono::Utc;
+use colored::*;
+use std::io::Write;
+
+/// Extension holding the branch an item is worked on
+const BRANCH_KEY: &str = "branch";
+/// Extension holding the hashes of the commits referencing an item, separated by spaces
+const COMMITS_KEY: &str = "commits";
+
+impl<T: DbDriver> CrudHandler<T> {
+    /// Records that the item is worked on in `branch`, reopening it if it was done
+    pub fn start(&mut self, id: &str, branch: &str) -> RtcResult<RunReturn> {
+        writeln!(
+            self.out(),
+            "Running start of item '{}' on branch '{}'",
+            id.green(),
+            branch.green()
+        )?;
+
+        let mut item = match self.db_wrapper.driver.get::<TodoItem>(id) {
+            Some(x) => x,
+            None => {
+                return Err(RtcError::ItemNotFound(format!(
+                    "ID '{}' does not exist.",
+                    id
+                )))
+            }
+        };
+        let before = item.clone();
+        item.extensions
+            .insert(String::from(BRANCH_KEY), String::from(branch));
+        item.set_status(Status::Open);
+        if item.extensions == before.extensions && before.status == Status::Open {
+            writeln!(self.out(), "Already started on branch '{}'", branch.blue())?;
+            return Ok(RunReturn::Update(false));
+        }
+
+        self.store_linked(before, item)?;
+        Ok(RunReturn::Update(true))
+    }
+
+    /// Attaches the commit `hash` to the items it references, and marks the ones it closes as
+    /// done. References to unknown items are skipped, as the commit is already made.
+    /// Returns the IDs of the items that changed.
+    pub fn link_commit(&mut self, hash: &str, references: &[Reference]) -> RtcResult<RunReturn> {
+        writeln!(
+            self.out(),
+            "Running link of commit {} to {} items",
+            hash.green(),
+            references.len().to_string().green()
+        )?;
+
+        let mut ids = vec![];
+        for reference in references {
+            let mut item = match self
+                .db_wrapper
+                .driver
+                .get::<TodoItem>(&reference.id.to_string())
+            {
+                Some(x) => x,
+                None => {
+                    writeln!(
+                        self.out(),
+                        "{} ID '{}' does not exist",
+                        "Skipping".red(),
+                        reference.id
+                    )?;
+                    continue;
+                }
+            };
+            let before = item.clone();
+
+            let commits = item
+                .extensions
+                .entry(String::from(COMMITS_KEY))
+                .or_default();
+            // Running the hook twice for a commit links it once
+            if !commits.split_whitespace().any(|c| c == hash) {
+                *commits = format!("{} {}", commits, hash).trim_start().to_string();
+            }
+            if reference.closes {
+                item.set_status(Status::Done);
+            }
+            if item.extensions == before.extensions && item.status == before.status {
+                continue;
+            }
+
+            ids.push(*item.id());
+            self.store_linked(before, item)?;
+        }
+
+        writeln!(self.out(), "Linked {} items", ids.len().to_string().blue())?;
+        Ok(RunReturn::Linked(ids))
+    }
+
+    fn store_linked(&mut self, before: TodoItem, mut after: TodoItem) -> RtcResult<()> {
+        after.set_dates(
+            *after.creation_date(),
+            after.completed_at().copied(),
+            Some(Utc::now()),
+        );
+        let id = after.id().to_string();
+        if let Err(e) = self.db_wrapper.driver.set(&id, &after) {
+            return Err(RtcError::Update(format!("Error: {}", e)));
+        }
+        writeln!(self.out(), "Updated {}", after)?;
+        self.record(Change::Updated { before, after })
+    }
+}
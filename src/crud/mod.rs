@@ -1,16 +1,152 @@
 mod adding;
+mod archiving;
+mod backup;
+mod bulk;
 mod deleting;
+mod doctor;
 mod filter;
+mod history;
+mod importing;
+mod linking;
+mod scanning;
+mod trash;
+mod undoing;
 mod updating;
+mod views;
 
-use crate::{DatabaseWrapper, DbDriver};
+pub use backup::RestoreMode;
+pub use bulk::BulkResult;
+pub(crate) use doctor::doctor_file;
+pub use doctor::Problem;
+pub use filter::Filters;
+pub use views::Views;
+
+use crate::util::audit_log::{AuditEntry, AuditLog};
+use crate::util::journal::Change;
+use crate::{DatabaseWrapper, DbDriver, RtcError, RtcResult};
+use std::cell::{RefCell, RefMut};
+use std::io::{self, Write};
+
+/// Where operations write what they report
+pub type Output = Box<dyn Write + Send>;
 
 pub struct CrudHandler<T: DbDriver> {
     db_wrapper: DatabaseWrapper<T>,
+    audit_log: Option<AuditLog>,
+    output: RefCell<Output>,
+    /// Changes of the running operation, journaled as one entry when its outermost transaction
+    /// commits, along with whether they make what was undone unredoable
+    changes: Vec<(Change, bool)>,
+    /// Audit entries of the running operation, logged once its outermost transaction commits
+    audit_entries: RefCell<Vec<AuditEntry>>,
+    /// Number of transactions currently running
+    depth: usize,
 }
 
 impl<T: DbDriver> CrudHandler<T> {
     pub fn new<U: DbDriver>(db_wrapper: DatabaseWrapper<U>) -> CrudHandler<U> {
-        CrudHandler { db_wrapper }
+        CrudHandler {
+            db_wrapper,
+            audit_log: None,
+            output: RefCell::new(Box::new(io::stdout())),
+            changes: vec![],
+            audit_entries: RefCell::new(vec![]),
+            depth: 0,
+        }
+    }
+
+    /// Records every mutation in `audit_log`
+    pub fn with_audit_log(mut self, audit_log: AuditLog) -> CrudHandler<T> {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    /// Writes what operations report to `output` instead of stdout
+    pub fn with_output(mut self, output: Output) -> CrudHandler<T> {
+        self.set_output(output);
+        self
+    }
+
+    /// Like [`CrudHandler::with_output`], returning the output used so far
+    pub fn set_output(&mut self, output: Output) -> Output {
+        self.output.replace(output)
+    }
+
+    pub(crate) fn out(&self) -> RefMut<'_, Output> {
+        self.output.borrow_mut()
+    }
+
+    /// Runs `f` in a transaction, keeping its changes only if it succeeds
+    pub fn transaction<R>(
+        &mut self,
+        f: impl FnOnce(&mut CrudHandler<T>) -> RtcResult<R>,
+    ) -> RtcResult<R> {
+        self.db_wrapper.begin();
+        self.depth += 1;
+        let recorded = (self.changes.len(), self.audit_entries.borrow().len());
+
+        let result = f(self);
+        self.depth -= 1;
+        // The outermost transaction journals the changes of the whole operation at once
+        let result = match result {
+            Ok(r) if self.depth == 0 => self.journal_changes().map(|_| r),
+            result => result,
+        };
+
+        match result {
+            Ok(r) => match self.db_wrapper.commit() {
+                Ok(_) => {
+                    if self.depth == 0 {
+                        self.write_audit_entries();
+                    }
+                    Ok(r)
+                }
+                Err(e) => {
+                    self.discard_since(recorded);
+                    Err(RtcError::Io(format!("Could not commit changes: {}", e)))
+                }
+            },
+            Err(e) => {
+                self.discard_since(recorded);
+                if let Err(rollback_error) = self.db_wrapper.rollback() {
+                    eprintln!("Could not roll back changes: {}", rollback_error);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Runs `f` in a transaction that is always rolled back, to see what it would do
+    pub(crate) fn rolled_back<R>(
+        &mut self,
+        f: impl FnOnce(&mut CrudHandler<T>) -> RtcResult<R>,
+    ) -> RtcResult<R> {
+        self.db_wrapper.begin();
+        self.depth += 1;
+        let recorded = (self.changes.len(), self.audit_entries.borrow().len());
+        let result = f(self);
+        self.depth -= 1;
+        self.discard_since(recorded);
+        if let Err(e) = self.db_wrapper.rollback() {
+            return Err(RtcError::Io(format!("Could not roll back changes: {}", e)));
+        }
+        result
+    }
+
+    /// Forgets the changes and audit entries recorded after the given numbers of them
+    fn discard_since(&mut self, (changes, audit_entries): (usize, usize)) {
+        self.changes.truncate(changes);
+        self.audit_entries.get_mut().truncate(audit_entries);
+    }
+
+    /// Logs the audit entries of a committed operation
+    fn write_audit_entries(&mut self) {
+        let entries = std::mem::take(self.audit_entries.get_mut());
+        if let Some(log) = &self.audit_log {
+            // The changes are kept either way
+            if let Err(e) = entries.iter().try_for_each(|entry| log.append(entry)) {
+                eprintln!("Could not write audit log: {}", e);
+            }
+        }
     }
 }
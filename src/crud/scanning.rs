@@ -0,0 +1,155 @@
+use crate::util::code_scan::CodeTodo;
+use crate::util::journal::Change;
+use crate::{CrudHandler, DbDriver, RtcError, RtcResult, RunReturn, Status, TodoItem};
+use chrono::Utc;
+use colored::*;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Extension linking an item to its comment, as `path#Lline`
+const SOURCE_KEY: &str = "source";
+/// Extension holding the directory the comment was found in, as items of different scans are
+/// kept apart
+const SCAN_ROOT_KEY: &str = "scan_root";
+
+/// Comments are told apart by their file and text, and by their order if those repeat
+type CommentKey = (String, String);
+
+impl<T: DbDriver> CrudHandler<T> {
+    /// Adds an item for each new comment found below `root`, and marks the items of comments that
+    /// were removed as done. Items of comments that moved keep their status and follow their line.
+    /// Returns the IDs of the items that were added, moved and marked as done.
+    pub fn sync_code_todos(&mut self, root: &str, todos: Vec<CodeTodo>) -> RtcResult<RunReturn> {
+        writeln!(
+            self.out(),
+            "Running scan of {} with {} comments",
+            root.green(),
+            todos.len().to_string().green()
+        )?;
+
+        let scanned_here = |item: &TodoItem| {
+            item.extensions
+                .get(SCAN_ROOT_KEY)
+                .is_some_and(|r| r == root)
+        };
+        let mut live = BTreeMap::<CommentKey, Vec<TodoItem>>::new();
+        let mut items = self.db_wrapper.driver.get_all::<TodoItem>();
+        items.sort_by_key(|item| (source_line(item), *item.id()));
+        for item in items.into_iter().filter(|item| scanned_here(item)) {
+            live.entry(comment_key(&item)).or_default().push(item);
+        }
+        // Comments of deleted and archived items are not added again
+        let mut gone = BTreeMap::<CommentKey, usize>::new();
+        let trashed = self.trash().into_values().map(|t| t.item().clone());
+        for item in trashed
+            .chain(self.archive_map().into_values())
+            .filter(|item| scanned_here(item))
+        {
+            *gone.entry(comment_key(&item)).or_default() += 1;
+        }
+
+        let (mut added, mut moved, mut closed) = (vec![], vec![], vec![]);
+        for todo in todos {
+            let key = (todo.path.clone(), name_of(&todo));
+            let existing = live.get_mut(&key).filter(|items| !items.is_empty());
+            match existing {
+                Some(items) => {
+                    let item = items.remove(0);
+                    if item.extensions.get(SOURCE_KEY) != Some(&todo.source()) {
+                        moved.push(self.move_code_todo(item, &todo)?);
+                    }
+                }
+                None => match gone.get_mut(&key).filter(|n| **n > 0) {
+                    Some(n) => *n -= 1,
+                    None => added.push(self.add_code_todo(root, &todo)?),
+                },
+            }
+        }
+
+        // Whatever is left was not found anymore
+        for item in live.into_values().flatten() {
+            if item.status == Status::Open {
+                closed.push(self.close_code_todo(item)?);
+            }
+        }
+
+        writeln!(
+            self.out(),
+            "Added {}, moved {} and closed {} items",
+            added.len().to_string().blue(),
+            moved.len().to_string().blue(),
+            closed.len().to_string().blue()
+        )?;
+        Ok(RunReturn::Scanned(added, moved, closed))
+    }
+
+    fn add_code_todo(&mut self, root: &str, todo: &CodeTodo) -> RtcResult<usize> {
+        let id = self.get_new_key()?;
+        let mut item = TodoItem::new(id, &name_of(todo));
+        item.tags = vec![todo.marker.to_lowercase()];
+        item.extensions
+            .insert(String::from(SOURCE_KEY), todo.source());
+        item.extensions
+            .insert(String::from(SCAN_ROOT_KEY), String::from(root));
+
+        if let Err(e) = self.db_wrapper.driver.set(&id.to_string(), &item) {
+            return Err(RtcError::Creation(format!("Error: {}", e)));
+        }
+        writeln!(self.out(), "Added {}", item)?;
+        self.record(Change::Added(item))?;
+        Ok(id)
+    }
+
+    fn move_code_todo(&mut self, item: TodoItem, todo: &CodeTodo) -> RtcResult<usize> {
+        let before = item.clone();
+        let mut item = item;
+        item.extensions
+            .insert(String::from(SOURCE_KEY), todo.source());
+        item.set_dates(
+            *before.creation_date(),
+            before.completed_at().copied(),
+            Some(Utc::now()),
+        );
+        self.store_code_todo(before, item)
+    }
+
+    fn close_code_todo(&mut self, item: TodoItem) -> RtcResult<usize> {
+        let before = item.clone();
+        let mut item = item;
+        item.set_status(Status::Done);
+        self.store_code_todo(before, item)
+    }
+
+    fn store_code_todo(&mut self, before: TodoItem, after: TodoItem) -> RtcResult<usize> {
+        let id = *after.id();
+        if let Err(e) = self.db_wrapper.driver.set(&id.to_string(), &after) {
+            return Err(RtcError::Update(format!("Error: {}", e)));
+        }
+        writeln!(self.out(), "Updated {}", after)?;
+        self.record(Change::Updated { before, after })?;
+        Ok(id)
+    }
+}
+
+/// Comments without text are named after their marker
+fn name_of(todo: &CodeTodo) -> String {
+    if todo.text.is_empty() {
+        todo.marker.clone()
+    } else {
+        todo.text.clone()
+    }
+}
+
+fn comment_key(item: &TodoItem) -> CommentKey {
+    let source = item.extensions.get(SOURCE_KEY).map_or("", String::as_str);
+    let path = source.rsplit_once("#L").map_or(source, |(path, _)| path);
+    (String::from(path), item.name().clone())
+}
+
+fn source_line(item: &TodoItem) -> usize {
+    item.extensions
+        .get(SOURCE_KEY)
+        .and_then(|s| s.rsplit_once("#L"))
+        .and_then(|(_, line)| line.parse::<usize>().ok())
+        .unwrap_or(0)
+}
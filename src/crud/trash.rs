@@ -0,0 +1,121 @@
+use crate::util::audit_log::AuditAction;
+use crate::util::journal::Change;
+use crate::{CrudHandler, DbDriver, RtcError, RtcResult, RunReturn, TodoItem, TrashedItem};
+use chrono::{Duration, Utc};
+use colored::*;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::Write;
+
+/// Deleted items by ID
+type Trash = BTreeMap<usize, TrashedItem>;
+
+impl<T: DbDriver> CrudHandler<T> {
+    pub fn list_trash(&self) -> RtcResult<RunReturn> {
+        writeln!(self.out(), "Running list trash")?;
+
+        let items = self.trash().into_values().collect::<Vec<TrashedItem>>();
+        if items.is_empty() {
+            writeln!(self.out(), "Trash is empty")?;
+        } else {
+            writeln!(self.out(), "Found {} items in trash:", items.len())?;
+        }
+
+        for item in &items {
+            writeln!(self.out(), "{}", item)?;
+        }
+        Ok(RunReturn::Trash(items))
+    }
+
+    pub fn restore(&mut self, id: &str) -> RtcResult<RunReturn> {
+        writeln!(self.out(), "Running restore for id: '{}'", id.green())?;
+
+        let trashed = id
+            .parse::<usize>()
+            .ok()
+            .and_then(|id| self.trash().remove(&id))
+            .ok_or(RtcError::ItemNotFound(format!(
+                "ID '{}' is not in the trash.",
+                id
+            )))?;
+
+        match self.restore_from_trash(trashed.item()) {
+            Ok(_) => {
+                writeln!(
+                    self.out(),
+                    "Restored item with ID: '{}' ('{}')",
+                    id.blue(),
+                    trashed.item().name().green()
+                )?;
+                self.record(Change::Restored(trashed.item().clone()))?;
+                Ok(RunReturn::Restoration(true))
+            }
+            Err(e) => Err(RtcError::Update(format!("Error: {}", e))),
+        }
+    }
+
+    /// Permanently removes trashed items, optionally only those deleted longer ago than `older_than`
+    pub fn empty_trash(&mut self, older_than: Option<Duration>) -> RtcResult<RunReturn> {
+        writeln!(
+            self.out(),
+            "Running empty trash for items older than: '{}'",
+            older_than
+                .map(|d| format!("{} days", d.num_days()))
+                .unwrap_or_default()
+                .green()
+        )?;
+
+        let cutoff = older_than.map(|d| Utc::now() - d);
+        let (purged, kept): (Trash, Trash) =
+            self.trash().into_iter().partition(|(_, v)| match cutoff {
+                Some(c) => v.deleted_at() < &c,
+                None => true,
+            });
+
+        match self.set_trash(&kept) {
+            Ok(_) => {
+                for trashed in purged.values() {
+                    self.audit(AuditAction::Purge, Some(trashed.item()), None)?;
+                }
+                writeln!(
+                    self.out(),
+                    "Permanently deleted {} items",
+                    purged.len().to_string().blue()
+                )?;
+                Ok(RunReturn::TrashEmptied(purged.len()))
+            }
+            Err(e) => Err(RtcError::Deletion(format!("Error: {}", e))),
+        }
+    }
+
+    /// Replaces the live item with a trashed copy, keeping its ID reserved
+    pub(crate) fn move_to_trash(&mut self, item: &TodoItem) -> Result<(), Box<dyn Error>> {
+        let mut trash = self.trash();
+        trash.insert(*item.id(), TrashedItem::new(item.clone()));
+        self.set_trash(&trash)?;
+        self.db_wrapper.driver.del(&item.id().to_string())?;
+        Ok(())
+    }
+
+    /// Makes the item live again, whether or not it is still in the trash
+    pub(crate) fn restore_from_trash(&mut self, item: &TodoItem) -> Result<(), Box<dyn Error>> {
+        self.db_wrapper.driver.set(&item.id().to_string(), item)?;
+        let mut trash = self.trash();
+        if trash.remove(item.id()).is_some() {
+            self.set_trash(&trash)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn trash(&self) -> Trash {
+        self.db_wrapper
+            .driver
+            .get::<Trash>(self.db_wrapper.key_trash())
+            .unwrap_or_default()
+    }
+
+    fn set_trash(&mut self, trash: &Trash) -> Result<(), Box<dyn Error>> {
+        let key = self.db_wrapper.key_trash().clone();
+        self.db_wrapper.driver.set(&key, trash)
+    }
+}
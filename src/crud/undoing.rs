@@ -0,0 +1,245 @@
+use crate::util::audit_log::AuditAction;
+use crate::util::journal::{Change, Journal};
+use crate::{CrudHandler, DbDriver, RtcError, RtcResult, RunReturn, TodoItem};
+use colored::*;
+use std::collections::BTreeSet;
+use std::io::Write;
+
+/// Where an item has to be for a change to be reverted or reapplied
+enum Place {
+    Live,
+    /// In the trash, or gone for good because the trash was emptied
+    Trash,
+    Archive,
+    Nowhere,
+}
+
+impl<T: DbDriver> CrudHandler<T> {
+    pub fn undo(&mut self, steps: usize) -> RtcResult<RunReturn> {
+        writeln!(
+            self.out(),
+            "Running undo for {} operation(s)",
+            steps.to_string().green()
+        )?;
+
+        let mut journal = self.journal();
+        let mut undone = vec![];
+
+        for _ in 0..steps {
+            let entry = match journal.undo_stack().pop() {
+                Some(e) => e,
+                None => break,
+            };
+            // Items changed more than once are as the last of their changes left them
+            let mut checked = BTreeSet::new();
+            for change in entry.changes.iter().rev() {
+                if checked.insert(change.id()) {
+                    self.check_unchanged(change, false)?;
+                }
+            }
+            for change in entry.changes.iter().rev() {
+                self.revert(change)?;
+                let (before, after) = change.states();
+                self.audit(AuditAction::Undo, after, before)?;
+            }
+            writeln!(self.out(), "Reverted {}", entry)?;
+            undone.push(entry.clone());
+            journal.redo_stack().push(entry);
+        }
+
+        if undone.is_empty() {
+            writeln!(self.out(), "Nothing to undo")?;
+        }
+        self.set_journal(&journal)?;
+        Ok(RunReturn::Undo(undone))
+    }
+
+    pub fn redo(&mut self, steps: usize) -> RtcResult<RunReturn> {
+        writeln!(
+            self.out(),
+            "Running redo for {} operation(s)",
+            steps.to_string().green()
+        )?;
+
+        let mut journal = self.journal();
+        let mut redone = vec![];
+
+        for _ in 0..steps {
+            let entry = match journal.redo_stack().pop() {
+                Some(e) => e,
+                None => break,
+            };
+            // Items changed more than once have to be as they were before the first change
+            let mut checked = BTreeSet::new();
+            for change in &entry.changes {
+                if checked.insert(change.id()) {
+                    self.check_unchanged(change, true)?;
+                }
+            }
+            for change in &entry.changes {
+                self.apply(change)?;
+                let (before, after) = change.states();
+                self.audit(AuditAction::Redo, before, after)?;
+            }
+            writeln!(self.out(), "Reapplied {}", entry)?;
+            redone.push(entry.clone());
+            journal.undo_stack().push(entry);
+        }
+
+        if redone.is_empty() {
+            writeln!(self.out(), "Nothing to redo")?;
+        }
+        self.set_journal(&journal)?;
+        Ok(RunReturn::Redo(redone))
+    }
+
+    /// Lists the operations that undo (or redo) would revert, most recent first
+    pub fn list_journal(&self, redo: bool) -> RtcResult<RunReturn> {
+        let mut journal = self.journal();
+        let mut entries = if redo {
+            journal.redo_stack().clone()
+        } else {
+            journal.undo_stack().clone()
+        };
+        entries.reverse();
+
+        if entries.is_empty() {
+            writeln!(
+                self.out(),
+                "Nothing to {}",
+                if redo { "redo" } else { "undo" }
+            )?;
+        } else {
+            writeln!(
+                self.out(),
+                "Operations that {} would revert, most recent first:",
+                if redo { "redo" } else { "undo" }
+            )?;
+        }
+
+        for (i, entry) in entries.iter().enumerate() {
+            writeln!(self.out(), "{}: {}", (i + 1).to_string().blue(), entry)?;
+        }
+        Ok(RunReturn::Journal(entries))
+    }
+
+    /// Records a change so it can be undone, and in the audit log. Changes made in a transaction
+    /// are undone together with the rest of the operation.
+    pub(crate) fn record(&mut self, change: Change) -> RtcResult<()> {
+        self.push_change(change, true)
+    }
+
+    /// Like [`CrudHandler::record`], for changes that are made on their own, e.g. by
+    /// auto-archiving, and should not make what was undone unredoable
+    pub(crate) fn record_keeping_redo(&mut self, change: Change) -> RtcResult<()> {
+        self.push_change(change, false)
+    }
+
+    fn push_change(&mut self, change: Change, clears_redo: bool) -> RtcResult<()> {
+        let (before, after) = change.states();
+        self.audit(change.audit_action(), before, after)?;
+
+        self.changes.push((change, clears_redo));
+        match self.depth {
+            0 => self.journal_changes(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Journals the changes recorded so far as a single operation
+    pub(crate) fn journal_changes(&mut self) -> RtcResult<()> {
+        if self.changes.is_empty() {
+            return Ok(());
+        }
+        let (changes, clears_redo): (Vec<Change>, Vec<bool>) =
+            std::mem::take(&mut self.changes).into_iter().unzip();
+        let mut journal = self.journal();
+        journal.record(changes, !clears_redo.contains(&true));
+        self.set_journal(&journal)
+    }
+
+    /// Fails if the item of `change` was changed since, i.e. is not as the change left it or, to
+    /// `redo` it, as it was before. Otherwise those changes would be overwritten.
+    fn check_unchanged(&self, change: &Change, redo: bool) -> RtcResult<()> {
+        let (place, item) = match (change, redo) {
+            (Change::Added(item), false)
+            | (Change::Restored(item), false)
+            | (Change::Deleted(item), true)
+            | (Change::Archived(item), true) => (Place::Live, item),
+            (Change::Updated { after, .. }, false) => (Place::Live, after),
+            (Change::Updated { before, .. }, true) => (Place::Live, before),
+            (Change::Deleted(item), false) | (Change::Restored(item), true) => (Place::Trash, item),
+            (Change::Archived(item), false) => (Place::Archive, item),
+            (Change::Added(item), true) => (Place::Nowhere, item),
+        };
+
+        let key = item.id().to_string();
+        let live = self.db_wrapper.driver.get::<TodoItem>(&key);
+        let trashed = self.trash().remove(item.id()).map(|t| t.item().clone());
+        let archived = self.db_wrapper.archive.get::<TodoItem>(&key);
+        let stored = |item: &TodoItem| serde_json::to_value(item).ok();
+        let is_item = |found: &Option<TodoItem>| found.as_ref().map(stored) == Some(stored(item));
+
+        let unchanged = match place {
+            Place::Live => is_item(&live),
+            Place::Trash => {
+                live.is_none() && (is_item(&trashed) || (trashed.is_none() && archived.is_none()))
+            }
+            Place::Archive => live.is_none() && is_item(&archived),
+            Place::Nowhere => live.is_none(),
+        };
+        if unchanged {
+            return Ok(());
+        }
+        Err(RtcError::Update(format!(
+            "Can not {} the {}, the item was changed since",
+            if redo { "reapply" } else { "revert" },
+            change
+        )))
+    }
+
+    fn revert(&mut self, change: &Change) -> RtcResult<()> {
+        let result = match change {
+            Change::Added(item) => self
+                .db_wrapper
+                .driver
+                .del(&item.id().to_string())
+                .map(|_| ()),
+            Change::Deleted(item) => self.restore_from_trash(item),
+            Change::Restored(item) => self.move_to_trash(item),
+            Change::Archived(item) => self.move_from_archive(item),
+            Change::Updated { before, .. } => {
+                self.db_wrapper.driver.set(&before.id().to_string(), before)
+            }
+        };
+        result.map_err(|e| RtcError::Update(format!("Error: {}", e)))
+    }
+
+    fn apply(&mut self, change: &Change) -> RtcResult<()> {
+        let result = match change {
+            Change::Added(item) => self.db_wrapper.driver.set(&item.id().to_string(), item),
+            Change::Deleted(item) => self.move_to_trash(item),
+            Change::Restored(item) => self.restore_from_trash(item),
+            Change::Archived(item) => self.move_to_archive(item),
+            Change::Updated { after, .. } => {
+                self.db_wrapper.driver.set(&after.id().to_string(), after)
+            }
+        };
+        result.map_err(|e| RtcError::Update(format!("Error: {}", e)))
+    }
+
+    fn journal(&self) -> Journal {
+        self.db_wrapper
+            .driver
+            .get::<Journal>(self.db_wrapper.key_journal())
+            .unwrap_or_default()
+    }
+
+    fn set_journal(&mut self, journal: &Journal) -> RtcResult<()> {
+        let key = self.db_wrapper.key_journal().clone();
+        self.db_wrapper
+            .driver
+            .set(&key, journal)
+            .map_err(|e| RtcError::Update(format!("Could not write journal: {}", e)))
+    }
+}
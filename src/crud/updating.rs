@@ -1,13 +1,16 @@
+use crate::util::journal::Change;
 use crate::{CrudHandler, DbDriver, RtcError, RtcResult, RunReturn, Status, TodoItem};
 use colored::*;
+use std::io::Write;
 
 impl<T: DbDriver> CrudHandler<T> {
     pub fn update(&mut self, id: &str, status: Status) -> RtcResult<RunReturn> {
-        println!(
+        writeln!(
+            self.out(),
             "Running update with args: '{}', '{}'",
             id.green(),
             status.to_string().green()
-        );
+        )?;
 
         self.db_wrapper
             .driver
@@ -23,15 +26,25 @@ impl<T: DbDriver> CrudHandler<T> {
             }
         };
 
-        todo_item.status = status;
+        let before = todo_item.clone();
+        todo_item.set_status(status);
 
         match self.db_wrapper.driver.set(id, &todo_item) {
             Ok(_) => {
-                println!("Found item with name: '{}'", todo_item.name().blue());
-                println!(
+                writeln!(
+                    self.out(),
+                    "Found item with name: '{}'",
+                    todo_item.name().blue()
+                )?;
+                writeln!(
+                    self.out(),
                     "Changed status to: '{}'",
                     todo_item.status.to_string().blue()
-                );
+                )?;
+                self.record(Change::Updated {
+                    before,
+                    after: todo_item,
+                })?;
                 Ok(RunReturn::Update(true))
             }
             Err(e) => Err(RtcError::Update(format!("Error: {}", e))),
@@ -0,0 +1,87 @@
+use crate::{CrudHandler, DbDriver, RtcError, RtcResult, RunReturn};
+use colored::*;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Saved views, mapping a view name to the query it runs, e.g. `status:open and tag:team`
+pub type Views = BTreeMap<String, String>;
+
+impl<T: DbDriver> CrudHandler<T> {
+    pub fn save_view(&mut self, name: &str, query: &str) -> RtcResult<RunReturn> {
+        writeln!(
+            self.out(),
+            "Saving view '{}' with query: '{}'",
+            name.green(),
+            query.green()
+        )?;
+
+        let mut views = self.views();
+        views.insert(String::from(name), String::from(query));
+
+        match self.set_views(&views) {
+            Ok(_) => {
+                writeln!(self.out(), "Saved view: '{}'", name.blue())?;
+                Ok(RunReturn::ViewSaved(String::from(name)))
+            }
+            Err(e) => Err(RtcError::Creation(format!("Error: {}", e))),
+        }
+    }
+
+    pub fn delete_view(&mut self, name: &str) -> RtcResult<RunReturn> {
+        writeln!(self.out(), "Running delete for view: '{}'", name.green())?;
+
+        let mut views = self.views();
+        if views.remove(name).is_none() {
+            return Err(RtcError::ItemNotFound(format!(
+                "View '{}' does not exist.",
+                name
+            )));
+        }
+
+        match self.set_views(&views) {
+            Ok(_) => {
+                writeln!(self.out(), "Deleted view: '{}'", name.blue())?;
+                Ok(RunReturn::ViewDeletion(true))
+            }
+            Err(e) => Err(RtcError::Deletion(format!("Error: {}", e))),
+        }
+    }
+
+    /// Returns the query saved under the view `name`
+    pub fn get_view(&self, name: &str) -> RtcResult<String> {
+        self.views()
+            .remove(name)
+            .ok_or(RtcError::ItemNotFound(format!(
+                "View '{}' does not exist.",
+                name
+            )))
+    }
+
+    pub fn list_views(&self) -> RtcResult<RunReturn> {
+        writeln!(self.out(), "Running list views")?;
+
+        let views = self.views();
+        if views.is_empty() {
+            writeln!(self.out(), "Found no views")?;
+        } else {
+            writeln!(self.out(), "Found {} views:", views.len())?;
+        }
+
+        for (name, query) in &views {
+            writeln!(self.out(), "{}: {}", name.blue(), query.green())?;
+        }
+        Ok(RunReturn::Views(views))
+    }
+
+    fn views(&self) -> Views {
+        self.db_wrapper
+            .driver
+            .get::<Views>(self.db_wrapper.key_views())
+            .unwrap_or_default()
+    }
+
+    fn set_views(&mut self, views: &Views) -> Result<(), Box<dyn std::error::Error>> {
+        let key = self.db_wrapper.key_views().clone();
+        self.db_wrapper.driver.set(&key, views)
+    }
+}
@@ -10,6 +10,14 @@ pub enum RtcError {
     ItemNotFound(String),
     NoItems(String),
     Parse(String),
+    Io(String),
+    Migration(String),
+    /// The database file exists but can not be read
+    Load(String),
+    Batch(String),
+    Git(String),
+    /// Error of an operation run by a daemon, already described
+    Daemon(String),
 }
 
 impl fmt::Display for RtcError {
@@ -21,6 +29,20 @@ impl fmt::Display for RtcError {
             RtcError::ItemNotFound(msg) => write!(f, "Item not found: {}", msg),
             RtcError::NoItems(msg) => write!(f, "No items in database: {}", msg),
             RtcError::Parse(msg) => write!(f, "Failed to parse: {}", msg),
+            RtcError::Io(msg) => write!(f, "I/O error: {}", msg),
+            RtcError::Migration(msg) => write!(f, "Could not migrate database: {}", msg),
+            RtcError::Load(msg) => write!(f, "Could not load database: {}", msg),
+            RtcError::Batch(msg) => write!(f, "Batch stopped, nothing was changed: {}", msg),
+            RtcError::Git(msg) => write!(f, "Git failed: {}", msg),
+            RtcError::Daemon(msg) => write!(f, "{}", msg),
         }
     }
 }
+
+impl std::error::Error for RtcError {}
+
+impl From<std::io::Error> for RtcError {
+    fn from(e: std::io::Error) -> Self {
+        RtcError::Io(e.to_string())
+    }
+}
@@ -0,0 +1,330 @@
+//! Comma separated values, e.g. for spreadsheets, with one row per item and a configurable
+//! mapping of columns to fields
+
+use crate::{RtcError, RtcResult, Status, TodoItem};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use std::collections::BTreeSet;
+
+const DATE_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Field of [`TodoItem`] a column holds
+#[derive(Debug, Clone, PartialEq)]
+enum Field {
+    Id,
+    Name,
+    Status,
+    Priority,
+    Projects,
+    Tags,
+    Due,
+    Created,
+    Completed,
+    Modified,
+    Uuid,
+    /// Any other name is an extension
+    Extension(String),
+}
+
+impl Field {
+    /// Columns of an export without a mapping, followed by one for each extension
+    const DEFAULT: [Field; 11] = [
+        Field::Id,
+        Field::Name,
+        Field::Status,
+        Field::Priority,
+        Field::Projects,
+        Field::Tags,
+        Field::Due,
+        Field::Created,
+        Field::Completed,
+        Field::Modified,
+        Field::Uuid,
+    ];
+
+    fn parse(name: &str) -> Field {
+        match &name.trim().to_lowercase()[..] {
+            "id" => Field::Id,
+            "name" => Field::Name,
+            "status" => Field::Status,
+            "priority" => Field::Priority,
+            "projects" => Field::Projects,
+            "tags" => Field::Tags,
+            "due" => Field::Due,
+            "created" => Field::Created,
+            "completed" => Field::Completed,
+            "modified" => Field::Modified,
+            "uuid" => Field::Uuid,
+            _ => Field::Extension(String::from(name.trim())),
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            Field::Id => "id",
+            Field::Name => "name",
+            Field::Status => "status",
+            Field::Priority => "priority",
+            Field::Projects => "projects",
+            Field::Tags => "tags",
+            Field::Due => "due",
+            Field::Created => "created",
+            Field::Completed => "completed",
+            Field::Modified => "modified",
+            Field::Uuid => "uuid",
+            Field::Extension(key) => key,
+        }
+    }
+}
+
+/// A column with the header it has in the file
+#[derive(Debug, Clone, PartialEq)]
+struct Column {
+    header: String,
+    field: Field,
+}
+
+/// How items are laid out in the file
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvOptions {
+    /// All columns if empty
+    columns: Vec<Column>,
+    header: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            columns: vec![],
+            header: true,
+        }
+    }
+}
+
+impl CsvOptions {
+    /// Parses a mapping like `Task=name,Owner=owner,due`, where columns without `=` are named
+    /// after their field
+    pub fn with_columns(mut self, mapping: &str) -> RtcResult<CsvOptions> {
+        self.columns = mapping
+            .split(',')
+            .map(|column| {
+                let (header, field) = column.split_once('=').unwrap_or((column, column));
+                if field.trim().is_empty() {
+                    Err(RtcError::Parse(format!("Column '{}' has no field", column)))
+                } else {
+                    Ok(Column {
+                        header: String::from(header.trim()),
+                        field: Field::parse(field),
+                    })
+                }
+            })
+            .collect::<RtcResult<Vec<Column>>>()?;
+        Ok(self)
+    }
+
+    /// The file has no header row, so its columns are told apart by position alone
+    pub fn without_header(mut self) -> CsvOptions {
+        self.header = false;
+        self
+    }
+
+    fn export_columns(&self, items: &[TodoItem]) -> Vec<Column> {
+        if !self.columns.is_empty() {
+            return self.columns.clone();
+        }
+        let extensions = items
+            .iter()
+            .flat_map(|item| item.extensions.keys())
+            .collect::<BTreeSet<&String>>();
+        Field::DEFAULT
+            .iter()
+            .cloned()
+            .chain(extensions.into_iter().map(|k| Field::Extension(k.clone())))
+            .map(|field| Column {
+                header: String::from(field.name()),
+                field,
+            })
+            .collect()
+    }
+
+    /// Mapped columns by their header, or else every column by its header or position
+    fn import_columns(&self, headers: Option<&csv::StringRecord>) -> Vec<Option<Field>> {
+        match (headers, self.columns.is_empty()) {
+            (Some(headers), false) => headers
+                .iter()
+                .map(|h| {
+                    self.columns
+                        .iter()
+                        .find(|c| c.header.eq_ignore_ascii_case(h.trim()))
+                        .map(|c| c.field.clone())
+                })
+                .collect(),
+            (Some(headers), true) => headers.iter().map(|h| Some(Field::parse(h))).collect(),
+            (None, false) => self.columns.iter().map(|c| Some(c.field.clone())).collect(),
+            (None, true) => Field::DEFAULT.iter().cloned().map(Some).collect(),
+        }
+    }
+}
+
+pub fn export(items: &[TodoItem], options: &CsvOptions) -> RtcResult<String> {
+    let columns = options.export_columns(items);
+    let mut writer = csv::Writer::from_writer(vec![]);
+
+    if options.header {
+        writer
+            .write_record(columns.iter().map(|c| &c.header))
+            .map_err(to_io_error)?;
+    }
+    for item in items {
+        writer
+            .write_record(columns.iter().map(|c| export_field(item, &c.field)))
+            .map_err(to_io_error)?;
+    }
+
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| RtcError::Io(e.to_string()))?;
+    String::from_utf8(bytes).map_err(|e| RtcError::Io(e.to_string()))
+}
+
+fn export_field(item: &TodoItem, field: &Field) -> String {
+    let format = |d: Option<&DateTime<Utc>>| {
+        d.map(|d| d.format(DATE_TIME_FORMAT).to_string())
+            .unwrap_or_default()
+    };
+    match field {
+        Field::Id => item.id().to_string(),
+        Field::Name => item.name().clone(),
+        Field::Status => item.status.to_string().to_lowercase(),
+        Field::Priority => item.priority.map(String::from).unwrap_or_default(),
+        Field::Projects => item.projects.join(" "),
+        Field::Tags => item.tags.join(" "),
+        Field::Due => format(item.due.as_ref()),
+        Field::Created => format(Some(item.creation_date())),
+        Field::Completed => format(item.completed_at()),
+        Field::Modified => format(item.modified_at()),
+        Field::Uuid => item.uuid.clone().unwrap_or_default(),
+        Field::Extension(key) => item.extensions.get(key).cloned().unwrap_or_default(),
+    }
+}
+
+/// Reads one item per row. Items get the ID of their `id` column, or `0` if there is none.
+pub fn import(text: &str, options: &CsvOptions) -> RtcResult<Vec<TodoItem>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(options.header)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+
+    let headers = if options.header {
+        Some(reader.headers().map_err(to_parse_error)?.clone())
+    } else {
+        None
+    };
+    let columns = options.import_columns(headers.as_ref());
+
+    reader
+        .records()
+        .enumerate()
+        .map(|(i, record)| {
+            let record = record.map_err(to_parse_error)?;
+            import_item(&record, &columns)
+                .map_err(|e| RtcError::Parse(format!("CSV row {}: {}", i + 1, e)))
+        })
+        .collect()
+}
+
+fn import_item(record: &csv::StringRecord, columns: &[Option<Field>]) -> RtcResult<TodoItem> {
+    let mut id = 0;
+    let mut name = "";
+    let (mut created, mut completed, mut modified) = (None, None, None);
+    let mut item = TodoItem::new(0, "");
+
+    let values = columns.iter().zip(record.iter());
+    for (field, value) in values.filter_map(|(f, v)| f.as_ref().map(|f| (f, v.trim()))) {
+        if value.is_empty() {
+            continue;
+        }
+        match field {
+            Field::Id => {
+                id = value
+                    .parse::<usize>()
+                    .map_err(|_| RtcError::Parse(format!("ID '{}'", value)))?
+            }
+            Field::Name => name = value,
+            Field::Status => item.status = parse_status(value),
+            Field::Priority => item.priority = value.chars().next().map(|c| c.to_ascii_uppercase()),
+            Field::Projects => item.projects = split_list(value),
+            Field::Tags => item.tags = split_list(value),
+            Field::Due => item.due = Some(parse_date(value)?),
+            Field::Created => created = Some(parse_date(value)?),
+            Field::Completed => completed = Some(parse_date(value)?),
+            Field::Modified => modified = Some(parse_date(value)?),
+            Field::Uuid => item.uuid = Some(String::from(value)),
+            Field::Extension(key) => {
+                item.extensions.insert(key.clone(), String::from(value));
+            }
+        }
+    }
+
+    if name.is_empty() {
+        return Err(RtcError::Parse(String::from("missing name")));
+    }
+    let completed = match item.status {
+        Status::Done => completed,
+        Status::Open => None,
+    };
+    // The name is only known once all columns are read
+    let mut imported = TodoItem::new(id, name);
+    imported.set_dates(created.unwrap_or_else(Utc::now), completed, modified);
+    imported.status = item.status;
+    imported.priority = item.priority;
+    imported.projects = item.projects;
+    imported.tags = item.tags;
+    imported.due = item.due;
+    imported.uuid = item.uuid;
+    imported.extensions = item.extensions;
+    Ok(imported)
+}
+
+/// Spreadsheets tend to mark done items in many ways
+fn parse_status(value: &str) -> Status {
+    match &value.to_lowercase()[..] {
+        "done" | "x" | "yes" | "true" | "completed" | "closed" => Status::Done,
+        _ => Status::Open,
+    }
+}
+
+/// Lists are separated by spaces or commas
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(&[' ', ','][..])
+        .filter(|v| !v.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Parses dates with and without time of day as UTC, as well as RFC 3339
+fn parse_date(value: &str) -> RtcResult<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|d| d.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(value, DATE_TIME_FORMAT)
+                .ok()
+                .or_else(|| {
+                    NaiveDate::parse_from_str(value, DATE_FORMAT)
+                        .ok()
+                        .and_then(|d| d.and_hms_opt(0, 0, 0))
+                })
+                .map(|d| Utc.from_utc_datetime(&d))
+        })
+        .ok_or_else(|| RtcError::Parse(format!("date '{}'", value)))
+}
+
+fn to_io_error(e: csv::Error) -> RtcError {
+    RtcError::Io(e.to_string())
+}
+
+fn to_parse_error(e: csv::Error) -> RtcError {
+    RtcError::Parse(format!("CSV {}", e))
+}
@@ -0,0 +1,280 @@
+//! iCalendar (RFC 5545) files holding one `VTODO` component per item
+
+use crate::{RtcError, RtcResult, Status, TodoItem};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+const DATE_TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+/// Date-times without `Z` are in local or a given time zone
+const LOCAL_DATE_TIME_FORMAT: &str = "%Y%m%dT%H%M%S";
+const DATE_FORMAT: &str = "%Y%m%d";
+/// Lines longer than this many bytes are folded
+const MAX_LINE_LENGTH: usize = 75;
+/// Projects have no iCalendar property of their own
+const PROJECTS_PROPERTY: &str = "X-RTC-PROJECTS";
+
+pub fn export(items: &[TodoItem]) -> String {
+    let mut lines = vec![
+        String::from("BEGIN:VCALENDAR"),
+        String::from("VERSION:2.0"),
+        String::from("PRODID:-//rust-todo-cli//EN"),
+    ];
+    for item in items {
+        lines.extend(export_item(item));
+    }
+    lines.push(String::from("END:VCALENDAR"));
+
+    lines.iter().map(|line| fold(line) + "\r\n").collect()
+}
+
+fn export_item(item: &TodoItem) -> Vec<String> {
+    let format = |d: &DateTime<Utc>| d.format(DATE_TIME_FORMAT).to_string();
+    let mut lines = vec![String::from("BEGIN:VTODO")];
+
+    // Items without a uuid still need a UID that stays the same across exports
+    let uid = match &item.uuid {
+        Some(u) => u.clone(),
+        None => format!(
+            "rtc-{}-{}@rust-todo-cli",
+            item.id(),
+            item.creation_date().timestamp_millis()
+        ),
+    };
+    lines.push(format!("UID:{}", escape(&uid)));
+    lines.push(format!("DTSTAMP:{}", format(item.last_modified())));
+    lines.push(format!("SUMMARY:{}", escape(item.name())));
+    lines.push(format!(
+        "STATUS:{}",
+        match item.status {
+            Status::Open => "NEEDS-ACTION",
+            Status::Done => "COMPLETED",
+        }
+    ));
+    lines.push(format!("CREATED:{}", format(item.creation_date())));
+    if let Some(m) = item.modified_at() {
+        lines.push(format!("LAST-MODIFIED:{}", format(m)));
+    }
+    if let Some(c) = item.completed_at() {
+        lines.push(format!("COMPLETED:{}", format(c)));
+    }
+    if let Some(d) = &item.due {
+        lines.push(format!("DUE:{}", format(d)));
+    }
+    if let Some(p) = item.priority {
+        lines.push(format!("PRIORITY:{}", priority_to_number(p)));
+    }
+    if !item.tags.is_empty() {
+        lines.push(format!("CATEGORIES:{}", escape_list(&item.tags)));
+    }
+    if !item.projects.is_empty() {
+        lines.push(format!(
+            "{}:{}",
+            PROJECTS_PROPERTY,
+            escape_list(&item.projects)
+        ));
+    }
+
+    lines.push(String::from("END:VTODO"));
+    lines
+}
+
+/// `A` is the highest priority, `1`, and `I` and below are the lowest, `9`
+fn priority_to_number(priority: char) -> u32 {
+    (priority as u32).saturating_sub('A' as u32).min(8) + 1
+}
+
+fn priority_from_number(number: u32) -> Option<char> {
+    match number {
+        // 0 means undefined
+        1..=9 => std::char::from_u32('A' as u32 + number - 1),
+        _ => None,
+    }
+}
+
+/// Splits lines longer than [`MAX_LINE_LENGTH`] bytes, continuing them after a space
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > MAX_LINE_LENGTH {
+            folded.push_str("\r\n ");
+            // The space counts towards the length of the continued line
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+fn escape_list(values: &[String]) -> String {
+    values
+        .iter()
+        .map(|v| escape(v))
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(escaped) => unescaped.push(escaped),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Splits a list value at commas that are not escaped
+fn unescape_list(value: &str) -> Vec<String> {
+    let mut values = vec![];
+    let mut current = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                current.push(c);
+                current.extend(chars.next());
+            }
+            ',' => values.push(unescape(&std::mem::take(&mut current))),
+            _ => current.push(c),
+        }
+    }
+    values.push(unescape(&current));
+    values.retain(|v| !v.is_empty());
+    values
+}
+
+/// A content line like `DUE;VALUE=DATE:20210305`
+struct Property {
+    name: String,
+    value: String,
+}
+
+/// Reads the `VTODO` components, ignoring everything else.
+/// Times in other time zones than UTC are read as if they were in UTC.
+pub fn import(text: &str) -> RtcResult<Vec<TodoItem>> {
+    let mut items = vec![];
+    let mut todo: Option<Vec<Property>> = None;
+    // Components nested in a VTODO, e.g. VALARM, have properties of their own
+    let mut nested = 0;
+
+    for line in unfold(text) {
+        let property = parse_line(&line)?;
+        match (&property.name[..], todo.as_mut()) {
+            ("BEGIN", None) if property.value.eq_ignore_ascii_case("VTODO") => todo = Some(vec![]),
+            ("BEGIN", Some(_)) => nested += 1,
+            ("END", Some(_)) if nested > 0 => nested -= 1,
+            ("END", Some(_)) => items.push(import_item(todo.take().unwrap_or_default())?),
+            (_, Some(properties)) if nested == 0 => properties.push(property),
+            _ => (),
+        }
+    }
+
+    if todo.is_some() {
+        return Err(RtcError::Parse(String::from("VTODO without END")));
+    }
+    Ok(items)
+}
+
+fn import_item(properties: Vec<Property>) -> RtcResult<TodoItem> {
+    let find = |name: &str| {
+        properties
+            .iter()
+            .find(|p| p.name == name)
+            .map(|p| &p.value[..])
+    };
+    let find_date = |name: &str| find(name).map(parse_date).transpose();
+
+    let mut item = TodoItem::new(0, &unescape(find("SUMMARY").unwrap_or_default()));
+    item.status = match find("STATUS") {
+        Some(s) if s.eq_ignore_ascii_case("COMPLETED") => Status::Done,
+        _ => Status::Open,
+    };
+    let completed_at = match item.status {
+        Status::Done => find_date("COMPLETED")?,
+        Status::Open => None,
+    };
+    item.set_dates(
+        find_date("CREATED")?.unwrap_or_else(Utc::now),
+        completed_at,
+        find_date("LAST-MODIFIED")?,
+    );
+    item.due = find_date("DUE")?;
+    item.priority = find("PRIORITY")
+        .and_then(|p| p.trim().parse::<u32>().ok())
+        .and_then(priority_from_number);
+    item.uuid = find("UID").map(unescape);
+
+    // Lists may also be split across several properties
+    for property in &properties {
+        match &property.name[..] {
+            "CATEGORIES" => item.tags.extend(unescape_list(&property.value)),
+            PROJECTS_PROPERTY => item.projects.extend(unescape_list(&property.value)),
+            _ => (),
+        }
+    }
+
+    Ok(item)
+}
+
+/// Joins lines that were folded, i.e. continued on the next line after a space or tab
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = vec![];
+    for line in text.lines() {
+        match (line.strip_prefix(&[' ', '\t'][..]), lines.last_mut()) {
+            (Some(continued), Some(last)) => last.push_str(continued),
+            _ if line.trim().is_empty() => (),
+            _ => lines.push(String::from(line)),
+        }
+    }
+    lines
+}
+
+fn parse_line(line: &str) -> RtcResult<Property> {
+    // Parameters may hold quoted colons, e.g. ALTREP="http://..."
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(i),
+        _ => None,
+    });
+    let colon =
+        colon.ok_or_else(|| RtcError::Parse(format!("iCalendar line '{}', missing ':'", line)))?;
+
+    let name = line[..colon].split(';').next().unwrap_or_default();
+    Ok(Property {
+        name: name.to_uppercase(),
+        value: String::from(&line[colon + 1..]),
+    })
+}
+
+/// Parses UTC and local date-times as well as dates
+fn parse_date(value: &str) -> RtcResult<DateTime<Utc>> {
+    let value = value.trim();
+    NaiveDateTime::parse_from_str(value.trim_end_matches('Z'), LOCAL_DATE_TIME_FORMAT)
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, DATE_FORMAT)
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        })
+        .map(|d| Utc.from_utc_datetime(&d))
+        .ok_or_else(|| RtcError::Parse(format!("iCalendar date '{}'", value)))
+}
@@ -0,0 +1,179 @@
+//! GitHub flavored Markdown task lists, e.g. for issues and pull requests:
+//! `- [x] (A) name +project #tag due:2021-03-05 key:value`
+
+use super::todotxt::{parse_date, parse_extension, parse_priority, DATE_FORMAT, DUE_KEY, UUID_KEY};
+use crate::{RtcError, RtcResult, Status, TodoItem};
+use chrono::Utc;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// Headings that exported items are listed under
+#[derive(Debug, Clone, Copy)]
+pub enum GroupBy {
+    /// Items are listed under their first tag only, so that they are not imported twice
+    Tag,
+    Status,
+    Due,
+}
+
+impl GroupBy {
+    /// Names accepted by `--group-by`
+    pub const NAMES: [&'static str; 3] = ["tag", "status", "due"];
+
+    /// Heading of the group `item` belongs to, and the key the groups are ordered by
+    fn group(self, item: &TodoItem) -> (String, String) {
+        match self {
+            GroupBy::Tag => match item.tags.first() {
+                Some(t) => (format!("0{}", t.to_lowercase()), t.clone()),
+                None => (String::from("1"), String::from("Untagged")),
+            },
+            GroupBy::Status => match item.status {
+                Status::Open => (String::from("0"), String::from("Open")),
+                Status::Done => (String::from("1"), String::from("Done")),
+            },
+            GroupBy::Due => match item.due {
+                Some(d) => {
+                    let date = d.format(DATE_FORMAT).to_string();
+                    (format!("0{}", date), date)
+                }
+                None => (String::from("1"), String::from("No due date")),
+            },
+        }
+    }
+}
+
+impl FromStr for GroupBy {
+    type Err = RtcError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_lowercase()[..] {
+            "tag" => Ok(GroupBy::Tag),
+            "status" => Ok(GroupBy::Status),
+            "due" => Ok(GroupBy::Due),
+            _ => Err(RtcError::Parse(format!(
+                "Unknown grouping '{}', expected one of {}",
+                s,
+                GroupBy::NAMES.join(", ")
+            ))),
+        }
+    }
+}
+
+pub fn export(items: &[TodoItem], group_by: Option<GroupBy>) -> String {
+    let group_by = match group_by {
+        Some(g) => g,
+        None => return items.iter().map(|item| export_item(item) + "\n").collect(),
+    };
+
+    let mut groups: BTreeMap<String, (String, Vec<&TodoItem>)> = BTreeMap::new();
+    for item in items {
+        let (key, heading) = group_by.group(item);
+        groups
+            .entry(key)
+            .or_insert_with(|| (heading, vec![]))
+            .1
+            .push(item);
+    }
+
+    groups
+        .values()
+        .map(|(heading, items)| {
+            let lines: String = items.iter().map(|item| export_item(item) + "\n").collect();
+            format!("## {}\n\n{}", heading, lines)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn export_item(item: &TodoItem) -> String {
+    let mut parts = vec![String::from(match item.status {
+        Status::Open => "- [ ]",
+        Status::Done => "- [x]",
+    })];
+
+    parts.extend(item.priority.map(|p| format!("({})", p)));
+    parts.push(item.name().clone());
+    parts.extend(item.projects.iter().map(|p| format!("+{}", p)));
+    // `@` would mention GitHub users
+    parts.extend(item.tags.iter().map(|t| format!("#{}", t)));
+    parts.extend(
+        item.due
+            .map(|d| format!("{}:{}", DUE_KEY, d.format(DATE_FORMAT))),
+    );
+    parts.extend(item.extensions.iter().map(|(k, v)| format!("{}:{}", k, v)));
+    parts.extend(item.uuid.as_ref().map(|u| format!("{}:{}", UUID_KEY, u)));
+
+    parts.retain(|p| !p.is_empty());
+    parts.join(" ")
+}
+
+/// Reads the task list items, including nested ones. Headings and other text are skipped.
+pub fn import(text: &str) -> RtcResult<Vec<TodoItem>> {
+    Ok(text.lines().filter_map(import_item).collect())
+}
+
+fn import_item(line: &str) -> Option<TodoItem> {
+    let (done, rest) = parse_task(line)?;
+
+    let mut tokens = rest.split_whitespace().peekable();
+    let priority = tokens
+        .next_if(|t| parse_priority(t).is_some())
+        .and_then(parse_priority);
+
+    let mut name = vec![];
+    let mut projects = vec![];
+    let mut tags = vec![];
+    let mut extensions = BTreeMap::new();
+    for token in tokens {
+        if let Some(project) = token.strip_prefix('+').filter(|p| !p.is_empty()) {
+            projects.push(String::from(project));
+        } else if let Some(tag) = token.strip_prefix('#').filter(|t| is_tag(t)) {
+            tags.push(String::from(tag));
+        } else if let Some((key, value)) = parse_extension(token) {
+            extensions.insert(String::from(key), String::from(value));
+        } else {
+            name.push(token);
+        }
+    }
+
+    let mut item = TodoItem::new(0, &name.join(" "));
+    item.status = if done { Status::Done } else { Status::Open };
+    // Task lists have no dates other than the due date
+    let now = Utc::now();
+    item.set_dates(now, if done { Some(now) } else { None }, None);
+    item.priority = priority;
+    // Due dates that are not dates stay extensions
+    item.due = extensions.get(DUE_KEY).and_then(|d| parse_date(d));
+    if item.due.is_some() {
+        extensions.remove(DUE_KEY);
+    }
+    item.uuid = extensions.remove(UUID_KEY);
+    item.projects = projects;
+    item.tags = tags;
+    item.extensions = extensions;
+    Some(item)
+}
+
+/// Splits `- [x] rest` into whether the task is done and the rest.
+/// Ordered list items like `1. [ ] rest` are tasks as well.
+fn parse_task(line: &str) -> Option<(bool, &str)> {
+    let line = line.trim_start();
+    let marker = match line.strip_prefix(&['-', '*', '+'][..]) {
+        Some(rest) => rest,
+        None => line
+            .trim_start_matches(|c: char| c.is_ascii_digit())
+            .strip_prefix(&['.', ')'][..])
+            .filter(|_| line.starts_with(|c: char| c.is_ascii_digit()))?,
+    };
+
+    let rest = marker.strip_prefix(' ')?.trim_start();
+    match rest.get(..3)? {
+        "[ ]" => Some((false, &rest[3..])),
+        "[x]" | "[X]" => Some((true, &rest[3..])),
+        _ => None,
+    }
+}
+
+/// `#123` refers to an issue rather than being a tag
+fn is_tag(tag: &str) -> bool {
+    !tag.is_empty() && !tag.chars().all(|c| c.is_ascii_digit())
+}
@@ -0,0 +1,112 @@
+mod csv;
+mod ical;
+mod markdown;
+mod org;
+mod taskwarrior;
+mod todotxt;
+
+pub use self::csv::CsvOptions;
+pub use markdown::GroupBy;
+
+use crate::{RtcError, RtcResult, TodoItem};
+use std::fmt;
+use std::str::FromStr;
+
+/// File formats that items can be exported to and imported from
+#[derive(Debug, Clone)]
+pub enum Format {
+    TodoTxt,
+    Taskwarrior,
+    ICalendar,
+    /// Grouped under headings if given
+    Markdown(Option<GroupBy>),
+    Csv(CsvOptions),
+    Org,
+}
+
+impl Format {
+    /// Names accepted by `--format`
+    pub const NAMES: [&'static str; 6] =
+        ["todotxt", "taskwarrior", "ical", "markdown", "csv", "org"];
+
+    /// Groups exported items under headings, in formats that support them
+    pub fn grouped_by(self, group_by: GroupBy) -> Format {
+        match self {
+            Format::Markdown(_) => Format::Markdown(Some(group_by)),
+            other => other,
+        }
+    }
+
+    /// Maps columns to fields, in formats that have columns
+    pub fn with_columns(self, mapping: &str) -> RtcResult<Format> {
+        match self {
+            Format::Csv(options) => Ok(Format::Csv(options.with_columns(mapping)?)),
+            other => Ok(other),
+        }
+    }
+
+    /// Leaves out the header row, in formats that have one
+    pub fn without_header(self) -> Format {
+        match self {
+            Format::Csv(options) => Format::Csv(options.without_header()),
+            other => other,
+        }
+    }
+
+    pub fn export(&self, items: &[TodoItem]) -> RtcResult<String> {
+        Ok(match self {
+            Format::TodoTxt => todotxt::export(items),
+            Format::Taskwarrior => taskwarrior::export(items),
+            Format::ICalendar => ical::export(items),
+            Format::Markdown(group_by) => markdown::export(items, *group_by),
+            Format::Csv(options) => csv::export(items, options)?,
+            Format::Org => org::export(items),
+        })
+    }
+
+    /// Parses items from `text`. Their IDs are `0` unless the file holds them, as CSV and Org
+    /// files may.
+    pub fn import(&self, text: &str) -> RtcResult<Vec<TodoItem>> {
+        match self {
+            Format::TodoTxt => todotxt::import(text),
+            Format::Taskwarrior => taskwarrior::import(text),
+            Format::ICalendar => ical::import(text),
+            Format::Markdown(_) => markdown::import(text),
+            Format::Csv(options) => csv::import(text, options),
+            Format::Org => org::import(text),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = RtcError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_lowercase()[..] {
+            "todotxt" | "todo.txt" => Ok(Format::TodoTxt),
+            "taskwarrior" | "tw" => Ok(Format::Taskwarrior),
+            "ical" | "ics" | "icalendar" => Ok(Format::ICalendar),
+            "markdown" | "md" => Ok(Format::Markdown(None)),
+            "csv" => Ok(Format::Csv(CsvOptions::default())),
+            "org" | "orgmode" => Ok(Format::Org),
+            _ => Err(RtcError::Parse(format!(
+                "Unknown format '{}', expected one of {}",
+                s,
+                Format::NAMES.join(", ")
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Format::TodoTxt => "todotxt",
+            Format::Taskwarrior => "taskwarrior",
+            Format::ICalendar => "ical",
+            Format::Markdown(_) => "markdown",
+            Format::Csv(_) => "csv",
+            Format::Org => "org",
+        };
+        write!(f, "{}", name)
+    }
+}
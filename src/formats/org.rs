@@ -0,0 +1,306 @@
+//! [Org mode](https://orgmode.org) outlines with a headline per item:
+//! `* TODO [#A] name :tag:`, followed by planning and a property drawer holding the ID
+
+use crate::{RtcError, RtcResult, Status, TodoItem};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc};
+use std::collections::BTreeMap;
+
+const DATE_FORMAT: &str = "%Y-%m-%d %a";
+const DATE_TIME_FORMAT: &str = "%Y-%m-%d %a %H:%M";
+/// Property holding the ID of an item in the database
+const ID_PROPERTY: &str = "RTC_ID";
+/// Org uses `ID` for globally unique IDs
+const UUID_PROPERTY: &str = "ID";
+const CREATED_PROPERTY: &str = "CREATED";
+const MODIFIED_PROPERTY: &str = "MODIFIED";
+const PROJECTS_PROPERTY: &str = "PROJECTS";
+/// Items have no scheduled date of their own, so it is kept as an extension
+const SCHEDULED_KEY: &str = "scheduled";
+
+pub fn export(items: &[TodoItem]) -> String {
+    items.iter().map(export_item).collect()
+}
+
+fn export_item(item: &TodoItem) -> String {
+    let mut headline = vec![String::from(match item.status {
+        Status::Open => "* TODO",
+        Status::Done => "* DONE",
+    })];
+    headline.extend(item.priority.map(|p| format!("[#{}]", p)));
+    headline.push(item.name().clone());
+    if !item.tags.is_empty() {
+        headline.push(format!(":{}:", item.tags.join(":")));
+    }
+    let mut lines = vec![headline.join(" ")];
+
+    let mut planning = vec![];
+    if let Some(c) = item.completed_at() {
+        planning.push(format!("CLOSED: [{}]", c.format(DATE_TIME_FORMAT)));
+    }
+    let scheduled = item
+        .extensions
+        .get(SCHEDULED_KEY)
+        .and_then(|s| parse_date(s));
+    if let Some(s) = scheduled {
+        planning.push(format!("SCHEDULED: <{}>", format_date(&s)));
+    }
+    if let Some(d) = &item.due {
+        planning.push(format!("DEADLINE: <{}>", format_date(d)));
+    }
+    if !planning.is_empty() {
+        lines.push(format!("  {}", planning.join(" ")));
+    }
+
+    let mut properties = vec![(ID_PROPERTY, item.id().to_string())];
+    properties.push((
+        CREATED_PROPERTY,
+        format!("[{}]", item.creation_date().format(DATE_TIME_FORMAT)),
+    ));
+    if let Some(m) = item.modified_at() {
+        properties.push((
+            MODIFIED_PROPERTY,
+            format!("[{}]", m.format(DATE_TIME_FORMAT)),
+        ));
+    }
+    if !item.projects.is_empty() {
+        properties.push((PROJECTS_PROPERTY, item.projects.join(" ")));
+    }
+    if let Some(u) = &item.uuid {
+        properties.push((UUID_PROPERTY, u.clone()));
+    }
+    let extensions = item
+        .extensions
+        .iter()
+        .filter(|(k, _)| scheduled.is_none() || k.as_str() != SCHEDULED_KEY)
+        .map(|(k, v)| (k.to_uppercase(), v.clone()))
+        .collect::<Vec<(String, String)>>();
+
+    lines.push(String::from("  :PROPERTIES:"));
+    for (key, value) in properties {
+        lines.push(format!("  :{}: {}", key, value));
+    }
+    for (key, value) in extensions {
+        lines.push(format!("  :{}: {}", key, value));
+    }
+    lines.push(String::from("  :END:"));
+
+    lines.iter().map(|line| format!("{}\n", line)).collect()
+}
+
+/// Dates without a time of day are shown as such
+fn format_date(date: &DateTime<Utc>) -> String {
+    if date.num_seconds_from_midnight() == 0 {
+        date.format(DATE_FORMAT).to_string()
+    } else {
+        date.format(DATE_TIME_FORMAT).to_string()
+    }
+}
+
+/// A headline with a `TODO` or `DONE` keyword, and what follows it up to the next headline
+struct Entry<'a> {
+    headline: &'a str,
+    done: bool,
+    body: Vec<&'a str>,
+}
+
+/// Reads the headlines with a `TODO` or `DONE` keyword at any level. Items get the ID in their
+/// `RTC_ID` property, or `0` if there is none.
+pub fn import(text: &str) -> RtcResult<Vec<TodoItem>> {
+    let mut entries: Vec<Entry> = vec![];
+    let mut in_entry = false;
+
+    for line in text.lines() {
+        match parse_headline(line) {
+            Some((done, headline)) => {
+                entries.push(Entry {
+                    headline,
+                    done,
+                    body: vec![],
+                });
+                in_entry = true;
+            }
+            // Headlines without a keyword are sections, not items
+            None if is_headline(line) => in_entry = false,
+            None if in_entry => {
+                if let Some(entry) = entries.last_mut() {
+                    entry.body.push(line.trim());
+                }
+            }
+            None => (),
+        }
+    }
+
+    entries.iter().map(import_item).collect()
+}
+
+fn is_headline(line: &str) -> bool {
+    let stars = line.len() - line.trim_start_matches('*').len();
+    stars > 0 && line[stars..].starts_with(' ')
+}
+
+/// Splits `** TODO rest` into whether it is done and the rest
+fn parse_headline(line: &str) -> Option<(bool, &str)> {
+    if !is_headline(line) {
+        return None;
+    }
+    let rest = line.trim_start_matches('*').trim_start();
+    let (keyword, rest) = rest.split_once(' ').unwrap_or((rest, ""));
+    match keyword {
+        "TODO" => Some((false, rest.trim())),
+        "DONE" => Some((true, rest.trim())),
+        _ => None,
+    }
+}
+
+fn import_item(entry: &Entry) -> RtcResult<TodoItem> {
+    let mut title = entry.headline;
+    let priority = title
+        .strip_prefix("[#")
+        .and_then(|t| t.split_once(']'))
+        .and_then(|(p, rest)| {
+            let mut chars = p.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c.is_ascii_uppercase() => {
+                    title = rest.trim_start();
+                    Some(c)
+                }
+                _ => None,
+            }
+        });
+
+    // Tags end the headline, as in `name :tag1:tag2:`
+    let mut tags = vec![];
+    if let Some((name, last)) = title.rsplit_once(' ') {
+        if last.len() > 1 && last.starts_with(':') && last.ends_with(':') {
+            tags = last
+                .split(':')
+                .filter(|t| !t.is_empty())
+                .map(String::from)
+                .collect();
+            title = name.trim_end();
+        }
+    }
+
+    let mut planning = BTreeMap::new();
+    let mut properties = BTreeMap::new();
+    let mut in_drawer = false;
+    for line in &entry.body {
+        match *line {
+            ":PROPERTIES:" => in_drawer = true,
+            ":END:" => in_drawer = false,
+            _ if in_drawer => {
+                if let Some((key, value)) = line
+                    .strip_prefix(':')
+                    .and_then(|l| l.split_once(':'))
+                    .filter(|(key, _)| !key.is_empty())
+                {
+                    properties.insert(key.to_uppercase(), value.trim());
+                }
+            }
+            _ => parse_planning(line, &mut planning),
+        }
+    }
+
+    let id = match properties.remove(ID_PROPERTY) {
+        Some(id) => id
+            .parse::<usize>()
+            .map_err(|_| RtcError::Parse(format!("Org {} '{}'", ID_PROPERTY, id)))?,
+        None => 0,
+    };
+    let mut item = TodoItem::new(id, title);
+    item.status = if entry.done {
+        Status::Done
+    } else {
+        Status::Open
+    };
+    let completed_at = match item.status {
+        Status::Done => planning
+            .get("CLOSED")
+            .copied()
+            .map(parse_timestamp)
+            .transpose()?,
+        Status::Open => None,
+    };
+    item.set_dates(
+        properties
+            .remove(CREATED_PROPERTY)
+            .map(parse_timestamp)
+            .transpose()?
+            .unwrap_or_else(Utc::now),
+        completed_at,
+        properties
+            .remove(MODIFIED_PROPERTY)
+            .map(parse_timestamp)
+            .transpose()?,
+    );
+    item.priority = priority;
+    item.tags = tags;
+    item.due = planning
+        .get("DEADLINE")
+        .copied()
+        .map(parse_timestamp)
+        .transpose()?;
+    item.projects = properties
+        .remove(PROJECTS_PROPERTY)
+        .map(|p| p.split_whitespace().map(String::from).collect())
+        .unwrap_or_default();
+    item.uuid = properties.remove(UUID_PROPERTY).map(String::from);
+    if let Some(s) = planning.get("SCHEDULED") {
+        let date = parse_timestamp(s)?;
+        item.extensions.insert(
+            String::from(SCHEDULED_KEY),
+            date.format("%Y-%m-%d").to_string(),
+        );
+    }
+    item.extensions.extend(
+        properties
+            .into_iter()
+            .map(|(k, v)| (k.to_lowercase(), String::from(v))),
+    );
+    Ok(item)
+}
+
+/// Collects `KEYWORD: <timestamp>` pairs of a planning line
+fn parse_planning<'a>(line: &'a str, planning: &mut BTreeMap<&'a str, &'a str>) {
+    for keyword in &["SCHEDULED", "DEADLINE", "CLOSED"] {
+        let prefix = format!("{}:", keyword);
+        if let Some(start) = line.find(&prefix) {
+            let rest = line[start + prefix.len()..].trim_start();
+            let end = rest.find(&['>', ']'][..]).map_or(rest.len(), |i| i + 1);
+            planning.insert(keyword, &rest[..end]);
+        }
+    }
+}
+
+/// Parses `<2021-03-05 Fri>`, `[2021-03-05 Fri 10:00]` and the like as UTC.
+/// Repeaters and warning periods are ignored.
+fn parse_timestamp(timestamp: &str) -> RtcResult<DateTime<Utc>> {
+    let inner = timestamp
+        .trim()
+        .trim_start_matches(&['<', '['][..])
+        .trim_end_matches(&['>', ']'][..]);
+    let mut parts = inner.split_whitespace();
+    let date = parts.next().and_then(parse_date);
+    // Time ranges like `10:00-11:00` start at their first time
+    let time = parts
+        .find(|p| p.contains(':'))
+        .and_then(|t| t.split('-').next());
+
+    let parsed = match (date, time) {
+        (Some(d), Some(t)) => NaiveDateTime::parse_from_str(
+            &format!("{} {}", d.format("%Y-%m-%d"), t),
+            "%Y-%m-%d %H:%M",
+        )
+        .ok()
+        .map(|d| Utc.from_utc_datetime(&d)),
+        (date, _) => date,
+    };
+    parsed.ok_or_else(|| RtcError::Parse(format!("Org timestamp '{}'", timestamp)))
+}
+
+fn parse_date(date: &str) -> Option<DateTime<Utc>> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|d| Utc.from_utc_datetime(&d))
+}
@@ -0,0 +1,168 @@
+//! Taskwarrior's JSON format, as written by `task export` and read by `task import`
+
+use crate::{RtcError, RtcResult, Status, TodoItem};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+const DATE_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// Attributes of a task. Attributes that are not listed are kept in `other`.
+#[derive(Debug, Serialize, Deserialize)]
+struct Task {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    uuid: Option<String>,
+    description: String,
+    status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    entry: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    end: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    modified: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    due: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    priority: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    project: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(flatten)]
+    other: BTreeMap<String, Value>,
+}
+
+/// Attributes Taskwarrior computes itself, which are not worth keeping
+const COMPUTED: [&str; 3] = ["id", "urgency", "mask"];
+
+/// Attributes with a field of their own in [`Task`]
+const NAMED: [&str; 10] = [
+    "uuid",
+    "description",
+    "status",
+    "entry",
+    "end",
+    "modified",
+    "due",
+    "priority",
+    "project",
+    "tags",
+];
+
+pub fn export(items: &[TodoItem]) -> String {
+    let tasks = items.iter().map(export_item).collect::<Vec<Task>>();
+    // Writing plain strings and dates can not fail
+    serde_json::to_string_pretty(&tasks).unwrap() + "\n"
+}
+
+fn export_item(item: &TodoItem) -> Task {
+    let format = |d: &DateTime<Utc>| d.format(DATE_FORMAT).to_string();
+
+    Task {
+        uuid: item.uuid.clone(),
+        description: item.name().clone(),
+        status: String::from(match item.status {
+            Status::Open => "pending",
+            Status::Done => "completed",
+        }),
+        entry: Some(format(item.creation_date())),
+        // Taskwarrior requires an end date for completed tasks
+        end: match item.status {
+            Status::Done => Some(format(item.completed_at().unwrap_or(item.last_modified()))),
+            Status::Open => None,
+        },
+        modified: item.modified_at().map(format),
+        due: item.due.as_ref().map(format),
+        priority: item.priority.map(|p| {
+            String::from(match p {
+                'A' => "H",
+                'B' => "M",
+                _ => "L",
+            })
+        }),
+        // Taskwarrior has a single project per task, so only the first one is kept
+        project: item.projects.first().cloned(),
+        tags: item.tags.clone(),
+        // Extensions named like other attributes, e.g. a `due` that is not a date, would repeat
+        // their keys
+        other: item
+            .extensions
+            .iter()
+            .filter(|(k, _)| !NAMED.contains(&&k[..]) && !COMPUTED.contains(&&k[..]))
+            .map(|(k, v)| (k.clone(), Value::String(v.clone())))
+            .collect(),
+    }
+}
+
+/// Reads a JSON array of tasks, or one task per line as older versions export.
+/// Deleted and recurring template tasks are skipped.
+pub fn import(text: &str) -> RtcResult<Vec<TodoItem>> {
+    let tasks = match serde_json::from_str::<Vec<Task>>(text) {
+        Ok(tasks) => tasks,
+        Err(_) => text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str::<Task>(line.trim().trim_end_matches(','))
+                    .map_err(|e| RtcError::Parse(format!("Taskwarrior task '{}', {}", line, e)))
+            })
+            .collect::<RtcResult<Vec<Task>>>()?,
+    };
+
+    tasks
+        .into_iter()
+        .filter(|task| task.status != "deleted" && task.status != "recurring")
+        .map(import_item)
+        .collect()
+}
+
+fn import_item(task: Task) -> RtcResult<TodoItem> {
+    let mut item = TodoItem::new(0, &task.description);
+
+    item.status = match &task.status[..] {
+        "completed" => Status::Done,
+        _ => Status::Open,
+    };
+    let completed_at = match item.status {
+        Status::Done => parse_date(&task.end)?,
+        Status::Open => None,
+    };
+    item.set_dates(
+        parse_date(&task.entry)?.unwrap_or_else(Utc::now),
+        completed_at,
+        parse_date(&task.modified)?,
+    );
+    item.due = parse_date(&task.due)?;
+    item.priority = match task.priority.as_deref() {
+        Some("H") => Some('A'),
+        Some("M") => Some('B'),
+        Some("L") => Some('C'),
+        _ => None,
+    };
+    item.projects = task.project.into_iter().collect();
+    item.tags = task.tags;
+    item.uuid = task.uuid;
+    // Other text attributes, e.g. user defined ones, become extensions
+    item.extensions = task
+        .other
+        .into_iter()
+        .filter(|(k, _)| !COMPUTED.contains(&&k[..]))
+        .filter_map(|(k, v)| match v {
+            Value::String(s) => Some((k, s)),
+            _ => None,
+        })
+        .collect();
+
+    Ok(item)
+}
+
+fn parse_date(date: &Option<String>) -> RtcResult<Option<DateTime<Utc>>> {
+    date.as_ref()
+        .map(|d| {
+            NaiveDateTime::parse_from_str(d, DATE_FORMAT)
+                .map(|d| Utc.from_utc_datetime(&d))
+                .map_err(|e| RtcError::Parse(format!("Taskwarrior date '{}', {}", d, e)))
+        })
+        .transpose()
+}
@@ -0,0 +1,152 @@
+//! The [todo.txt format](https://github.com/todotxt/todo.txt), one item per line:
+//! `x (A) 2021-03-02 2021-03-01 name +project @context key:value`
+
+use crate::{RtcResult, Status, TodoItem};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use std::collections::BTreeMap;
+
+pub(super) const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Completed items lose their `(A)` prefix in todo.txt, so their priority becomes an extension
+const PRIORITY_KEY: &str = "pri";
+/// Extensions that map to fields of [`TodoItem`]
+pub(super) const DUE_KEY: &str = "due";
+pub(super) const UUID_KEY: &str = "uuid";
+
+pub fn export(items: &[TodoItem]) -> String {
+    items.iter().map(|item| export_item(item) + "\n").collect()
+}
+
+fn export_item(item: &TodoItem) -> String {
+    let mut parts = vec![];
+
+    match item.status {
+        Status::Done => {
+            parts.push(String::from("x"));
+            // A completion date is only valid together with a creation date
+            let completed_at = item.completed_at().unwrap_or(item.creation_date());
+            parts.push(completed_at.format(DATE_FORMAT).to_string());
+        }
+        Status::Open => parts.extend(item.priority.map(|p| format!("({})", p))),
+    }
+    parts.push(item.creation_date().format(DATE_FORMAT).to_string());
+    parts.push(item.name().clone());
+
+    parts.extend(item.projects.iter().map(|p| format!("+{}", p)));
+    parts.extend(item.tags.iter().map(|t| format!("@{}", t)));
+    parts.extend(
+        item.due
+            .map(|d| format!("{}:{}", DUE_KEY, d.format(DATE_FORMAT))),
+    );
+    parts.extend(item.extensions.iter().map(|(k, v)| format!("{}:{}", k, v)));
+    parts.extend(item.uuid.as_ref().map(|u| format!("{}:{}", UUID_KEY, u)));
+    if let (Status::Done, Some(p)) = (&item.status, item.priority) {
+        parts.push(format!("{}:{}", PRIORITY_KEY, p));
+    }
+
+    parts.retain(|p| !p.is_empty());
+    parts.join(" ")
+}
+
+pub fn import(text: &str) -> RtcResult<Vec<TodoItem>> {
+    Ok(text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(import_item)
+        .collect())
+}
+
+fn import_item(line: &str) -> TodoItem {
+    let mut tokens = line.split_whitespace().peekable();
+
+    let done = tokens.next_if_eq(&"x").is_some();
+    let priority = if done {
+        None
+    } else {
+        tokens
+            .next_if(|t| parse_priority(t).is_some())
+            .and_then(parse_priority)
+    };
+
+    let first_date = tokens
+        .next_if(|t| parse_date(t).is_some())
+        .and_then(parse_date);
+    let second_date = tokens
+        .next_if(|t| parse_date(t).is_some())
+        .and_then(parse_date);
+    // Completed items list their completion date before their creation date
+    let (completed_at, creation_date) = match (done, first_date, second_date) {
+        (true, completed, created) => (completed, created.or(completed)),
+        (false, created, _) => (None, created),
+    };
+
+    let mut name = vec![];
+    let mut projects = vec![];
+    let mut tags = vec![];
+    let mut extensions = BTreeMap::new();
+    for token in tokens {
+        if let Some(project) = token.strip_prefix('+').filter(|p| !p.is_empty()) {
+            projects.push(String::from(project));
+        } else if let Some(tag) = token.strip_prefix('@').filter(|t| !t.is_empty()) {
+            tags.push(String::from(tag));
+        } else if let Some((key, value)) = parse_extension(token) {
+            extensions.insert(String::from(key), String::from(value));
+        } else {
+            name.push(token);
+        }
+    }
+
+    let mut item = TodoItem::new(0, &name.join(" "));
+    item.status = if done { Status::Done } else { Status::Open };
+    item.set_dates(creation_date.unwrap_or_else(Utc::now), completed_at, None);
+    item.priority = priority.or_else(|| {
+        extensions
+            .remove(PRIORITY_KEY)
+            .as_deref()
+            .and_then(parse_priority_letter)
+    });
+    // Due dates that are not dates stay extensions
+    item.due = extensions.get(DUE_KEY).and_then(|d| parse_date(d));
+    if item.due.is_some() {
+        extensions.remove(DUE_KEY);
+    }
+    item.uuid = extensions.remove(UUID_KEY);
+    item.projects = projects;
+    item.tags = tags;
+    item.extensions = extensions;
+    item
+}
+
+/// Parses `(A)`
+pub(super) fn parse_priority(token: &str) -> Option<char> {
+    token
+        .strip_prefix('(')
+        .and_then(|t| t.strip_suffix(')'))
+        .and_then(parse_priority_letter)
+}
+
+fn parse_priority_letter(s: &str) -> Option<char> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_uppercase() => Some(c),
+        _ => None,
+    }
+}
+
+pub(super) fn parse_date(token: &str) -> Option<DateTime<Utc>> {
+    NaiveDate::parse_from_str(token, DATE_FORMAT)
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|d| Utc.from_utc_datetime(&d))
+}
+
+/// Parses `key:value`, where neither side is empty or contains another colon.
+/// Values starting with `//` are left alone, as those are URLs.
+pub(super) fn parse_extension(token: &str) -> Option<(&str, &str)> {
+    let (key, value) = token.split_once(':')?;
+    if key.is_empty() || value.is_empty() || value.contains(':') || value.starts_with("//") {
+        None
+    } else {
+        Some((key, value))
+    }
+}
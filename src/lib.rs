@@ -1,19 +1,44 @@
-use crate::util::db::pickle_db_impl;
-use pickledb::PickleDb;
+use crate::util::code_scan;
+use crate::util::db::file_lock::FileLock;
+use crate::util::db::{migration, pickle_db_impl};
+use crate::util::git;
 
 // Re-exports
-pub use crate::crud::CrudHandler;
+pub use crate::crud::{BulkResult, CrudHandler, Filters, Output, Problem, RestoreMode, Views};
 pub use crate::errors::rtc_error::{Result as RtcResult, RtcError};
+pub use crate::formats::{CsvOptions, Format, GroupBy};
+#[cfg(unix)]
+pub use crate::rpc::{call as call_daemon, Daemon, Stdio};
+pub use crate::server::{ItemPatch, NewItem, Server};
+pub use crate::util::audit_log::{AuditAction, AuditEntry, AuditLog, FieldChange};
+pub use crate::util::cli::build_app;
 pub use crate::util::config::{Config, Operation};
+pub use crate::util::db::backup::{Backup, BackupRotation, BACKUP_VERSION};
 pub use crate::util::db::database_wrapper::{DatabaseWrapper, DbDriver};
-pub use crate::util::todo_item::{Status, TodoItem};
+pub use crate::util::db::migration::SCHEMA_VERSION;
+pub use crate::util::db::pickle_db_impl::PickleDbDriver;
+pub use crate::util::journal::{Change, JournalEntry};
+pub use crate::util::list_options::{ListOptions, SortField, SortKey};
+pub use crate::util::settings::Settings;
+pub use crate::util::todo_item::{Status, TodoItem, TrashedItem};
+pub use crate::util::transfer_options::{ExportOptions, ImportOptions};
 
+use chrono::Duration;
 use colored::*;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::iter;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 mod crud;
 mod errors;
+mod formats;
+#[cfg(unix)]
+mod rpc;
+mod server;
 mod util;
 
 #[derive(Debug)]
@@ -21,63 +46,661 @@ pub enum RunReturn {
     Addition(usize),
     Deletion(bool),
     Update(bool),
-    Filter(Vec<TodoItem>),
+    /// The requested page of matching items and the total number of matches
+    Filter(Vec<TodoItem>, usize),
     GetAll(Vec<TodoItem>),
+    ViewSaved(String),
+    ViewDeletion(bool),
+    Views(Views),
+    /// Operations that were reverted, most recent first
+    Undo(Vec<JournalEntry>),
+    /// Operations that were reapplied, oldest first
+    Redo(Vec<JournalEntry>),
+    /// Operations that undo or redo would revert, most recent first
+    Journal(Vec<JournalEntry>),
+    Trash(Vec<TrashedItem>),
+    Restoration(bool),
+    /// Number of items that were permanently deleted
+    TrashEmptied(usize),
+    /// IDs of the items that were moved into the archive
+    Archived(Vec<usize>),
+    /// Most recent changes, most recent last
+    Log(Vec<AuditEntry>),
+    /// The item, unless it was purged, and its history if requested
+    Show(Option<TodoItem>, Vec<AuditEntry>),
+    /// Problems found in the stored data
+    Doctor(Vec<Problem>),
+    /// Outcome per ID of a bulk operation, empty if it was not confirmed
+    Bulk(Vec<BulkResult>),
+    /// Outcome per line number of a batch, leaving out empty lines and comments
+    Batch(Vec<(usize, RtcResult<RunReturn>)>),
+    /// IDs of the items that were imported or, when syncing, updated
+    Imported(Vec<usize>),
+    /// The exported items, already written to a file unless exported to stdout
+    Exported(String),
+    /// File the backup was written to
+    BackedUp(PathBuf),
+    /// IDs of the items that were restored from a backup
+    BackupRestored(Vec<usize>),
+    /// IDs of the items whose comments were added, moved and removed since the last scan
+    Scanned(Vec<usize>, Vec<usize>, Vec<usize>),
+    /// IDs of the items a commit was attached to or closed
+    Linked(Vec<usize>),
+    /// The hook that was written
+    HooksInstalled(PathBuf),
+    DaemonStopped,
+    StdioClosed,
 }
 
 /// # Errors
 /// Will return propagated errors of type [`RtcError`]
 pub fn run(config: &Config) -> RtcResult<RunReturn> {
-    let db = pickle_db_impl::init_pickle_db(&config.database_file());
-    let db_wrapper = DatabaseWrapper::new(db);
-    let mut crud_handler = CrudHandler::<PickleDb>::new(db_wrapper);
+    run_with_output(config, || Box::new(io::stdout()))
+}
+
+/// Like [`run`], but with operations reporting to a writer made by `output` instead of stdout,
+/// e.g. [`io::sink`] where only the result is of interest
+///
+/// # Errors
+/// Will return propagated errors of type [`RtcError`]
+pub fn run_with_output(config: &Config, output: impl Fn() -> Output) -> RtcResult<RunReturn> {
+    if is_session(config.operation()) {
+        return run_session(config);
+    }
+
+    // Held until the end of the run, so that no other process loads the db in between
+    let _lock = FileLock::acquire(&config.lock_file())?;
+    let mut crud_handler = match open(config, output()) {
+        Ok(crud_handler) => crud_handler,
+        Err(e) => return open_failed(config, e, &mut output()),
+    };
+    run_on(&mut crud_handler, config)
+}
+
+/// Runs the command line `args` of `config` on a running daemon, if there is one that can run it
+#[cfg(unix)]
+pub fn run_via_daemon(config: &Config, args: &[String]) -> Option<RtcResult<()>> {
+    rpc::delegate(config, args)
+}
+
+#[cfg(not(unix))]
+pub fn run_via_daemon(_config: &Config, _args: &[String]) -> Option<RtcResult<()>> {
+    None
+}
+
+/// Operations that keep running and answer requests on the database
+fn is_session(operation: &Operation) -> bool {
+    matches!(
+        operation,
+        Operation::Daemon | Operation::StopDaemon | Operation::Stdio
+    )
+}
+
+#[cfg(unix)]
+fn run_session(config: &Config) -> RtcResult<RunReturn> {
+    match config.operation() {
+        Operation::StopDaemon => rpc::stop(config),
+        Operation::Stdio => rpc::serve_stdio(config),
+        _ => Daemon::bind(config)?.serve(),
+    }
+}
+
+#[cfg(not(unix))]
+fn run_session(_config: &Config) -> RtcResult<RunReturn> {
+    Err(RtcError::Io(String::from(
+        "The daemon and stdio mode need Unix",
+    )))
+}
+
+/// Loads the database of `config`, migrating it if needed, with operations reporting to
+/// `output`. The lock must be held.
+pub(crate) fn open(config: &Config, mut output: Output) -> RtcResult<CrudHandler<PickleDbDriver>> {
+    let db = pickle_db_impl::init_pickle_db(config.database_file(), &mut output)?;
+    // The archive is part of the database, not a database of its own to report
+    let archive = pickle_db_impl::init_pickle_db(&config.archive_file(), &mut io::sink())?;
+    let mut db_wrapper = DatabaseWrapper::new(db, archive);
+    migration::migrate(
+        &mut db_wrapper,
+        config.database_file(),
+        &config.backup_rotation(),
+        &mut output,
+    )?;
+    let audit_log = AuditLog::new(config.audit_log_file(), &config.user());
+    Ok(CrudHandler::<PickleDbDriver>::new(db_wrapper)
+        .with_audit_log(audit_log)
+        .with_output(output))
+}
+
+/// Doctor reports a database file that can not be loaded instead of failing on it
+pub(crate) fn open_failed(
+    config: &Config,
+    error: RtcError,
+    out: &mut dyn Write,
+) -> RtcResult<RunReturn> {
+    let fix = match (config.operation(), &error) {
+        (Operation::Doctor, RtcError::Load(_)) => false,
+        (Operation::DoctorFix, RtcError::Load(_)) => true,
+        _ => return Err(error),
+    };
+    // Either the database or the archive may be the file that failed
+    let unloadable = vec![config.database_file().to_string(), config.archive_file()]
+        .into_iter()
+        .filter_map(
+            |file| match pickle_db_impl::init_pickle_db(&file, &mut io::sink()) {
+                Err(RtcError::Load(message)) => Some((file, message)),
+                _ => None,
+            },
+        )
+        .collect::<Vec<(String, String)>>();
+    crud::doctor_file(out, &unloadable, fix, &config.quarantine_file())
+}
+
+/// Runs the operation of `config` on an open database. The lock must be held.
+pub(crate) fn run_on(
+    crud_handler: &mut CrudHandler<PickleDbDriver>,
+    config: &Config,
+) -> RtcResult<RunReturn> {
+    if let Some(reason) = backup_reason(config) {
+        let saved = config
+            .backup_rotation()
+            .save_backup(crud_handler.backup(), reason)?;
+        if let Some(file) = saved {
+            writeln!(crud_handler.out(), "Backed up db to {}", file.display())?;
+        }
+    }
+
+    // Doctor only changes data when asked to, and undoing a change must not make a new one
+    let skips_archive = matches!(
+        config.operation(),
+        Operation::Doctor | Operation::DoctorFix | Operation::Undo | Operation::Redo
+    );
+    if let (Some(days), false) = (config.settings().auto_archive_days, skips_archive) {
+        crud_handler.transaction(|crud_handler| crud_handler.auto_archive(days))?;
+    }
+
+    // Each run is all-or-nothing
+    crud_handler.transaction(|crud_handler| match config.operation() {
+        Operation::Batch => run_batch(crud_handler, config, false),
+        Operation::BatchContinueOnError => run_batch(crud_handler, config, true),
+        _ => run_operation(crud_handler, config),
+    })
+}
+
+/// Operations that may lose data are preceded by an automatic backup
+fn backup_reason(config: &Config) -> Option<&'static str> {
+    match config.operation() {
+        Operation::Delete if single_id(config.args()).is_none() => Some("delete"),
+        Operation::EmptyTrash => Some("empty-trash"),
+        Operation::Import if !config.import_options().dry_run => Some("import"),
+        Operation::RestoreBackup | Operation::MergeBackup => Some("restore"),
+        // Batches may run any of the above
+        Operation::Batch | Operation::BatchContinueOnError => Some("batch"),
+        _ => None,
+    }
+}
 
+fn run_operation<T: DbDriver>(
+    crud_handler: &mut CrudHandler<T>,
+    config: &Config,
+) -> RtcResult<RunReturn> {
     match config.operation() {
-        Operation::Add => run_add::<PickleDb>(&mut crud_handler, config.args()),
-        Operation::Delete => run_delete::<PickleDb>(&mut crud_handler, config.args()),
-        Operation::Update => run_update::<PickleDb>(&mut crud_handler, config.args()),
-        Operation::Filter => run_filter::<PickleDb>(&crud_handler, config.args()),
-        Operation::GetAll => run_getall::<PickleDb>(&crud_handler),
+        Operation::Add => run_add(crud_handler, config.args()),
+        Operation::Delete => run_delete(crud_handler, config.args(), config.assume_yes()),
+        Operation::Update => run_update(crud_handler, config.args(), config.assume_yes()),
+        Operation::Done => {
+            let mut args = config.args().clone();
+            args.push(Status::Done.to_string());
+            run_update(crud_handler, &args, config.assume_yes())
+        }
+        Operation::DoneWhere => {
+            run_done_where(crud_handler, &config.args()[0], config.assume_yes())
+        }
+        Operation::Import => run_import(crud_handler, config),
+        Operation::Export => run_export(crud_handler, config),
+        Operation::Backup => run_backup(crud_handler, config),
+        Operation::RestoreBackup => run_restore_backup(crud_handler, config, RestoreMode::Replace),
+        Operation::MergeBackup => run_restore_backup(crud_handler, config, RestoreMode::Merge),
+        Operation::Scan => run_scan(crud_handler, &config.path(&config.args()[0])),
+        Operation::Start => {
+            let branch = git::current_branch(&config.path(&config.args()[1]))?;
+            crud_handler.start(&config.args()[0], &branch)
+        }
+        Operation::InstallHooks => run_install_hooks(crud_handler, &config.path(&config.args()[0])),
+        Operation::PostCommit => {
+            let (hash, message) = git::last_commit(&config.path(&config.args()[0]))?;
+            crud_handler.link_commit(&hash, &git::parse_references(&message))
+        }
+        Operation::Batch | Operation::BatchContinueOnError => Err(RtcError::Batch(String::from(
+            "batches can not run other batches",
+        ))),
+        Operation::Daemon | Operation::StopDaemon | Operation::Stdio => Err(RtcError::Batch(
+            String::from("batches can not run the daemon or stdio mode"),
+        )),
+        Operation::Filter => run_filter(crud_handler, config.args(), config.list_options()),
+        Operation::GetAll => run_getall(crud_handler, config.list_options()),
+        Operation::SaveView => run_save_view(crud_handler, config.args()),
+        Operation::RunView => run_view(crud_handler, config.args(), config.list_options()),
+        Operation::DeleteView => crud_handler.delete_view(&config.args()[0]),
+        Operation::ListViews => crud_handler.list_views(),
+        Operation::Undo => crud_handler.undo(parse_count(config.args(), 1)?),
+        Operation::Redo => crud_handler.redo(parse_count(config.args(), 1)?),
+        Operation::UndoList => crud_handler.list_journal(false),
+        Operation::RedoList => crud_handler.list_journal(true),
+        Operation::ListTrash => crud_handler.list_trash(),
+        Operation::EmptyTrash => crud_handler.empty_trash(
+            config
+                .args()
+                .first()
+                .map(|s| parse_duration(s))
+                .transpose()?,
+        ),
+        Operation::Restore => crud_handler.restore(&config.args()[0]),
+        Operation::Log => crud_handler.log(parse_count(config.args(), 20)?),
+        Operation::Show => crud_handler.show(&config.args()[0], false),
+        Operation::ShowHistory => crud_handler.show(&config.args()[0], true),
+        Operation::Get => crud_handler.get(&config.args()[0]),
+        Operation::Archive => crud_handler.archive(config.args().first().map(String::as_str)),
+        Operation::Doctor => crud_handler.doctor(false, &config.quarantine_file()),
+        Operation::DoctorFix => crud_handler.doctor(true, &config.quarantine_file()),
     }
 }
 
+/// Runs every command of a batch script within the transaction of the whole run
+fn run_batch<T: DbDriver>(
+    crud_handler: &mut CrudHandler<T>,
+    config: &Config,
+    continue_on_error: bool,
+) -> RtcResult<RunReturn> {
+    let script = match &config.args()[0][..] {
+        "-" => io::read_to_string(io::stdin())?,
+        file => fs::read_to_string(config.path(file))?,
+    };
+
+    let mut results = vec![];
+    for (number, line) in script.lines().enumerate() {
+        let (number, line) = (number + 1, line.trim());
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        writeln!(
+            crud_handler.out(),
+            "{} {}: {}",
+            "Line".bold(),
+            number,
+            line.green()
+        )?;
+
+        // Lines that fail keep none of their changes, even when the batch continues
+        let result = parse_batch_line(line, config).and_then(|line_config| {
+            crud_handler.transaction(|crud_handler| run_operation(crud_handler, &line_config))
+        });
+        if let Err(e) = &result {
+            if !continue_on_error {
+                return Err(RtcError::Batch(format!("line {}: {}", number, e)));
+            }
+            writeln!(
+                crud_handler.out(),
+                "{} line {}: {}",
+                "Skipping".red(),
+                number,
+                e
+            )?;
+        }
+        results.push((number, result));
+    }
+
+    let failures = results.iter().filter(|(_, r)| r.is_err()).count();
+    writeln!(
+        crud_handler.out(),
+        "Ran {} lines, {} failed",
+        results.len().to_string().blue(),
+        failures.to_string().blue()
+    )?;
+    Ok(RunReturn::Batch(results))
+}
+
+/// Parses a line of a batch script like the arguments of a command line
+fn parse_batch_line(line: &str, session: &Config) -> RtcResult<Config> {
+    let words = split_words(line)?;
+    let matches = build_app()
+        .get_matches_from_safe(iter::once(String::from("rtc")).chain(words))
+        .map_err(|e| RtcError::Parse(e.message))?;
+    Ok(Config::new(&matches)?.in_session_of(session))
+}
+
+/// Splits a line into words like a shell, keeping quoted words together
+fn split_words(line: &str) -> RtcResult<Vec<String>> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (Some('\''), c) => word.get_or_insert_with(String::new).push(c),
+            (_, '\\') => {
+                if let Some(escaped) = chars.next() {
+                    word.get_or_insert_with(String::new).push(escaped);
+                }
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (_, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if quote.is_some() {
+        return Err(RtcError::Parse(format!("'{}', unterminated quote", line)));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+fn run_import<T: DbDriver>(
+    crud_handler: &mut CrudHandler<T>,
+    config: &Config,
+) -> RtcResult<RunReturn> {
+    let options = config.import_options();
+    let format = options.format(&config.args()[0])?;
+    let text = match &config.args()[1][..] {
+        "-" => io::read_to_string(io::stdin())?,
+        file => fs::read_to_string(config.path(file))?,
+    };
+    crud_handler.import(&format, &text, options)
+}
+
+fn run_export<T: DbDriver>(crud_handler: &CrudHandler<T>, config: &Config) -> RtcResult<RunReturn> {
+    let options = config.export_options();
+    let format = options.format(&config.args()[0])?;
+    let arg_map = parse_filter_args(&options.filters)?;
+    let result = crud_handler.export(&format, &filters_from_map(&arg_map))?;
+
+    if let RunReturn::Exported(text) = &result {
+        match config.args().get(1).map(String::as_str) {
+            None | Some("-") => write!(crud_handler.out(), "{}", text)?,
+            Some(file) => {
+                fs::write(config.path(file), text)?;
+                writeln!(crud_handler.out(), "Exported to {}", file)?;
+            }
+        }
+    }
+    Ok(result)
+}
+
+fn run_backup<T: DbDriver>(crud_handler: &CrudHandler<T>, config: &Config) -> RtcResult<RunReturn> {
+    let file = match config.args().first() {
+        Some(f) => config.path(f),
+        None => {
+            fs::create_dir_all(config.backup_dir())?;
+            config.backup_rotation().manual_file()
+        }
+    };
+    fs::write(&file, crud_handler.backup().to_json()?)?;
+    writeln!(crud_handler.out(), "Backed up db to {}", file.display())?;
+    Ok(RunReturn::BackedUp(file))
+}
+
+fn run_restore_backup<T: DbDriver>(
+    crud_handler: &mut CrudHandler<T>,
+    config: &Config,
+    mode: RestoreMode,
+) -> RtcResult<RunReturn> {
+    let backup = Backup::parse(&fs::read_to_string(config.path(&config.args()[0]))?)?;
+    crud_handler.restore_backup(backup, mode)
+}
+
+fn run_scan<T: DbDriver>(crud_handler: &mut CrudHandler<T>, path: &Path) -> RtcResult<RunReturn> {
+    // The same tree is recognized however it is given
+    let root = fs::canonicalize(path)?;
+    let todos = code_scan::scan(&root)?;
+    crud_handler.sync_code_todos(&root.to_string_lossy(), todos)
+}
+
+/// The hook runs this very binary, wherever it is installed
+fn run_install_hooks<T: DbDriver>(
+    crud_handler: &CrudHandler<T>,
+    repo: &Path,
+) -> RtcResult<RunReturn> {
+    let hook = git::install_hook(repo, &env::current_exe()?)?;
+    writeln!(crud_handler.out(), "Installed {}", hook.display())?;
+    Ok(RunReturn::HooksInstalled(hook))
+}
+
 fn run_add<T: DbDriver>(
     crud_handler: &mut CrudHandler<T>,
     args: &[String],
 ) -> RtcResult<RunReturn> {
     let name = args.iter().fold(String::from(""), |acc, it| acc + " " + it);
-    crud_handler.add(&name.trim())
+    crud_handler.add(name.trim())
 }
 
 fn run_delete<T: DbDriver>(
     crud_handler: &mut CrudHandler<T>,
     args: &[String],
+    assume_yes: bool,
 ) -> RtcResult<RunReturn> {
-    let id = &args[0];
-    crud_handler.delete(id)
+    match single_id(args) {
+        Some(id) => crud_handler.delete(id),
+        None => {
+            let ids = parse_ids(args, &crud_handler.ids())?;
+            run_bulk(crud_handler, "delete", &ids, assume_yes, |c, ids| {
+                c.delete_many(ids)
+            })
+        }
+    }
 }
 
 fn run_update<T: DbDriver>(
     crud_handler: &mut CrudHandler<T>,
     args: &[String],
+    assume_yes: bool,
+) -> RtcResult<RunReturn> {
+    let (status, id_args) = args.split_last().unwrap();
+    let status = Status::from_str(status)?;
+
+    match single_id(id_args) {
+        Some(id) => crud_handler.update(id, status),
+        None => {
+            let ids = parse_ids(id_args, &crud_handler.ids())?;
+            run_bulk(crud_handler, "update", &ids, assume_yes, |c, ids| {
+                c.update_many(ids, status)
+            })
+        }
+    }
+}
+
+fn run_done_where<T: DbDriver>(
+    crud_handler: &mut CrudHandler<T>,
+    expression: &str,
+    assume_yes: bool,
+) -> RtcResult<RunReturn> {
+    let filter_args = parse_where(expression)?;
+    let arg_map = parse_filter_args(&filter_args)?;
+    let ids = crud_handler.matching_ids(&filters_from_map(&arg_map))?;
+
+    run_bulk(crud_handler, "mark as done", &ids, assume_yes, |c, ids| {
+        c.update_many(ids, Status::Done)
+    })
+}
+
+/// Runs a bulk operation once the user confirmed its summary
+fn run_bulk<T, F>(
+    crud_handler: &mut CrudHandler<T>,
+    action: &str,
+    ids: &[usize],
+    assume_yes: bool,
+    operation: F,
+) -> RtcResult<RunReturn>
+where
+    T: DbDriver,
+    F: FnOnce(&mut CrudHandler<T>, &[usize]) -> RtcResult<RunReturn>,
+{
+    if ids.is_empty() {
+        writeln!(crud_handler.out(), "Found no items to {}", action)?;
+        return Ok(RunReturn::Bulk(vec![]));
+    }
+
+    crud_handler.print_bulk_summary(action, ids)?;
+    if !confirm(&mut *crud_handler.out(), assume_yes)? {
+        writeln!(crud_handler.out(), "Aborted, nothing was changed")?;
+        return Ok(RunReturn::Bulk(vec![]));
+    }
+    operation(crud_handler, ids)
+}
+
+/// Asks on `out` whether to continue, unless `--yes` was given
+fn confirm(out: &mut dyn Write, assume_yes: bool) -> RtcResult<bool> {
+    if assume_yes {
+        return Ok(true);
+    }
+
+    write!(out, "Continue? [y/N] ")?;
+    out.flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(&answer.trim().to_lowercase()[..], "y" | "yes"))
+}
+
+/// Whether the operation reads its input from stdin, as `import` and `batch` do with `-`
+fn reads_stdin(config: &Config) -> bool {
+    let args = config.args();
+    match config.operation() {
+        Operation::Import => args.get(1),
+        Operation::Batch | Operation::BatchContinueOnError => args.first(),
+        _ => None,
+    }
+    .is_some_and(|file| file == "-")
+}
+
+/// The ID of operations on a single item, as opposed to multiple IDs or ranges
+fn single_id(args: &[String]) -> Option<&String> {
+    match args {
+        [id] if !id.contains(&[',', '-'][..]) => Some(id),
+        _ => None,
+    }
+}
+
+/// Parses IDs given like `3 5`, `4,7,8` or as ranges like `9-12`, in ascending order. Ranges only
+/// stand for the `existing` IDs in them, so that wide ones are not spelled out.
+fn parse_ids(args: &[String], existing: &BTreeSet<usize>) -> RtcResult<Vec<usize>> {
+    let parse = |s: &str| {
+        s.trim()
+            .parse::<usize>()
+            .map_err(|e| RtcError::Parse(format!("'{}', {}", s, e)))
+    };
+
+    let mut ids = vec![];
+    for part in args
+        .iter()
+        .flat_map(|a| a.split(','))
+        .filter(|p| !p.is_empty())
+    {
+        match part.split_once('-') {
+            Some((from, to)) => {
+                let (from, to) = (parse(from)?, parse(to)?);
+                if from > to {
+                    return Err(RtcError::Parse(format!("'{}', range is empty", part)));
+                }
+                ids.extend(existing.range(from..=to));
+            }
+            None => ids.push(parse(part)?),
+        }
+    }
+
+    ids.sort_unstable();
+    ids.dedup();
+    Ok(ids)
+}
+
+/// Turns filter expressions like `name:report and status:open` into filter arguments
+fn parse_where(expression: &str) -> RtcResult<Vec<String>> {
+    // `and` only joins conditions when a `key:value` follows, so that values may contain it
+    let starts_condition = |word: &str| {
+        word.split_once(&[':', '='][..]).is_some_and(|(key, _)| {
+            !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '_')
+        })
+    };
+    let words = expression.split_whitespace().collect::<Vec<&str>>();
+    let mut conditions = vec![String::new()];
+    for (i, word) in words.iter().enumerate() {
+        let current = conditions.last_mut().unwrap();
+        if *word == "and"
+            && !current.is_empty()
+            && words.get(i + 1).is_some_and(|w| starts_condition(w))
+        {
+            conditions.push(String::new());
+            continue;
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+
+    conditions
+        .iter()
+        .map(|condition| {
+            let (key, value) = condition
+                .trim()
+                .split_once(&[':', '='][..])
+                .ok_or_else(|| {
+                    RtcError::Parse(format!("'{}', expected key:value", condition.trim()))
+                })?;
+            // Filters that are not known would silently match everything
+            if !Filters::KEYS.contains(&key) {
+                return Err(RtcError::Parse(format!(
+                    "Unknown filter '{}', expected one of {}",
+                    key,
+                    Filters::KEYS.join(", ")
+                )));
+            }
+            Ok(format!("{}={}", key, value.trim_matches(&['\'', '"'][..])))
+        })
+        .collect()
+}
+
+fn run_filter<T: DbDriver>(
+    crud_handler: &CrudHandler<T>,
+    args: &[String],
+    options: &ListOptions,
 ) -> RtcResult<RunReturn> {
-    let id = &args[0];
-    let status = &args[1];
-    crud_handler.update(id, Status::from_str(status)?)
+    let arg_map = parse_filter_args(args)?;
+    crud_handler.filter(&filters_from_map(&arg_map), options)
+}
+
+fn filters_from_map<'a>(arg_map: &HashMap<&'a str, &'a str>) -> Filters<'a> {
+    Filters {
+        name: arg_map.get("name").cloned(),
+        status: arg_map.get("status").cloned(),
+        before: arg_map.get("before").cloned(),
+        after: arg_map.get("after").cloned(),
+        completed_before: arg_map.get("completed_before").cloned(),
+        completed_after: arg_map.get("completed_after").cloned(),
+        modified_since: arg_map.get("modified_since").cloned(),
+        tag: arg_map.get("tag").cloned(),
+        project: arg_map.get("project").cloned(),
+    }
 }
 
-fn run_filter<T: DbDriver>(crud_handler: &CrudHandler<T>, args: &[String]) -> RtcResult<RunReturn> {
+fn parse_filter_args(args: &[String]) -> RtcResult<HashMap<&str, &str>> {
     let usage = format!(
-        "{}\n\t{}\n\t{}\n",
+        "{}\n\t{}\n\t{}\n\t{}\n\t{}\n",
         "USAGE for filter:".bold().yellow(),
         "any/all of name=substring status=(done|open) before=date(iso) after=date(iso)".yellow(),
+        "completed_before=date(iso) completed_after=date(iso) modified_since=date(iso)".yellow(),
+        "tag=tag project=project".yellow(),
         "e.g. -f name='my item' status=done before=2021-01-01 after=2020-01-01".yellow(),
     );
 
-    if args.len() > 4 {
-        print!("{}", usage);
-        return Err(RtcError::Parse(String::from(
-            "Length of arguments is greater than 4",
+    if args.len() > Filters::KEYS.len() {
+        eprint!("{}", usage);
+        return Err(RtcError::Parse(format!(
+            "Length of arguments is greater than {}",
+            Filters::KEYS.len()
         )));
     }
 
@@ -86,20 +709,75 @@ fn run_filter<T: DbDriver>(crud_handler: &CrudHandler<T>, args: &[String]) -> Rt
     for arg in args {
         let kv = arg.split('=').collect::<Vec<&str>>();
         if kv.len() != 2 {
-            print!("{}", usage);
+            eprint!("{}", usage);
             return Err(RtcError::Parse(format!("{:?}", kv)));
         }
         arg_map.insert(kv[0], kv[1]);
     }
 
-    crud_handler.filter(
-        arg_map.get("name").cloned(),
-        arg_map.get("status").cloned(),
-        arg_map.get("before").cloned(),
-        arg_map.get("after").cloned(),
-    )
+    Ok(arg_map)
+}
+
+fn run_getall<T: DbDriver>(
+    crud_handler: &CrudHandler<T>,
+    options: &ListOptions,
+) -> RtcResult<RunReturn> {
+    crud_handler.filter(&Filters::default(), options)
+}
+
+fn run_save_view<T: DbDriver>(
+    crud_handler: &mut CrudHandler<T>,
+    args: &[String],
+) -> RtcResult<RunReturn> {
+    let (name, words) = args.split_first().unwrap();
+    // Filters like `status=open tag=team` are joined the way a quoted query joins them
+    let query = words
+        .iter()
+        .filter(|word| *word != "and")
+        .cloned()
+        .collect::<Vec<String>>()
+        .join(" and ");
+    // Fail early instead of when the view is run
+    parse_filter_args(&parse_where(&query)?)?;
+    crud_handler.save_view(name, &query)
 }
 
-fn run_getall<T: DbDriver>(crud_handler: &CrudHandler<T>) -> RtcResult<RunReturn> {
-    crud_handler.filter(None, None, None, None)
+fn run_view<T: DbDriver>(
+    crud_handler: &CrudHandler<T>,
+    args: &[String],
+    options: &ListOptions,
+) -> RtcResult<RunReturn> {
+    let query = crud_handler.get_view(&args[0])?;
+    run_filter(crud_handler, &parse_where(&query)?, options)
+}
+
+/// Parses the optional first argument as a count
+fn parse_count(args: &[String], default: usize) -> RtcResult<usize> {
+    match args.first() {
+        Some(s) => s
+            .parse::<usize>()
+            .map_err(|e| RtcError::Parse(format!("'{}', {}", s, e))),
+        None => Ok(default),
+    }
+}
+
+/// Longest duration accepted, a hundred years
+const MAX_DURATION_HOURS: i64 = 100 * 366 * 24;
+
+/// Parses durations like `30d`, `12h` or `2w`
+fn parse_duration(s: &str) -> RtcResult<Duration> {
+    let err = || RtcError::Parse(format!("'{}', expected a duration like 30d, 12h or 2w", s));
+    let (number, hours_per_unit) = [("h", 1), ("d", 24), ("w", 24 * 7)]
+        .iter()
+        .find_map(|(unit, hours)| s.strip_suffix(unit).map(|number| (number, *hours)))
+        .ok_or_else(err)?;
+
+    // Durations far beyond any date would overflow
+    let hours = number
+        .parse::<i64>()
+        .ok()
+        .and_then(|n| n.checked_mul(hours_per_unit))
+        .filter(|hours| (0..=MAX_DURATION_HOURS).contains(hours))
+        .ok_or_else(err)?;
+    Ok(Duration::hours(hours))
 }
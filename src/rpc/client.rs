@@ -0,0 +1,71 @@
+use crate::{is_session, reads_stdin, single_id, Config, Operation, RtcError, RtcResult};
+use serde_json::{json, Value};
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+
+/// Calls `method` of the daemon listening on `socket`, returning its result
+pub fn call(socket: &Path, method: &str, params: Value) -> RtcResult<Value> {
+    let stream = UnixStream::connect(socket)?;
+    call_on(stream, method, params)
+}
+
+fn call_on(mut stream: UnixStream, method: &str, params: Value) -> RtcResult<Value> {
+    let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+    writeln!(stream, "{}", request)?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let mut response = serde_json::from_str::<Value>(&line)
+        .map_err(|e| RtcError::Parse(format!("Daemon response, {}", e)))?;
+
+    match response.get_mut("error").map(Value::take) {
+        Some(error) => {
+            // Output printed before the operation failed
+            if let Some(output) = error["data"]["output"].as_str() {
+                print!("{}", output);
+            }
+            Err(RtcError::Daemon(
+                error["message"].as_str().unwrap_or_default().to_string(),
+            ))
+        }
+        None => Ok(response["result"].take()),
+    }
+}
+
+/// Runs the command line `args` on the daemon serving the database of `config` and prints what it
+/// printed. Returns `None` if no daemon is running, or if the command needs this process, e.g. to
+/// read stdin or to ask for confirmation.
+pub fn delegate(config: &Config, args: &[String]) -> Option<RtcResult<()>> {
+    if !can_delegate(config) {
+        return None;
+    }
+    // Sockets left behind by a daemon that did not stop are not answered
+    let stream = UnixStream::connect(config.socket_file()).ok()?;
+
+    let cwd = match env::current_dir() {
+        Ok(dir) => dir,
+        Err(e) => return Some(Err(e.into())),
+    };
+    let params = json!({ "args": args, "cwd": cwd });
+    Some(call_on(stream, "run", params).map(|result| {
+        print!("{}", result["output"].as_str().unwrap_or_default());
+    }))
+}
+
+fn can_delegate(config: &Config) -> bool {
+    let args = config.args();
+    let may_ask = match config.operation() {
+        Operation::Delete | Operation::Done => single_id(args).is_none(),
+        Operation::Update => args
+            .split_last()
+            .is_some_and(|(_, ids)| single_id(ids).is_none()),
+        // Batches may run any of the above
+        Operation::DoneWhere | Operation::Batch | Operation::BatchContinueOnError => true,
+        _ => false,
+    };
+
+    let may_ask = may_ask && !config.assume_yes();
+    !(reads_stdin(config) || may_ask || is_session(config.operation()))
+}
@@ -0,0 +1,132 @@
+use super::{call, lock, parse_request, reply, State};
+use crate::{Config, ListOptions, Operation, RtcError, RtcResult, RunReturn};
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Keeps the database loaded and serves JSON-RPC 2.0 on a Unix socket next to it, one request per
+/// line. Requests run one at a time, each holding the lock of the database like a run of `rtc`,
+/// and the database is loaded again only if another process changed its file.
+pub struct Daemon {
+    listener: UnixListener,
+    socket: PathBuf,
+    state: Arc<Mutex<State>>,
+    stopping: Arc<AtomicBool>,
+}
+
+impl Daemon {
+    /// Listens on the socket of the database of `session`, replacing a socket that was left behind
+    pub fn bind(session: &Config) -> RtcResult<Daemon> {
+        // Requests run in the directories of their clients
+        let session = session.for_operation(Operation::Daemon, vec![], ListOptions::default());
+        let session = session.absolute()?;
+        let socket = session.socket_file();
+
+        if UnixStream::connect(&socket).is_ok() {
+            return Err(RtcError::Io(format!(
+                "A daemon is already listening on {}",
+                socket.display()
+            )));
+        }
+        let _ = fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket)?;
+
+        Ok(Daemon {
+            listener,
+            socket,
+            state: Arc::new(Mutex::new(State::new(session))),
+            stopping: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Answers each connection in its own thread until `shutdown` is requested
+    pub fn serve(self) -> RtcResult<RunReturn> {
+        println!("Serving JSON-RPC on {}", self.socket.display());
+
+        for stream in self.listener.incoming() {
+            if self.stopping.load(Ordering::SeqCst) {
+                break;
+            }
+            let stream = match stream {
+                Ok(s) => s,
+                Err(e) => {
+                    eprintln!("Could not accept connection: {}", e);
+                    continue;
+                }
+            };
+
+            let state = Arc::clone(&self.state);
+            let stopping = Arc::clone(&self.stopping);
+            let socket = self.socket.clone();
+            thread::spawn(move || {
+                if let Err(e) = serve_connection(stream, &state, &stopping) {
+                    eprintln!("Connection failed: {}", e);
+                }
+                if stopping.load(Ordering::SeqCst) {
+                    // Wakes up the loop waiting for connections
+                    let _ = UnixStream::connect(socket);
+                }
+            });
+        }
+
+        fs::remove_file(&self.socket)?;
+        println!("Stopped daemon");
+        Ok(RunReturn::DaemonStopped)
+    }
+}
+
+/// Stops the daemon serving the database of `config`
+pub fn stop(config: &Config) -> RtcResult<RunReturn> {
+    call(&config.socket_file(), "shutdown", Value::Null)?;
+    println!("Stopped daemon");
+    Ok(RunReturn::DaemonStopped)
+}
+
+/// Answers the requests of a connection, one per line, until it is closed
+fn serve_connection(
+    stream: UnixStream,
+    state: &Mutex<State>,
+    stopping: &AtomicBool,
+) -> RtcResult<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = respond(&line, state, stopping);
+        writeln!(writer, "{}", response)?;
+        if stopping.load(Ordering::SeqCst) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn respond(line: &str, state: &Mutex<State>, stopping: &AtomicBool) -> Value {
+    let request = match parse_request(line) {
+        Ok(request) => request,
+        Err(response) => return response,
+    };
+
+    let mut state = lock(state);
+    let result = match &request.method[..] {
+        "ping" => Ok(json!({
+            "database": state.session.database_file(),
+            "pid": process::id(),
+        })),
+        "shutdown" => {
+            stopping.store(true, Ordering::SeqCst);
+            Ok(Value::Null)
+        }
+        method => state.call(method, request.params),
+    };
+    reply(request.id, result)
+}
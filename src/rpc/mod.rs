@@ -0,0 +1,442 @@
+mod client;
+mod daemon;
+mod stdio;
+
+pub use client::{call, delegate};
+pub use daemon::{stop, Daemon};
+pub use stdio::{serve_stdio, Stdio};
+
+use crate::util::db::file_lock::FileLock;
+use crate::{
+    build_app, open, run_on, Config, CrudHandler, ListOptions, Operation, PickleDbDriver, RtcError,
+    RtcResult, RunReturn, Status, TodoItem,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::iter;
+use std::os::unix::fs::MetadataExt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+
+/// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// An operation failed
+const OPERATION_FAILED: i64 = -32000;
+
+/// The database of a long running session, kept loaded between requests
+struct State {
+    session: Config,
+    /// The open database, with the hash its file had then
+    loaded: Option<(CrudHandler<PickleDbDriver>, FileHash)>,
+    /// The last hash taken, with the metadata the files had then
+    hashed: Option<(FileStamp, FileHash)>,
+}
+
+/// Hash of the contents of the database file, if there is one
+type FileHash = Option<u64>;
+
+/// Size, inode and modification and change times of the database and archive files, if they
+/// exist. Unlike the modification time, the change time can not be set back by a rewrite.
+type FileStamp = [Option<(u64, u64, SystemTime, i64, i64)>; 2];
+
+/// What an operation reports, kept for the answer to a request
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Buffer {
+    fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+}
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// Parameters of `run`, a command line as it was given to `rtc`
+#[derive(Deserialize)]
+struct RunParams {
+    args: Vec<String>,
+    cwd: Option<PathBuf>,
+}
+
+/// Parameters of `list` and `subscribe`, like the options of `-f`
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ListParams {
+    filters: Vec<String>,
+    query: Option<String>,
+    sort: Vec<String>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    reverse: bool,
+    include_archived: bool,
+}
+
+/// Parameters of `search`, words that must all be part of the name, tags or projects of the items
+/// found
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SearchParams {
+    text: String,
+    limit: Option<usize>,
+    #[serde(default)]
+    include_archived: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AddParams {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UpdateParams {
+    id: usize,
+    /// `open` or `done`
+    status: String,
+}
+
+/// Parameters of `show` and `delete`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct IdParams {
+    id: usize,
+}
+
+/// Failure answered to a request
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Value,
+}
+
+impl RpcError {
+    fn new(code: i64, message: &str) -> RpcError {
+        RpcError {
+            code,
+            message: String::from(message),
+            data: Value::Null,
+        }
+    }
+}
+
+impl From<RtcError> for RpcError {
+    fn from(e: RtcError) -> Self {
+        RpcError::new(OPERATION_FAILED, &e.to_string())
+    }
+}
+
+/// Parses a line into a request, or into the answer why it is none
+fn parse_request(line: &str) -> Result<Request, Value> {
+    let request = serde_json::from_str::<Value>(line)
+        .map_err(|e| reply(Value::Null, Err(RpcError::new(PARSE_ERROR, &e.to_string()))))?;
+    let id = request.get("id").cloned().unwrap_or_default();
+    match serde_json::from_value::<Request>(request) {
+        Ok(r) if r.jsonrpc == "2.0" => Ok(r),
+        Ok(_) => Err(reply(
+            id,
+            Err(RpcError::new(INVALID_REQUEST, "jsonrpc must be 2.0")),
+        )),
+        Err(e) => Err(reply(
+            id,
+            Err(RpcError::new(INVALID_REQUEST, &e.to_string())),
+        )),
+    }
+}
+
+fn reply(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => {
+            let mut error_object = json!({ "code": error.code, "message": error.message });
+            if !error.data.is_null() {
+                error_object["data"] = error.data;
+            }
+            json!({ "jsonrpc": "2.0", "id": id, "error": error_object })
+        }
+    }
+}
+
+/// Parameters that are left out are the defaults
+fn params<'a, P: Deserialize<'a>>(params: Value) -> Result<P, RpcError> {
+    let params = match params {
+        Value::Null => json!({}),
+        params => params,
+    };
+    P::deserialize(params).map_err(|e| RpcError::new(INVALID_PARAMS, &e.to_string()))
+}
+
+/// A panicking request leaves the database as it was, since it is loaded again
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    state.lock().unwrap_or_else(|poisoned| {
+        let mut state = poisoned.into_inner();
+        state.loaded = None;
+        state
+    })
+}
+
+impl State {
+    fn new(session: Config) -> State {
+        State {
+            session,
+            loaded: None,
+            hashed: None,
+        }
+    }
+
+    /// Answers the methods every session has
+    fn call(&mut self, method: &str, params_value: Value) -> Result<Value, RpcError> {
+        match method {
+            "run" => self.run(params(params_value)?),
+            "list" => self.list(params(params_value)?),
+            "search" => self.search(params(params_value)?),
+            "show" => self.show(params::<IdParams>(params_value)?.id),
+            "add" => self.add(params(params_value)?),
+            "update" => self.update(params(params_value)?),
+            "delete" => self.delete(params(params_value)?),
+            method => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                &format!("Unknown method '{}'", method),
+            )),
+        }
+    }
+
+    /// Runs a command line and returns what it printed
+    fn run(&mut self, params: RunParams) -> Result<Value, RpcError> {
+        let matches = build_app()
+            .get_matches_from_safe(iter::once(String::from("rtc")).chain(params.args))
+            .map_err(|e| RpcError::new(INVALID_PARAMS, &e.message))?;
+        // There is nobody to confirm bulk operations
+        let config = Config::new(&matches)?
+            .in_session_of(&self.session)
+            .in_dir(params.cwd)
+            .with_assume_yes();
+        if crate::is_session(config.operation()) {
+            return Err(RpcError::new(
+                INVALID_PARAMS,
+                "Sessions can not be started from within a session",
+            ));
+        }
+        // Stdin is the session's, if it has one at all
+        if crate::reads_stdin(&config) {
+            return Err(RpcError::new(
+                INVALID_PARAMS,
+                "Input can not be read from stdin within a session, give a file instead",
+            ));
+        }
+
+        let output = Buffer::default();
+        match self.run_locked(&config, &output) {
+            Ok(_) => Ok(json!({ "output": output.text() })),
+            Err(e) => {
+                let mut error = RpcError::from(e);
+                error.data = json!({ "output": output.text() });
+                Err(error)
+            }
+        }
+    }
+
+    fn list(&mut self, params: ListParams) -> Result<Value, RpcError> {
+        let mut filter_args = params.filters;
+        if let Some(query) = &params.query {
+            filter_args.extend(crate::parse_where(query)?);
+        }
+        let to_string = |n: Option<usize>| n.map(|n| n.to_string());
+        let list_options = ListOptions::new(
+            &params.sort,
+            to_string(params.limit).as_deref(),
+            to_string(params.offset).as_deref(),
+            params.reverse,
+        )?
+        .with_archived(params.include_archived);
+
+        let config = self
+            .session
+            .for_operation(Operation::Filter, filter_args, list_options);
+        let (items, total) = self.items(&config)?;
+        Ok(json!({ "items": items, "total": total }))
+    }
+
+    fn search(&mut self, params: SearchParams) -> Result<Value, RpcError> {
+        let words = params
+            .text
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .collect::<Vec<String>>();
+        let config = self.session.for_operation(
+            Operation::GetAll,
+            vec![],
+            ListOptions::default().with_archived(params.include_archived),
+        );
+
+        let mut items = self.items(&config)?.0;
+        items.retain(|item| {
+            let text = iter::once(item.name())
+                .chain(&item.tags)
+                .chain(&item.projects)
+                .map(|s| s.to_lowercase())
+                .collect::<Vec<String>>();
+            words
+                .iter()
+                .all(|word| text.iter().any(|s| s.contains(word)))
+        });
+        let total = items.len();
+        items.truncate(params.limit.unwrap_or(total));
+        Ok(json!({ "items": items, "total": total }))
+    }
+
+    /// Items listed by `config`, which are none rather than an error while the database is empty
+    fn items(&mut self, config: &Config) -> RtcResult<(Vec<TodoItem>, usize)> {
+        match self.run_locked(config, &Buffer::default()) {
+            Ok(RunReturn::Filter(items, total)) => Ok((items, total)),
+            Ok(x) => Err(unexpected(x)),
+            Err(RtcError::NoItems(_)) => Ok((vec![], 0)),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn show(&mut self, id: usize) -> Result<Value, RpcError> {
+        let config = self.session.for_operation(
+            Operation::Show,
+            vec![id.to_string()],
+            ListOptions::default(),
+        );
+        match self.run_locked(&config, &Buffer::default())? {
+            RunReturn::Show(item, _) => Ok(json!(item)),
+            x => Err(RpcError::from(unexpected(x))),
+        }
+    }
+
+    fn add(&mut self, params: AddParams) -> Result<Value, RpcError> {
+        if params.name.trim().is_empty() {
+            return Err(RpcError::new(INVALID_PARAMS, "Item name must not be empty"));
+        }
+        let config =
+            self.session
+                .for_operation(Operation::Add, vec![params.name], ListOptions::default());
+        match self.run_locked(&config, &Buffer::default())? {
+            RunReturn::Addition(id) => self.show(id),
+            x => Err(RpcError::from(unexpected(x))),
+        }
+    }
+
+    fn update(&mut self, params: UpdateParams) -> Result<Value, RpcError> {
+        let status = Status::from_str(&params.status)?;
+        let config = self.session.for_operation(
+            Operation::Update,
+            vec![params.id.to_string(), status.to_string()],
+            ListOptions::default(),
+        );
+        self.run_locked(&config, &Buffer::default())?;
+        self.show(params.id)
+    }
+
+    /// Moves an item to the trash
+    fn delete(&mut self, params: IdParams) -> Result<Value, RpcError> {
+        let config = self.session.for_operation(
+            Operation::Delete,
+            vec![params.id.to_string()],
+            ListOptions::default(),
+        );
+        self.run_locked(&config, &Buffer::default())?;
+        Ok(Value::Null)
+    }
+
+    /// Runs `config` on the loaded database, reporting to `output`. The database is loaded first
+    /// if its file changed since.
+    fn run_locked(&mut self, config: &Config, output: &Buffer) -> RtcResult<RunReturn> {
+        let _lock = FileLock::acquire(&self.session.lock_file())?;
+
+        let hash = self.file_hash();
+        let mut crud_handler = match self.loaded.take() {
+            Some((mut crud_handler, loaded)) if loaded == hash => {
+                crud_handler.set_output(Box::new(output.clone()));
+                crud_handler
+            }
+            _ => match open(&self.session, Box::new(output.clone())) {
+                Ok(crud_handler) => crud_handler,
+                Err(e) => return crate::open_failed(config, e, &mut output.clone()),
+            },
+        };
+        let result = run_on(&mut crud_handler, config);
+
+        // Writes of failed operations may have been cut short, so the file is the one to trust
+        if result.is_ok() {
+            let hash = self.file_hash();
+            self.loaded = Some((crud_handler, hash));
+        }
+        result
+    }
+
+    /// Hashes the files only if their metadata changed since the last hash, as reading them on
+    /// every request would cost as much as loading the database
+    fn file_hash(&mut self) -> FileHash {
+        let stamp = self.file_stamp();
+        match &self.hashed {
+            Some((hashed, hash)) if *hashed == stamp => *hash,
+            _ => {
+                let hash = self.hash_files();
+                self.hashed = Some((stamp, hash));
+                hash
+            }
+        }
+    }
+
+    fn file_stamp(&self) -> FileStamp {
+        let stamp = |file: &str| {
+            let metadata = fs::metadata(file).ok()?;
+            Some((
+                metadata.len(),
+                metadata.ino(),
+                metadata.modified().ok()?,
+                metadata.ctime(),
+                metadata.ctime_nsec(),
+            ))
+        };
+        [
+            stamp(self.session.database_file()),
+            stamp(&self.session.archive_file()),
+        ]
+    }
+
+    /// Compares contents rather than modification times, which may not change with a rewrite
+    fn hash_files(&self) -> FileHash {
+        let contents = fs::read(self.session.database_file()).ok()?;
+        let mut hasher = DefaultHasher::new();
+        contents.hash(&mut hasher);
+        // Archiving may only rewrite the archive
+        fs::read(self.session.archive_file()).ok().hash(&mut hasher);
+        Some(hasher.finish())
+    }
+}
+
+fn unexpected(run_return: RunReturn) -> RtcError {
+    RtcError::Io(format!("Unexpected result {:?}", run_return))
+}
@@ -0,0 +1,138 @@
+use super::{lock, params, parse_request, reply, FileHash, ListParams, State};
+use crate::{Config, ListOptions, Operation, RtcResult, RunReturn};
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// How often the database file is checked for changes while there is a subscription
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Answers JSON-RPC 2.0 requests read line by line, e.g. from an editor plugin that started
+/// `rtc stdio`. Besides the methods of the daemon, a client may `subscribe` to a list of items
+/// and is then sent it again with a `changed` notification whenever the database changes.
+pub struct Stdio {
+    state: Arc<Mutex<State>>,
+    subscription: Arc<Mutex<Option<Subscription>>>,
+    stopping: Arc<AtomicBool>,
+}
+
+struct Subscription {
+    params: ListParams,
+    /// Hash of the database file when the list was last sent
+    hash: FileHash,
+}
+
+impl Stdio {
+    pub fn new(session: &Config) -> RtcResult<Stdio> {
+        // Requests of `run` may come from other directories
+        let session = session.for_operation(Operation::Stdio, vec![], ListOptions::default());
+        Ok(Stdio {
+            state: Arc::new(Mutex::new(State::new(session.absolute()?))),
+            subscription: Arc::new(Mutex::new(None)),
+            stopping: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Answers the requests of `input` on `output` until the input ends or `shutdown` is
+    /// requested
+    pub fn serve<W: Write + Send + 'static>(
+        self,
+        input: impl BufRead,
+        output: W,
+    ) -> RtcResult<RunReturn> {
+        let output = Arc::new(Mutex::new(output));
+        let watcher = {
+            let (state, subscription) = (Arc::clone(&self.state), Arc::clone(&self.subscription));
+            let (stopping, output) = (Arc::clone(&self.stopping), Arc::clone(&output));
+            thread::spawn(move || {
+                while !stopping.load(Ordering::SeqCst) {
+                    thread::sleep(POLL_INTERVAL);
+                    if let Some(notification) = changes(&state, &subscription) {
+                        if let Err(e) = write_line(&output, &notification) {
+                            eprintln!("Could not notify: {}", e);
+                        }
+                    }
+                }
+            })
+        };
+
+        let result = self.answer(input, &output);
+        self.stopping.store(true, Ordering::SeqCst);
+        let _ = watcher.join();
+        result.map(|_| RunReturn::StdioClosed)
+    }
+
+    fn answer<W: Write>(&self, input: impl BufRead, output: &Mutex<W>) -> RtcResult<()> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let response = self.respond(&line);
+            write_line(output, &response)?;
+            if self.stopping.load(Ordering::SeqCst) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn respond(&self, line: &str) -> Value {
+        let request = match parse_request(line) {
+            Ok(request) => request,
+            Err(response) => return response,
+        };
+
+        let result = match &request.method[..] {
+            "subscribe" => params(request.params).and_then(|params: ListParams| {
+                let mut subscription = self.subscription.lock().unwrap();
+                let mut state = lock(&self.state);
+                let items = state.list(params.clone())?;
+                let hash = state.file_hash();
+                *subscription = Some(Subscription { params, hash });
+                Ok(items)
+            }),
+            "unsubscribe" => {
+                *self.subscription.lock().unwrap() = None;
+                Ok(Value::Null)
+            }
+            "shutdown" => {
+                self.stopping.store(true, Ordering::SeqCst);
+                Ok(Value::Null)
+            }
+            method => lock(&self.state).call(method, request.params),
+        };
+        reply(request.id, result)
+    }
+}
+
+/// Serves the requests of stdin, answering on stdout
+pub fn serve_stdio(session: &Config) -> RtcResult<RunReturn> {
+    Stdio::new(session)?.serve(io::stdin().lock(), io::stdout())
+}
+
+/// The list subscribed to, if the database changed since it was last sent
+fn changes(state: &Mutex<State>, subscription: &Mutex<Option<Subscription>>) -> Option<Value> {
+    let mut subscription = subscription.lock().unwrap();
+    let subscription = subscription.as_mut()?;
+    let mut state = lock(state);
+
+    let hash = state.file_hash();
+    if hash == subscription.hash {
+        return None;
+    }
+    // Lists that fail, e.g. while the file is written, are tried again
+    let items = state.list(subscription.params.clone()).ok()?;
+    subscription.hash = hash;
+    Some(json!({ "jsonrpc": "2.0", "method": "changed", "params": items }))
+}
+
+fn write_line<W: Write>(output: &Mutex<W>, message: &Value) -> io::Result<()> {
+    let mut output = output.lock().unwrap();
+    writeln!(output, "{}", message)?;
+    output.flush()
+}
@@ -0,0 +1,68 @@
+mod routes;
+
+pub use routes::{ItemPatch, NewItem};
+
+use crate::{Config, RtcError, RtcResult};
+use std::net::SocketAddr;
+use tiny_http::{Header, Request, Response};
+
+/// JSON API on top of the operations of the command line, serving one request at a time.
+/// Each request runs in its own transaction and holds the lock of the database like a run of
+/// `rtc` does, so that both can be used side by side.
+pub struct Server {
+    http: tiny_http::Server,
+    session: Config,
+}
+
+impl Server {
+    /// Listens on `addr`, e.g. `127.0.0.1:8080`, or on any free port with port `0`
+    pub fn bind(addr: &str, session: Config) -> RtcResult<Server> {
+        let http = tiny_http::Server::http(addr)
+            .map_err(|e| RtcError::Io(format!("Could not listen on {}: {}", addr, e)))?;
+        Ok(Server { http, session })
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.http.server_addr().to_ip()
+    }
+
+    /// Answers requests until the process ends
+    pub fn serve(&self) {
+        for request in self.http.incoming_requests() {
+            if let Err(e) = self.handle(request) {
+                eprintln!("Could not respond: {}", e);
+            }
+        }
+    }
+
+    fn handle(&self, mut request: Request) -> std::io::Result<()> {
+        let mut body = String::new();
+        let response = match request.as_reader().read_to_string(&mut body) {
+            Ok(_) => routes::route(
+                &self.session,
+                &request.method().to_string(),
+                request.url(),
+                &body,
+            ),
+            Err(e) => routes::Response::error(400, &format!("Request body, {}", e)),
+        };
+        println!("{} {} {}", request.method(), request.url(), response.status);
+
+        let mut headers = response.headers;
+        let text = match response.body {
+            Some(body) => {
+                headers.push(("Content-Type", String::from("application/json")));
+                body.to_string()
+            }
+            None => String::new(),
+        };
+        let mut http_response = Response::from_string(text).with_status_code(response.status);
+        for (name, value) in headers {
+            // Names and values are known to be valid
+            if let Ok(header) = Header::from_bytes(name.as_bytes(), value.as_bytes()) {
+                http_response.add_header(header);
+            }
+        }
+        request.respond(http_response)
+    }
+}
@@ -0,0 +1,244 @@
+use crate::{
+    parse_where, run_with_output, Config, Filters, ListOptions, Operation, RtcError, RtcResult,
+    RunReturn, Status, TodoItem,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io;
+use std::str::FromStr;
+
+/// Body of `POST /items`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NewItem {
+    pub name: String,
+}
+
+/// Body of `PATCH /items/{id}`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ItemPatch {
+    /// `open` or `done`
+    pub status: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    /// JSON, unless there is no content
+    pub body: Option<Value>,
+    pub headers: Vec<(&'static str, String)>,
+}
+
+impl Response {
+    fn json(status: u16, body: Value) -> Response {
+        Response {
+            status,
+            body: Some(body),
+            headers: vec![],
+        }
+    }
+
+    pub(super) fn error(status: u16, message: &str) -> Response {
+        Response::json(status, json!({ "error": message }))
+    }
+}
+
+/// Answers a request for `url`, running one operation per request in `session`
+pub fn route(session: &Config, method: &str, url: &str, body: &str) -> Response {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let segments = path.trim_matches('/').split('/').collect::<Vec<&str>>();
+
+    let result = match (&segments[..], method) {
+        (["items"], "GET") => list(session, query),
+        (["items"], "POST") => create(session, body),
+        (["items"], _) => return method_not_allowed("GET, POST"),
+        (["items", id], method) => match id.parse::<usize>() {
+            Ok(_) => match method {
+                "GET" => show(session, id),
+                "PATCH" => patch(session, id, body),
+                "DELETE" => delete(session, id),
+                _ => return method_not_allowed("GET, PATCH, DELETE"),
+            },
+            Err(_) => Err(RtcError::ItemNotFound(format!(
+                "ID '{}' does not exist.",
+                id
+            ))),
+        },
+        _ => return Response::error(404, &format!("Unknown path '{}'", path)),
+    };
+    result.unwrap_or_else(|e| Response::error(status_of(&e), &e.to_string()))
+}
+
+fn method_not_allowed(allowed: &str) -> Response {
+    let mut response = Response::error(405, "Method not allowed");
+    response.headers.push(("Allow", String::from(allowed)));
+    response
+}
+
+/// Requests that can not succeed as they are are the client's fault
+fn status_of(error: &RtcError) -> u16 {
+    match error {
+        RtcError::ItemNotFound(_) | RtcError::NoItems(_) => 404,
+        RtcError::Parse(_) => 400,
+        _ => 500,
+    }
+}
+
+fn run_operation(
+    session: &Config,
+    operation: Operation,
+    args: Vec<String>,
+) -> RtcResult<RunReturn> {
+    run(&session.for_operation(operation, args, ListOptions::default()))
+}
+
+/// Runs `config` without what it prints, as the server only logs requests
+fn run(config: &Config) -> RtcResult<RunReturn> {
+    run_with_output(config, || Box::new(io::sink()))
+}
+
+/// `GET /items` with filters as parameters, e.g. `?status=open&tag=work`, or as a `query` like
+/// `name:report and status:open`, as well as `sort`, `limit`, `offset`, `reverse` and
+/// `include_archived`
+fn list(session: &Config, query: &str) -> RtcResult<Response> {
+    let mut filter_args = vec![];
+    let mut sort_keys = vec![];
+    let (mut limit, mut offset) = (None, None);
+    let (mut reverse, mut include_archived) = (false, false);
+
+    for (key, value) in parse_query(query)? {
+        match &key[..] {
+            "query" if value.is_empty() => (),
+            "query" => filter_args.extend(parse_where(&value)?),
+            "sort" => sort_keys.extend(value.split(',').map(String::from)),
+            "limit" => limit = Some(value),
+            "offset" => offset = Some(value),
+            "reverse" => reverse = parse_bool(&key, &value)?,
+            "include_archived" => include_archived = parse_bool(&key, &value)?,
+            key if Filters::KEYS.contains(&key) => filter_args.push(format!("{}={}", key, value)),
+            key => return Err(RtcError::Parse(format!("Unknown parameter '{}'", key))),
+        }
+    }
+
+    let list_options = ListOptions::new(&sort_keys, limit.as_deref(), offset.as_deref(), reverse)?
+        .with_archived(include_archived);
+    let config = session.for_operation(Operation::Filter, filter_args, list_options);
+    let (items, total) = match run(&config) {
+        Ok(RunReturn::Filter(items, total)) => (items, total),
+        Ok(x) => return Err(unexpected(x)),
+        // An empty list is no error
+        Err(RtcError::NoItems(_)) => (vec![], 0),
+        Err(e) => return Err(e),
+    };
+    Ok(Response::json(
+        200,
+        json!({ "items": items, "total": total }),
+    ))
+}
+
+/// `POST /items` with a [`NewItem`]
+fn create(session: &Config, body: &str) -> RtcResult<Response> {
+    let new_item = parse_body::<NewItem>(body)?;
+    if new_item.name.trim().is_empty() {
+        return Err(RtcError::Parse(String::from("Item name must not be empty")));
+    }
+
+    let id = match run_operation(session, Operation::Add, vec![new_item.name])? {
+        RunReturn::Addition(id) => id.to_string(),
+        x => return Err(unexpected(x)),
+    };
+    let mut response = Response::json(201, json!(find(session, &id)?));
+    response
+        .headers
+        .push(("Location", format!("/items/{}", id)));
+    Ok(response)
+}
+
+/// `GET /items/{id}`, which finds neither archived items nor those in the trash
+fn show(session: &Config, id: &str) -> RtcResult<Response> {
+    Ok(Response::json(200, json!(find(session, id)?)))
+}
+
+/// `PATCH /items/{id}` with an [`ItemPatch`]
+fn patch(session: &Config, id: &str, body: &str) -> RtcResult<Response> {
+    let item_patch = parse_body::<ItemPatch>(body)?;
+    let status = Status::from_str(&item_patch.status)?;
+
+    run_operation(
+        session,
+        Operation::Update,
+        vec![String::from(id), status.to_string()],
+    )?;
+    show(session, id)
+}
+
+/// `DELETE /items/{id}`, moving the item to the trash
+fn delete(session: &Config, id: &str) -> RtcResult<Response> {
+    run_operation(session, Operation::Delete, vec![String::from(id)])?;
+    Ok(Response {
+        status: 204,
+        body: None,
+        headers: vec![],
+    })
+}
+
+fn find(session: &Config, id: &str) -> RtcResult<TodoItem> {
+    match run_operation(session, Operation::Get, vec![String::from(id)])? {
+        RunReturn::Show(Some(item), _) => Ok(item),
+        x => Err(unexpected(x)),
+    }
+}
+
+fn parse_body<'a, B: Deserialize<'a>>(body: &'a str) -> RtcResult<B> {
+    serde_json::from_str(body).map_err(|e| RtcError::Parse(format!("Request body, {}", e)))
+}
+
+fn parse_bool(key: &str, value: &str) -> RtcResult<bool> {
+    match value {
+        "" | "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        _ => Err(RtcError::Parse(format!(
+            "'{}', expected true or false for {}",
+            value, key
+        ))),
+    }
+}
+
+/// Splits `a=1&b=two%20words` into decoded pairs
+fn parse_query(query: &str) -> RtcResult<Vec<(String, String)>> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Ok((decode(key)?, decode(value)?))
+        })
+        .collect()
+}
+
+/// Decodes `+` and percent-encoded bytes of a query
+fn decode(s: &str) -> RtcResult<String> {
+    let invalid = || RtcError::Parse(format!("'{}', invalid percent-encoding", s));
+    let mut bytes = vec![];
+    let mut rest = s.as_bytes();
+
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = rest.get(..2).ok_or_else(invalid)?;
+                let hex = std::str::from_utf8(hex).map_err(|_| invalid())?;
+                bytes.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+                rest = &rest[2..];
+            }
+            _ => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| invalid())
+}
+
+fn unexpected(run_return: RunReturn) -> RtcError {
+    RtcError::Io(format!("Unexpected result {:?}", run_return))
+}
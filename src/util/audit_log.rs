@@ -0,0 +1,174 @@
+use crate::{RtcError, RtcResult, TodoItem};
+use chrono::serde::ts_milliseconds;
+use chrono::{DateTime, Utc};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+    Restore,
+    Archive,
+    Purge,
+    Undo,
+    Redo,
+}
+
+impl fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Old and new value of a single field, `None` if it was not set or the item did not exist
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let show = |v: &Option<Value>| v.as_ref().map_or(String::from("-"), Value::to_string);
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.field,
+            show(&self.old).yellow(),
+            show(&self.new).green()
+        )
+    }
+}
+
+/// A single mutation of an item
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditEntry {
+    #[serde(with = "ts_milliseconds")]
+    pub timestamp: DateTime<Utc>,
+    pub user: String,
+    pub id: usize,
+    pub action: AuditAction,
+    pub changes: Vec<FieldChange>,
+}
+
+impl AuditEntry {
+    /// Creates an entry holding every field that differs between `before` and `after`. Fields that
+    /// are not set, i.e. empty or `null`, are left out of either.
+    pub(crate) fn new(
+        user: &str,
+        action: AuditAction,
+        before: Option<&TodoItem>,
+        after: Option<&TodoItem>,
+    ) -> AuditEntry {
+        let is_set = |value: &Value| match value {
+            Value::Null => false,
+            Value::Array(a) => !a.is_empty(),
+            Value::Object(o) => !o.is_empty(),
+            _ => true,
+        };
+        let to_map = |item: Option<&TodoItem>| match item.map(serde_json::to_value) {
+            Some(Ok(Value::Object(map))) => map.into_iter().filter(|(_, v)| is_set(v)).collect(),
+            _ => serde_json::Map::new(),
+        };
+        let (old, new) = (to_map(before), to_map(after));
+
+        let mut fields = old
+            .keys()
+            .chain(new.keys())
+            .cloned()
+            .collect::<Vec<String>>();
+        fields.sort();
+        fields.dedup();
+
+        let changes = fields
+            .into_iter()
+            .filter(|f| old.get(f) != new.get(f))
+            .map(|f| FieldChange {
+                old: old.get(&f).cloned(),
+                new: new.get(&f).cloned(),
+                field: f,
+            })
+            .collect();
+
+        AuditEntry {
+            timestamp: Utc::now(),
+            user: String::from(user),
+            id: *before.or(after).map_or(&0, TodoItem::id),
+            action,
+            changes,
+        }
+    }
+}
+
+impl fmt::Display for AuditEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "'{}' {} of item '{}' by '{}'",
+            self.timestamp.to_string().blue(),
+            self.action.to_string().green(),
+            self.id.to_string().blue(),
+            self.user.blue()
+        )?;
+        for change in &self.changes {
+            write!(f, "\n    {}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Append-only log of every mutation, stored as one JSON entry per line
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    path: PathBuf,
+    user: String,
+}
+
+impl AuditLog {
+    pub fn new(path: PathBuf, user: &str) -> AuditLog {
+        AuditLog {
+            path,
+            user: String::from(user),
+        }
+    }
+
+    pub fn user(&self) -> &String {
+        &self.user
+    }
+
+    pub(crate) fn append(&self, entry: &AuditEntry) -> RtcResult<()> {
+        let line = serde_json::to_string(entry)
+            .map_err(|e| RtcError::Io(format!("Could not serialize log entry: {}", e)))?;
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", line)?;
+        Ok(())
+    }
+
+    /// Reads all entries, oldest first
+    pub(crate) fn read(&self) -> RtcResult<Vec<AuditEntry>> {
+        if !self.path.exists() {
+            return Ok(vec![]);
+        }
+
+        fs::read_to_string(&self.path)?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str(line)
+                    .map_err(|e| RtcError::Parse(format!("'{}', {}", self.path.display(), e)))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,407 @@
+use crate::{Format, GroupBy};
+use clap::{App, AppSettings, Arg, ArgGroup, SubCommand};
+
+/// Builds the command line interface, which is also used to parse the lines of batch scripts
+pub fn build_app<'a, 'b>() -> App<'a, 'b> {
+    App::new("Rust TODO CLI")
+        .version("0.1")
+        .author("Lorenz Leitner")
+        .about("CLI for managing TODOs")
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .arg(
+            Arg::with_name("add")
+                .help("Add an item, return ID")
+                .short("a")
+                .long("add")
+                .takes_value(true)
+                .multiple(true)
+                .value_name("NAME"),
+        )
+        .arg(
+            Arg::with_name("delete")
+                .help("Delete items by ID or range (e.g. 3 5 9-12), moving them to the trash")
+                .short("d")
+                .long("delete")
+                .takes_value(true)
+                .multiple(true)
+                .value_name("IDS"),
+        )
+        .arg(
+            Arg::with_name("update")
+                .help("Update the status of items by ID or range (e.g. 4,7,8 done)")
+                .short("u")
+                .long("update")
+                .takes_value(true)
+                .min_values(2)
+                .value_name("IDS STATUS"),
+        )
+        .arg(
+            Arg::with_name("filter")
+                .help("Filter items by name, status, dates, tag and/or project")
+                .short("f")
+                .long("filter")
+                .takes_value(true)
+                .multiple(true)
+                .value_name("FILTERS"),
+        )
+        .arg(
+            Arg::with_name("getall")
+                .help("Get all items in the database")
+                .short("g")
+                .long("getall"),
+        )
+        .arg(
+            Arg::with_name("yes")
+                .help("Do not ask for confirmation of bulk operations")
+                .short("y")
+                .long("yes")
+                .global(true),
+        )
+        .args(
+            &list_args()
+                .into_iter()
+                .map(|arg| arg.conflicts_with_all(&["add", "delete", "update"]))
+                .collect::<Vec<Arg>>(),
+        )
+        .group(
+            ArgGroup::with_name("req_cmds")
+                .args(&["add", "delete", "update", "filter", "getall"])
+                .required(true),
+        )
+        .subcommand(
+            SubCommand::with_name("view")
+                .about("Run a saved filter")
+                .setting(AppSettings::SubcommandsNegateReqs)
+                .arg(
+                    Arg::with_name("name")
+                        .help("Name of the view")
+                        .required(true),
+                )
+                .args(&list_args())
+                .subcommand(
+                    SubCommand::with_name("save")
+                        .about(
+                            "Save a filter under a name, e.g. view save standup 'status:open and tag:team'",
+                        )
+                        .arg(Arg::with_name("name").required(true))
+                        .arg(
+                            Arg::with_name("filter")
+                                .help("Filters, same as for --filter or --where")
+                                .required(true)
+                                .multiple(true)
+                                .value_name("FILTERS"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("delete")
+                        .about("Delete a saved view")
+                        .arg(Arg::with_name("name").required(true)),
+                ),
+        )
+        .subcommand(SubCommand::with_name("views").about("List all saved views"))
+        .subcommand(
+            SubCommand::with_name("trash")
+                .about("List deleted items")
+                .subcommand(
+                    SubCommand::with_name("empty")
+                        .about("Permanently delete items in the trash")
+                        .arg(
+                            Arg::with_name("older_than")
+                                .help("Only items deleted longer ago than this, e.g. 30d, 12h, 2w")
+                                .long("older-than")
+                                .takes_value(true)
+                                .value_name("DURATION"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("restore")
+                .about("Restore a deleted item from the trash, or the database from a backup")
+                .arg(
+                    Arg::with_name("id")
+                        .help("ID of a trashed item")
+                        .required_unless("backup")
+                        .conflicts_with("backup")
+                        .value_name("ID"),
+                )
+                .arg(
+                    Arg::with_name("backup")
+                        .help("Restore the database from this backup file instead")
+                        .long("backup")
+                        .takes_value(true)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::with_name("merge")
+                        .help("Add the items of the backup instead of replacing the database")
+                        .long("merge")
+                        .requires("backup"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("backup")
+                .about("Back up the whole database")
+                .arg(
+                    Arg::with_name("file")
+                        .help("Defaults to a new file in the backup directory next to the db")
+                        .value_name("FILE"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("archive")
+                .about("Move done items into the archive")
+                .arg(
+                    Arg::with_name("before")
+                        .help("Only items completed before this date (iso)")
+                        .long("before")
+                        .takes_value(true)
+                        .value_name("DATE"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("log")
+                .about("Show recent changes")
+                .arg(
+                    Arg::with_name("n")
+                        .help("Number of changes, defaults to 20")
+                        .value_name("N"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("show")
+                .about("Show an item, including archived and deleted ones")
+                .arg(Arg::with_name("id").required(true).value_name("ID"))
+                .arg(
+                    Arg::with_name("history")
+                        .help("Also show how the item changed over time")
+                        .long("history"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("start")
+                .about("Record the current git branch on an item and reopen it if it was done")
+                .arg(Arg::with_name("id").required(true).value_name("ID"))
+                .arg(repo_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("git")
+                .about("Link items to the commits mentioning them, like closes rtc#12")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("install-hooks")
+                        .about("Install a post-commit hook running post-commit after each commit")
+                        .arg(repo_arg()),
+                )
+                .subcommand(
+                    SubCommand::with_name("post-commit")
+                        .about("Link the last commit to the items it mentions")
+                        .arg(repo_arg()),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("done")
+                .about("Mark items as done by ID, range or filter")
+                .arg(
+                    Arg::with_name("ids")
+                        .help("IDs or ranges, e.g. 3 5 9-12")
+                        .multiple(true)
+                        .value_name("IDS")
+                        .required_unless("where"),
+                )
+                .arg(
+                    Arg::with_name("where")
+                        .help("Filter expression, e.g. 'name:report and status:open'")
+                        .long("where")
+                        .takes_value(true)
+                        .value_name("FILTERS")
+                        .conflicts_with("ids"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("import")
+                .about("Add items from a file, - for stdin")
+                .arg(format_arg())
+                .arg(Arg::with_name("file").required(true).value_name("FILE"))
+                .args(&column_args())
+                .arg(
+                    Arg::with_name("keep_ids")
+                        .help("Keep the IDs in the file instead of assigning new ones, csv only")
+                        .long("keep-ids"),
+                )
+                .arg(
+                    Arg::with_name("sync")
+                        .help("Update the items whose IDs are in the database, csv and org only")
+                        .long("sync"),
+                )
+                .arg(
+                    Arg::with_name("dry_run")
+                        .help("Only show the items that would be created")
+                        .long("dry-run"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("Write all items to a file, or to stdout")
+                .arg(format_arg())
+                .arg(Arg::with_name("file").value_name("FILE"))
+                .arg(
+                    Arg::with_name("filter")
+                        .help("Only export items matching a filter, may be given more than once")
+                        .short("f")
+                        .long("filter")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .value_name("FILTER"),
+                )
+                .arg(
+                    Arg::with_name("group_by")
+                        .help("List items under headings, markdown only")
+                        .long("group-by")
+                        .takes_value(true)
+                        .possible_values(&GroupBy::NAMES)
+                        .value_name("GROUP"),
+                )
+                .args(&column_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("scan")
+                .about("Track the TODO, FIXME and XXX comments of a source tree as items")
+                .arg(
+                    Arg::with_name("path")
+                        .help("Directory to scan, skipping files ignored by .gitignore")
+                        .required(true)
+                        .value_name("PATH"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("batch")
+                .about("Run commands from a file or stdin, one per line, all in one transaction")
+                .arg(
+                    Arg::with_name("file")
+                        .help("Script to read, - for stdin")
+                        .default_value("-")
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::with_name("continue_on_error")
+                        .help("Skip lines that fail instead of stopping and keeping no changes")
+                        .long("continue-on-error"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("daemon")
+                .about("Keep the database loaded and serve JSON-RPC on a Unix socket next to it")
+                .arg(
+                    Arg::with_name("stop")
+                        .help("Stop the running daemon")
+                        .long("stop"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("stdio").about(
+                "Answer JSON-RPC on stdin and stdout, one message per line, e.g. for editors",
+            ),
+        )
+        .subcommand(
+            SubCommand::with_name("doctor")
+                .about("Check the database for corrupt records")
+                .arg(
+                    Arg::with_name("fix")
+                        .help(
+                            "Move corrupt records into a quarantine file and repair the ID counter",
+                        )
+                        .long("fix"),
+                ),
+        )
+        .subcommand(journal_subcommand(
+            "undo",
+            "Revert the last add, delete or update operations",
+        ))
+        .subcommand(journal_subcommand(
+            "redo",
+            "Reapply the last undone operations",
+        ))
+}
+
+fn journal_subcommand<'a, 'b>(name: &'a str, about: &'a str) -> App<'a, 'b> {
+    SubCommand::with_name(name)
+        .about(about)
+        .arg(
+            Arg::with_name("steps")
+                .help("Number of operations, defaults to 1")
+                .value_name("STEPS"),
+        )
+        .arg(
+            Arg::with_name("list")
+                .help("Only list the operations that would be reverted")
+                .long("list")
+                .conflicts_with("steps"),
+        )
+}
+
+fn format_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("format")
+        .help("File format")
+        .long("format")
+        .takes_value(true)
+        .required(true)
+        .possible_values(&Format::NAMES)
+        .value_name("FORMAT")
+}
+
+/// Repository of the git operations
+fn repo_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("repo")
+        .help("Git repository to use")
+        .long("repo")
+        .takes_value(true)
+        .default_value(".")
+        .value_name("DIR")
+}
+
+/// Column layout arguments of formats with columns
+fn column_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("columns")
+            .help("Columns as HEADER=FIELD or FIELD, comma separated, csv only")
+            .long("columns")
+            .takes_value(true)
+            .value_name("MAPPING"),
+        Arg::with_name("no_header")
+            .help("The file has no header row, csv only")
+            .long("no-header"),
+    ]
+}
+
+/// Sorting and pagination arguments of operations that list items
+fn list_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("sort")
+            .help("Sort results by one or more of id, name, status, created, completed, modified, due, priority (e.g. name:desc)")
+            .short("s")
+            .long("sort")
+            .takes_value(true)
+            .multiple(true)
+            .value_name("FIELD[:asc|desc]"),
+        Arg::with_name("limit")
+            .help("Show at most this many results")
+            .short("l")
+            .long("limit")
+            .takes_value(true)
+            .value_name("N"),
+        Arg::with_name("offset")
+            .help("Skip this many results")
+            .short("o")
+            .long("offset")
+            .takes_value(true)
+            .value_name("N"),
+        Arg::with_name("reverse")
+            .help("Reverse the order of results")
+            .short("r")
+            .long("reverse"),
+        Arg::with_name("include_archived")
+            .help("Also list archived items")
+            .long("include-archived"),
+    ]
+}
@@ -0,0 +1,164 @@
+use crate::{RtcError, RtcResult};
+use ignore::WalkBuilder;
+use std::fs;
+use std::path::Path;
+
+/// Words that mark a comment as something left to do
+const MARKERS: [&str; 3] = ["TODO", "FIXME", "XXX"];
+
+/// A `TODO`, `FIXME` or `XXX` comment found in a source file
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeTodo {
+    /// Relative to the scanned directory, separated by `/`
+    pub path: String,
+    /// Starting at 1
+    pub line: usize,
+    /// The marker, e.g. `FIXME`
+    pub marker: String,
+    /// What follows the marker, without any `(author)` or `:`
+    pub text: String,
+}
+
+impl CodeTodo {
+    /// Where the comment is, as `path#Lline`. Unlike a `:`, the `#` survives todo.txt extensions.
+    pub fn source(&self) -> String {
+        format!("{}#L{}", self.path, self.line)
+    }
+}
+
+/// Finds the comments in the files below `root`, skipping hidden files and the ones ignored by
+/// `.gitignore` and similar files. Files are read in order of their paths.
+pub fn scan(root: &Path) -> RtcResult<Vec<CodeTodo>> {
+    if !root.is_dir() {
+        return Err(RtcError::Parse(format!(
+            "'{}' is not a directory",
+            root.display()
+        )));
+    }
+
+    let walker = WalkBuilder::new(root)
+        // Also outside of git repositories
+        .require_git(false)
+        .sort_by_file_path(|a, b| a.cmp(b))
+        .build();
+
+    let mut todos = vec![];
+    for entry in walker {
+        let entry = entry.map_err(|e| RtcError::Io(e.to_string()))?;
+        let path = entry.path();
+        let tokens = match comment_tokens(path) {
+            Some(t) if path.is_file() => t,
+            _ => continue,
+        };
+        // Files that are not text are skipped
+        let text = match fs::read_to_string(path) {
+            Ok(t) => t,
+            Err(_) => continue,
+        };
+
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        let relative = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        for (number, line) in text.lines().enumerate() {
+            if let Some((marker, text)) = find_todo(line, tokens) {
+                todos.push(CodeTodo {
+                    path: relative.clone(),
+                    line: number + 1,
+                    marker: String::from(marker),
+                    text,
+                });
+            }
+        }
+    }
+    Ok(todos)
+}
+
+/// Tokens starting a comment in the language of `path`, or `None` for other files
+fn comment_tokens(path: &Path) -> Option<&'static [&'static str]> {
+    const C_LIKE: &[&str] = &["//", "/*", "*"];
+    const HASH: &[&str] = &["#"];
+
+    let name = path.file_name()?.to_str()?;
+    match name {
+        "Makefile" | "Dockerfile" | "CMakeLists.txt" => return Some(HASH),
+        _ => (),
+    }
+    let tokens: &[&str] = match path.extension()?.to_str()? {
+        "rs" | "c" | "h" | "cc" | "cpp" | "hpp" | "cs" | "java" | "kt" | "scala" | "swift"
+        | "go" | "js" | "jsx" | "mjs" | "ts" | "tsx" | "dart" | "proto" => C_LIKE,
+        "css" | "scss" | "less" => &["/*", "*", "//"],
+        "php" => &["//", "/*", "*", "#"],
+        "py" | "rb" | "sh" | "bash" | "zsh" | "fish" | "pl" | "r" | "ex" | "exs" | "nix"
+        | "toml" | "yaml" | "yml" | "cmake" | "mk" | "ps1" => HASH,
+        "sql" | "lua" | "hs" | "elm" => &["--"],
+        "html" | "xml" | "vue" | "svelte" | "md" => &["<!--"],
+        "lisp" | "el" | "clj" | "scm" | "asm" | "ini" => &[";"],
+        "tex" | "erl" | "m" => &["%"],
+        _ => return None,
+    };
+    Some(tokens)
+}
+
+/// Finds a marker right at the start of the comment on `line`, like `TODO(anna): text` or
+/// `FIXME text`, and returns it with its text. Markers later in a comment are only mentioned.
+fn find_todo(line: &str, tokens: &[&str]) -> Option<(&'static str, String)> {
+    let (start, token) = find_comment(line, tokens)?;
+
+    // Also doc comments like `///`, `//!` or `##`
+    let comment = line[start + token.len()..]
+        .trim_start_matches(&['/', '!', '*', '#', '-', ';', '%'][..])
+        .trim_start();
+    parse_marker(comment)
+}
+
+/// Finds where the comment on `line` starts, skipping tokens inside string literals like
+/// `"// TODO"`. A quote only starts a literal if it is closed on the same line, so that e.g.
+/// lifetimes like `'a` are not taken for one.
+fn find_comment<'t>(line: &str, tokens: &[&'t str]) -> Option<(usize, &'t str)> {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            None => {
+                let token = tokens.iter().find(|token| {
+                    // Continuation lines of block comments only start with `*`
+                    line[i..].starts_with(**token)
+                        && (**token != "*" || line[..i].trim().is_empty())
+                });
+                if let Some(token) = token {
+                    return Some((i, token));
+                }
+                if matches!(c, '"' | '\'' | '`') && line[i + 1..].contains(c) {
+                    quote = Some(c);
+                }
+            }
+        }
+    }
+    None
+}
+
+fn parse_marker(comment: &str) -> Option<(&'static str, String)> {
+    let marker = MARKERS.iter().find(|m| comment.starts_with(*m))?;
+    let mut rest = &comment[marker.len()..];
+    // Whole words only, e.g. not `TODOS`
+    if rest.starts_with(|c: char| c.is_alphanumeric() || c == '_') {
+        return None;
+    }
+    if rest.starts_with('(') {
+        rest = rest.split_once(')').map_or("", |(_, r)| r);
+    }
+    let text = rest
+        .trim_start_matches(&[':', '-'][..])
+        .trim()
+        .trim_end_matches("*/")
+        .trim_end_matches("-->")
+        .trim();
+    Some((marker, String::from(text)))
+}
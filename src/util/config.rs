@@ -1,7 +1,14 @@
+use crate::util::db::backup::BackupRotation;
+use crate::{ExportOptions, GroupBy, ImportOptions, ListOptions, RtcResult, Settings};
 use clap::ArgMatches;
 use colored::*;
 use std::env;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Automatic backups kept unless the settings say otherwise
+const DEFAULT_BACKUPS_TO_KEEP: usize = 5;
 
 pub enum Operation {
     Add,
@@ -9,12 +16,54 @@ pub enum Operation {
     Update,
     Filter,
     GetAll,
+    SaveView,
+    RunView,
+    DeleteView,
+    ListViews,
+    Undo,
+    Redo,
+    UndoList,
+    RedoList,
+    ListTrash,
+    EmptyTrash,
+    Restore,
+    Archive,
+    Log,
+    Show,
+    ShowHistory,
+    /// Finds an item that is neither archived nor in the trash, e.g. for the server
+    Get,
+    Doctor,
+    DoctorFix,
+    Done,
+    DoneWhere,
+    Batch,
+    BatchContinueOnError,
+    Import,
+    Export,
+    Backup,
+    RestoreBackup,
+    MergeBackup,
+    Scan,
+    Start,
+    InstallHooks,
+    PostCommit,
+    Daemon,
+    StopDaemon,
+    Stdio,
 }
 
 pub struct Config {
     operation: Operation,
     args: Vec<String>,
+    list_options: ListOptions,
+    import_options: ImportOptions,
+    export_options: ExportOptions,
+    settings: Settings,
     database_file: String,
+    assume_yes: bool,
+    /// Directory relative paths given as arguments are resolved against, if not the current one
+    working_dir: Option<PathBuf>,
 }
 
 impl Config {
@@ -26,23 +75,111 @@ impl Config {
         &self.args
     }
 
+    pub fn list_options(&self) -> &ListOptions {
+        &self.list_options
+    }
+
+    pub fn import_options(&self) -> &ImportOptions {
+        &self.import_options
+    }
+
+    pub fn export_options(&self) -> &ExportOptions {
+        &self.export_options
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
     pub fn database_file(&self) -> &String {
         &self.database_file
     }
 
+    /// A path given as an argument, relative to the directory it was given in
+    pub fn path(&self, path: &str) -> PathBuf {
+        match &self.working_dir {
+            Some(dir) => dir.join(path),
+            None => PathBuf::from(path),
+        }
+    }
+
+    /// Whether bulk operations run without asking for confirmation
+    pub fn assume_yes(&self) -> bool {
+        self.assume_yes
+    }
+
+    /// The audit log is kept next to the database file
+    pub fn audit_log_file(&self) -> PathBuf {
+        Path::new(&self.database_file).with_extension("history.jsonl")
+    }
+
+    /// Lock file that serializes access to the database across processes
+    pub fn lock_file(&self) -> PathBuf {
+        Path::new(&self.database_file).with_extension("lock")
+    }
+
+    /// Archived items are kept in a file next to the database file
+    pub fn archive_file(&self) -> String {
+        Path::new(&self.database_file)
+            .with_extension("archive.json")
+            .to_string_lossy()
+            .to_string()
+    }
+
+    /// Records that doctor takes out of the database are kept next to the database file
+    pub fn quarantine_file(&self) -> PathBuf {
+        Path::new(&self.database_file).with_extension("quarantine.json")
+    }
+
+    /// Socket a daemon serving the database listens on
+    pub fn socket_file(&self) -> PathBuf {
+        Path::new(&self.database_file).with_extension("sock")
+    }
+
+    /// Backups are kept in a directory next to the database file
+    pub fn backup_dir(&self) -> PathBuf {
+        Path::new(&self.database_file).with_extension("backups")
+    }
+
+    pub fn backup_rotation(&self) -> BackupRotation {
+        BackupRotation::new(
+            self.backup_dir(),
+            self.settings
+                .backups_to_keep
+                .unwrap_or(DEFAULT_BACKUPS_TO_KEEP),
+        )
+    }
+
+    /// Name of the acting user, from the settings or the environment
+    pub fn user(&self) -> String {
+        self.settings
+            .user
+            .clone()
+            .or_else(|| env::var("USER").ok())
+            .or_else(|| env::var("USERNAME").ok())
+            .unwrap_or_else(|| String::from("unknown"))
+    }
+
     /// Creates Config from clap CLI arguments
-    pub fn new(matches: &ArgMatches) -> Result<Config, std::io::Error> {
+    pub fn new(matches: &ArgMatches) -> RtcResult<Config> {
         // Is there a better way to do this?
         // I'd like to match on matches or the arg group, i.e. the reverse of this
         let operation;
         let args: Vec<String>;
+        // Subcommands that list items take their own sorting and pagination options
+        let mut list_matches = matches;
 
-        if matches.is_present("add") {
+        if let (name, Some(sub_matches)) = matches.subcommand() {
+            let (o, a) = Config::get_subcommand_operation(name, sub_matches);
+            operation = o;
+            args = a;
+            list_matches = sub_matches;
+        } else if matches.is_present("add") {
             operation = Operation::Add;
             args = Config::get_args_vector("add", matches);
         } else if matches.is_present("delete") {
             operation = Operation::Delete;
-            args = vec![matches.value_of("delete").unwrap().to_string()];
+            args = Config::get_args_vector("delete", matches);
         } else if matches.is_present("update") {
             operation = Operation::Update;
             args = Config::get_args_vector("update", matches);
@@ -53,31 +190,219 @@ impl Config {
             operation = Operation::GetAll;
             args = vec![];
         } else {
-            // Else can never occur due to ArgGroup restriction in main.rs
+            // Else can never occur due to ArgGroup restriction in main.rs,
+            // which only subcommands can bypass
             panic!("This code can never be reached.");
             // >inb4 this code will be reached
         }
 
+        let list_options = ListOptions::new(
+            &list_matches
+                .values_of("sort")
+                .map(|v| {
+                    v.map(std::string::ToString::to_string)
+                        .collect::<Vec<String>>()
+                })
+                .unwrap_or_default(),
+            list_matches.value_of("limit"),
+            list_matches.value_of("offset"),
+            list_matches.is_present("reverse"),
+        )?
+        .with_archived(list_matches.is_present("include_archived"));
+
+        let (import_options, export_options) = match operation {
+            Operation::Import => (Config::get_import_options(list_matches), Default::default()),
+            Operation::Export => (
+                Default::default(),
+                Config::get_export_options(list_matches)?,
+            ),
+            _ => Default::default(),
+        };
+
+        // `--yes` may be given before or after the subcommand
+        let assume_yes = matches.is_present("yes") || list_matches.is_present("yes");
+
+        Ok(Config {
+            operation,
+            args,
+            list_options,
+            import_options,
+            export_options,
+            settings: Config::load_settings()?,
+            assume_yes,
+            database_file: Config::default_database_file()?,
+            working_dir: None,
+        })
+    }
+
+    /// Config of a long running session outside of the command line, e.g. a server, with the
+    /// default database unless `database_file` is given. Its operations are set per request.
+    pub fn session(database_file: Option<&str>) -> RtcResult<Config> {
+        Ok(Config {
+            operation: Operation::GetAll,
+            args: vec![],
+            list_options: ListOptions::default(),
+            import_options: ImportOptions::default(),
+            export_options: ExportOptions::default(),
+            settings: Config::load_settings()?,
+            // Nobody is there to confirm
+            assume_yes: true,
+            database_file: match database_file {
+                Some(f) => String::from(f),
+                None => Config::default_database_file()?,
+            },
+            working_dir: None,
+        })
+    }
+
+    /// Config running `operation` with the database and settings of this session
+    pub fn for_operation(
+        &self,
+        operation: Operation,
+        args: Vec<String>,
+        list_options: ListOptions,
+    ) -> Config {
+        Config {
+            operation,
+            args,
+            list_options,
+            import_options: ImportOptions::default(),
+            export_options: ExportOptions::default(),
+            settings: self.settings.clone(),
+            assume_yes: self.assume_yes,
+            database_file: self.database_file.clone(),
+            working_dir: self.working_dir.clone(),
+        }
+    }
+
+    /// Resolves the database file against the current directory, for sessions answering requests
+    /// from other directories
+    pub(crate) fn absolute(mut self) -> RtcResult<Config> {
+        self.database_file = env::current_dir()?
+            .join(&self.database_file)
+            .to_string_lossy()
+            .to_string();
+        Ok(self)
+    }
+
+    fn default_database_file() -> RtcResult<String> {
         let cache_dir = if let Some(p) = dirs::cache_dir() {
             p
         } else {
-            println!("{}", "Warning: Using tmp dir".yellow());
+            eprintln!("{}", "Warning: Using tmp dir".yellow());
             env::temp_dir()
         };
 
         fs::create_dir_all(cache_dir.join("rust_todo_cli"))?;
+        Ok(String::from(
+            cache_dir
+                .join("rust_todo_cli")
+                .join("rtc.json")
+                .to_str()
+                .unwrap(),
+        ))
+    }
 
-        Ok(Config {
-            operation,
-            args,
-            database_file: String::from(
-                cache_dir
-                    .join("rust_todo_cli")
-                    .join("rtc.json")
-                    .to_str()
-                    .unwrap(),
+    fn load_settings() -> RtcResult<Settings> {
+        match dirs::config_dir() {
+            Some(p) => Settings::load(&p.join("rust_todo_cli").join("config.json")),
+            None => Ok(Settings::default()),
+        }
+    }
+
+    fn get_subcommand_operation(name: &str, matches: &ArgMatches) -> (Operation, Vec<String>) {
+        match (name, matches.subcommand()) {
+            ("view", ("save", Some(m))) => {
+                let mut args = vec![m.value_of("name").unwrap().to_string()];
+                args.extend(Config::get_args_vector("filter", m));
+                (Operation::SaveView, args)
+            }
+            ("view", ("delete", Some(m))) => (
+                Operation::DeleteView,
+                vec![m.value_of("name").unwrap().to_string()],
             ),
-        })
+            ("view", _) => (
+                Operation::RunView,
+                vec![matches.value_of("name").unwrap().to_string()],
+            ),
+            ("views", _) => (Operation::ListViews, vec![]),
+            ("trash", ("empty", Some(m))) => (
+                Operation::EmptyTrash,
+                Config::get_optional_args("older_than", m),
+            ),
+            ("trash", _) => (Operation::ListTrash, vec![]),
+            ("restore", _) => match matches.value_of("backup") {
+                Some(file) if matches.is_present("merge") => {
+                    (Operation::MergeBackup, vec![file.to_string()])
+                }
+                Some(file) => (Operation::RestoreBackup, vec![file.to_string()]),
+                None => (
+                    Operation::Restore,
+                    vec![matches.value_of("id").unwrap().to_string()],
+                ),
+            },
+            ("backup", _) => (
+                Operation::Backup,
+                Config::get_optional_args("file", matches),
+            ),
+            ("archive", _) => (
+                Operation::Archive,
+                Config::get_optional_args("before", matches),
+            ),
+            ("log", _) => (Operation::Log, Config::get_optional_args("n", matches)),
+            ("show", _) => (
+                if matches.is_present("history") {
+                    Operation::ShowHistory
+                } else {
+                    Operation::Show
+                },
+                vec![matches.value_of("id").unwrap().to_string()],
+            ),
+            ("start", _) => {
+                let mut args = Config::get_args_vector("id", matches);
+                args.extend(Config::get_args_vector("repo", matches));
+                (Operation::Start, args)
+            }
+            ("git", ("install-hooks", Some(m))) => {
+                (Operation::InstallHooks, Config::get_args_vector("repo", m))
+            }
+            ("git", (_, Some(m))) => (Operation::PostCommit, Config::get_args_vector("repo", m)),
+            ("daemon", _) if matches.is_present("stop") => (Operation::StopDaemon, vec![]),
+            ("daemon", _) => (Operation::Daemon, vec![]),
+            ("stdio", _) => (Operation::Stdio, vec![]),
+            ("doctor", _) if matches.is_present("fix") => (Operation::DoctorFix, vec![]),
+            ("doctor", _) => (Operation::Doctor, vec![]),
+            // The format and the file, with the flags kept in their options
+            ("import", _) => {
+                let mut args = Config::get_args_vector("format", matches);
+                args.extend(Config::get_args_vector("file", matches));
+                (Operation::Import, args)
+            }
+            ("export", _) => {
+                let mut args = Config::get_args_vector("format", matches);
+                args.extend(Config::get_optional_args("file", matches));
+                (Operation::Export, args)
+            }
+            ("scan", _) => (Operation::Scan, Config::get_args_vector("path", matches)),
+            ("batch", _) => (
+                if matches.is_present("continue_on_error") {
+                    Operation::BatchContinueOnError
+                } else {
+                    Operation::Batch
+                },
+                Config::get_args_vector("file", matches),
+            ),
+            ("done", _) if matches.is_present("where") => (
+                Operation::DoneWhere,
+                vec![matches.value_of("where").unwrap().to_string()],
+            ),
+            ("done", _) => (Operation::Done, Config::get_args_vector("ids", matches)),
+            ("undo", _) if matches.is_present("list") => (Operation::UndoList, vec![]),
+            ("redo", _) if matches.is_present("list") => (Operation::RedoList, vec![]),
+            ("undo", _) => (Operation::Undo, Config::get_optional_args("steps", matches)),
+            ("redo", _) => (Operation::Redo, Config::get_optional_args("steps", matches)),
+            _ => panic!("Unknown subcommand '{}'", name),
+        }
     }
 
     fn get_args_vector(name: &str, matches: &ArgMatches) -> Vec<String> {
@@ -88,12 +413,93 @@ impl Config {
             .collect()
     }
 
+    fn get_import_options(matches: &ArgMatches) -> ImportOptions {
+        ImportOptions {
+            columns: matches.value_of("columns").map(String::from),
+            no_header: matches.is_present("no_header"),
+            keep_ids: matches.is_present("keep_ids"),
+            dry_run: matches.is_present("dry_run"),
+            sync: matches.is_present("sync"),
+        }
+    }
+
+    fn get_export_options(matches: &ArgMatches) -> RtcResult<ExportOptions> {
+        Ok(ExportOptions {
+            columns: matches.value_of("columns").map(String::from),
+            no_header: matches.is_present("no_header"),
+            group_by: matches
+                .value_of("group_by")
+                .map(GroupBy::from_str)
+                .transpose()?,
+            filters: Config::get_optional_args("filter", matches),
+        })
+    }
+
+    fn get_optional_args(name: &str, matches: &ArgMatches) -> Vec<String> {
+        if matches.is_present(name) {
+            Config::get_args_vector(name, matches)
+        } else {
+            vec![]
+        }
+    }
+
+    /// Shares the database and settings of `session`, for the lines of a batch
+    pub(crate) fn in_session_of(mut self, session: &Config) -> Config {
+        self.settings = session.settings.clone();
+        self.database_file = session.database_file.clone();
+        self.assume_yes |= session.assume_yes;
+        self.working_dir = session.working_dir.clone();
+        self
+    }
+
+    /// Resolves relative paths given as arguments against `dir` instead of the current directory
+    pub(crate) fn in_dir(mut self, dir: Option<PathBuf>) -> Config {
+        self.working_dir = dir;
+        self
+    }
+
     /// Used in test cases
     pub fn new_for_testing(operation: Operation, args: Vec<String>, db_name: &str) -> Config {
         Config {
             operation,
             args,
+            list_options: ListOptions::default(),
+            import_options: ImportOptions::default(),
+            export_options: ExportOptions::default(),
+            settings: Settings::default(),
             database_file: String::from(db_name),
+            assume_yes: false,
+            working_dir: None,
         }
     }
+
+    /// Skips confirmations, e.g. in sessions where there is nobody to answer them
+    pub fn with_assume_yes(mut self) -> Config {
+        self.assume_yes = true;
+        self
+    }
+
+    /// Used in test cases
+    pub fn with_settings(mut self, settings: Settings) -> Config {
+        self.settings = settings;
+        self
+    }
+
+    /// Used in test cases
+    pub fn with_list_options(mut self, list_options: ListOptions) -> Config {
+        self.list_options = list_options;
+        self
+    }
+
+    /// Used in test cases
+    pub fn with_import_options(mut self, import_options: ImportOptions) -> Config {
+        self.import_options = import_options;
+        self
+    }
+
+    /// Used in test cases
+    pub fn with_export_options(mut self, export_options: ExportOptions) -> Config {
+        self.export_options = export_options;
+        self
+    }
 }
@@ -0,0 +1,167 @@
+use crate::{DatabaseWrapper, DbDriver, RtcError, RtcResult};
+use chrono::serde::ts_milliseconds;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// Version of the backup document, bumped whenever its layout changes
+pub const BACKUP_VERSION: u32 = 1;
+/// Marks a JSON document as a backup
+const BACKUP_FORMAT: &str = "rust-todo-cli backup";
+/// Automatic backups are named with this prefix, so that rotation leaves other files alone
+const AUTO_PREFIX: &str = "auto-";
+const TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%S%3f";
+
+/// Every record of a database, i.e. the items under their ID as well as the ID counter,
+/// trash, archive, views, journal and schema version, exactly as they are stored
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Backup {
+    format: String,
+    version: u32,
+    #[serde(with = "ts_milliseconds")]
+    created_at: DateTime<Utc>,
+    /// Version of the layout the records are in
+    schema_version: u32,
+    records: BTreeMap<String, Value>,
+}
+
+impl Backup {
+    pub fn of<T: DbDriver>(db_wrapper: &DatabaseWrapper<T>) -> Backup {
+        let mut records = records_of(&db_wrapper.driver);
+        // The archive has its own file, but backups hold it like any other record
+        let archive = records_of(&db_wrapper.archive);
+        if !archive.is_empty() {
+            records.insert(
+                db_wrapper.key_archive().clone(),
+                Value::Object(archive.into_iter().collect()),
+            );
+        }
+
+        Backup {
+            format: String::from(BACKUP_FORMAT),
+            version: BACKUP_VERSION,
+            created_at: Utc::now(),
+            // Databases without a version predate versioning
+            schema_version: db_wrapper
+                .driver
+                .get::<u32>(db_wrapper.key_schema_version())
+                .unwrap_or(0),
+            records,
+        }
+    }
+
+    /// Reads a backup, rejecting other documents and backups written by a newer version
+    pub fn parse(text: &str) -> RtcResult<Backup> {
+        let backup = serde_json::from_str::<Backup>(text)
+            .map_err(|e| RtcError::Parse(format!("Backup, {}", e)))?;
+
+        if backup.format != BACKUP_FORMAT {
+            return Err(RtcError::Parse(format!(
+                "Backup, unknown format '{}'",
+                backup.format
+            )));
+        }
+        if backup.version > BACKUP_VERSION {
+            return Err(RtcError::Parse(format!(
+                "Backup version {} is newer than the supported version {}",
+                backup.version, BACKUP_VERSION
+            )));
+        }
+        Ok(backup)
+    }
+
+    pub fn to_json(&self) -> RtcResult<String> {
+        serde_json::to_string_pretty(self).map_err(|e| RtcError::Io(e.to_string()))
+    }
+
+    pub fn schema_version(&self) -> u32 {
+        self.schema_version
+    }
+
+    pub fn into_records(self) -> BTreeMap<String, Value> {
+        self.records
+    }
+}
+
+fn records_of<T: DbDriver>(driver: &T) -> BTreeMap<String, Value> {
+    driver
+        .keys()
+        .into_iter()
+        .filter_map(|key| {
+            let value = driver.get::<Value>(&key)?;
+            Some((key, value))
+        })
+        .collect()
+}
+
+/// Directory automatic backups are written to, keeping only the most recent ones
+pub struct BackupRotation {
+    dir: PathBuf,
+    keep: usize,
+}
+
+impl BackupRotation {
+    /// No backups are taken if `keep` is `0`
+    pub fn new(dir: PathBuf, keep: usize) -> BackupRotation {
+        BackupRotation { dir, keep }
+    }
+
+    /// Backs up the database before an operation named `reason`, then removes the oldest
+    /// automatic backups beyond the number to keep
+    pub fn save<T: DbDriver>(
+        &self,
+        db_wrapper: &DatabaseWrapper<T>,
+        reason: &str,
+    ) -> RtcResult<Option<PathBuf>> {
+        self.save_backup(Backup::of(db_wrapper), reason)
+    }
+
+    /// Like [`BackupRotation::save`], with the backup already taken
+    pub fn save_backup(&self, backup: Backup, reason: &str) -> RtcResult<Option<PathBuf>> {
+        if self.keep == 0 {
+            return Ok(None);
+        }
+
+        let file = self.dir.join(format!(
+            "{}{}-{}.json",
+            AUTO_PREFIX,
+            backup.created_at.format(TIMESTAMP_FORMAT),
+            reason
+        ));
+        fs::create_dir_all(&self.dir)?;
+        fs::write(&file, backup.to_json()?)?;
+
+        self.remove_old()?;
+        Ok(Some(file))
+    }
+
+    fn remove_old(&self) -> RtcResult<()> {
+        let mut backups = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(AUTO_PREFIX))
+            })
+            .collect::<Vec<PathBuf>>();
+
+        // Names start with their timestamp, so the oldest sort first
+        backups.sort();
+        let excess = backups.len().saturating_sub(self.keep);
+        for path in &backups[..excess] {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// File name for a backup taken on request, which is not rotated
+    pub fn manual_file(&self) -> PathBuf {
+        self.dir.join(format!(
+            "backup-{}.json",
+            Utc::now().format(TIMESTAMP_FORMAT)
+        ))
+    }
+}
@@ -1,12 +1,18 @@
 use crate::RtcError;
-use pickledb::PickleDb;
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 
 /// Struct to hold a database implementation and some metadata
 pub struct DatabaseWrapper<T: DbDriver> {
     pub driver: T,
+    /// Archived items under their ID, kept apart so that the database stays small
+    pub archive: T,
     key_total_creations: String,
+    key_views: String,
+    key_journal: String,
+    key_trash: String,
+    key_archive: String,
+    key_schema_version: String,
 }
 
 impl<T: DbDriver> DatabaseWrapper<T> {
@@ -14,11 +20,59 @@ impl<T: DbDriver> DatabaseWrapper<T> {
         &self.key_total_creations
     }
 
-    pub fn new(db_driver: T) -> DatabaseWrapper<T> {
+    pub fn key_views(&self) -> &String {
+        &self.key_views
+    }
+
+    pub fn key_journal(&self) -> &String {
+        &self.key_journal
+    }
+
+    pub fn key_trash(&self) -> &String {
+        &self.key_trash
+    }
+
+    /// Key of the archive in backups, and in databases from before it got its own file
+    pub fn key_archive(&self) -> &String {
+        &self.key_archive
+    }
+
+    pub fn key_schema_version(&self) -> &String {
+        &self.key_schema_version
+    }
+
+    pub fn new(db_driver: T, archive_driver: T) -> DatabaseWrapper<T> {
         DatabaseWrapper {
             driver: db_driver,
+            archive: archive_driver,
             key_total_creations: String::from("total_creations"),
+            key_views: String::from("views"),
+            key_journal: String::from("journal"),
+            key_trash: String::from("trash"),
+            key_archive: String::from("archive"),
+            key_schema_version: String::from("schema_version"),
+        }
+    }
+
+    /// Starts a transaction on both the database and the archive
+    pub fn begin(&mut self) {
+        self.driver.begin();
+        self.archive.begin();
+    }
+
+    /// Commits the archive first, so that items moved into it are never lost. Should that fail,
+    /// the database is rolled back.
+    pub fn commit(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Err(e) = self.archive.commit() {
+            let _ = self.driver.rollback();
+            return Err(e);
         }
+        self.driver.commit()
+    }
+
+    pub fn rollback(&mut self) -> Result<(), Box<dyn Error>> {
+        let archive = self.archive.rollback();
+        self.driver.rollback().and(archive)
     }
     // This struct could also have the crud functions as methods
 }
@@ -28,49 +82,19 @@ impl<T: DbDriver> DatabaseWrapper<T> {
 pub trait DbDriver {
     fn get<V: for<'de> Deserialize<'de>>(&self, key: &str) -> Option<V>;
     fn get_all<V: for<'de> Deserialize<'de>>(&self) -> Vec<V>;
+    fn keys(&self) -> Vec<String>;
     fn set<V: Serialize>(&mut self, key: &str, value: &V) -> Result<(), Box<dyn Error>>;
     fn del(&mut self, key: &str) -> Result<bool, Box<dyn Error>>;
+    /// Sets a value that is only written along with the next change, and that no rollback reverts
+    fn stage<V: Serialize>(&mut self, key: &str, value: &V) -> Result<(), Box<dyn Error>>;
     fn n_keys(&self) -> usize;
     fn error_if_db_empty(&self, msg: &str) -> Result<(), RtcError>;
-}
-
-impl DbDriver for PickleDb {
-    fn get<V: for<'de> Deserialize<'de>>(&self, key: &str) -> Option<V> {
-        self.get::<V>(key)
-    }
-
-    fn get_all<V: for<'de> Deserialize<'de>>(&self) -> Vec<V> {
-        self.iter()
-            // Could also use .filter and .is_some or filter_map to map Options to values,
-            // and get rid of Nones
-            // https://stackoverflow.com/a/30590082/4644044
-            .flat_map(|kv| kv.get_value::<V>())
-            .collect()
-    }
-
-    fn set<V: Serialize>(&mut self, key: &str, value: &V) -> Result<(), Box<dyn Error>> {
-        match self.set(key, value) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(Box::new(e)),
-        }
-    }
-
-    fn del(&mut self, key: &str) -> Result<bool, Box<dyn Error>> {
-        match self.rem(key) {
-            Ok(x) => Ok(x),
-            Err(e) => Err(Box::new(e)),
-        }
-    }
 
-    fn n_keys(&self) -> usize {
-        self.total_keys()
-    }
-
-    fn error_if_db_empty(&self, msg: &str) -> Result<(), RtcError> {
-        if self.n_keys() < 2 {
-            Err(RtcError::NoItems(String::from(msg)))
-        } else {
-            Ok(())
-        }
-    }
+    /// Starts a transaction, which may be nested in another one. Changes made outside of
+    /// transactions are kept right away.
+    fn begin(&mut self);
+    /// Keeps the changes since the matching `begin`, once the outermost transaction commits
+    fn commit(&mut self) -> Result<(), Box<dyn Error>>;
+    /// Reverts the changes since the matching `begin`
+    fn rollback(&mut self) -> Result<(), Box<dyn Error>>;
 }
@@ -0,0 +1,37 @@
+use crate::RtcResult;
+use fs2::FileExt;
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+
+/// Advisory lock that keeps other `rtc` processes from loading the database
+/// while this one may still write it. Released when dropped.
+pub struct FileLock {
+    file: File,
+}
+
+impl FileLock {
+    /// Blocks until the lock at `lock_file` is acquired.
+    ///
+    /// A separate lock file is used because dumps replace the database file
+    /// by renaming, which would leave a lock on the database file itself behind.
+    pub fn acquire(lock_file: &Path) -> RtcResult<FileLock> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(lock_file)?;
+
+        if file.try_lock_exclusive().is_err() {
+            eprintln!("Waiting for another rtc process to finish");
+            file.lock_exclusive()?;
+        }
+        Ok(FileLock { file })
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
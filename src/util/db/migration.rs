@@ -0,0 +1,253 @@
+use crate::util::db::backup::BackupRotation;
+use crate::{DatabaseWrapper, DbDriver, RtcError, RtcResult};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// Version of the layout items are stored in, bumped whenever a migration is added
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// Upgrades a single stored item by one schema version
+type ItemMigration = fn(&mut Map<String, Value>);
+
+/// The migration at index `n` upgrades items from version `n` to `n + 1`
+const MIGRATIONS: [(&str, ItemMigration); SCHEMA_VERSION as usize] = [
+    ("add completion and modification dates", v0_to_v1),
+    ("add priority, projects, tags and extensions", v1_to_v2),
+    ("add due dates and uuids", v2_to_v3),
+];
+
+fn v0_to_v1(item: &mut Map<String, Value>) {
+    item.entry("completed_at").or_insert(Value::Null);
+    item.entry("modified_at").or_insert(Value::Null);
+}
+
+fn v1_to_v2(item: &mut Map<String, Value>) {
+    item.entry("priority").or_insert(Value::Null);
+    item.entry("projects").or_insert(Value::Array(vec![]));
+    item.entry("tags").or_insert(Value::Array(vec![]));
+    item.entry("extensions")
+        .or_insert(Value::Object(Map::new()));
+}
+
+fn v2_to_v3(item: &mut Map<String, Value>) {
+    item.entry("due").or_insert(Value::Null);
+    item.entry("uuid").or_insert(Value::Null);
+}
+
+/// Upgrades all items stored in an older schema version, after backing up the database file
+/// and taking an automatic backup. Returns the version the database was migrated from, if it
+/// had to be.
+pub fn migrate<T: DbDriver>(
+    db_wrapper: &mut DatabaseWrapper<T>,
+    database_file: &str,
+    backups: &BackupRotation,
+    out: &mut dyn Write,
+) -> RtcResult<Option<u32>> {
+    let key = db_wrapper.key_schema_version().clone();
+    let version = match db_wrapper.driver.get::<u32>(&key) {
+        Some(v) => v,
+        // Databases without any keys are new, not old, and only written once something is added
+        None if db_wrapper.driver.n_keys() == 0 => {
+            db_wrapper
+                .driver
+                .stage(&key, &SCHEMA_VERSION)
+                .map_err(|e| RtcError::Migration(format!("Error: {}", e)))?;
+            return Ok(None);
+        }
+        None => 0,
+    };
+
+    if version > SCHEMA_VERSION {
+        return Err(RtcError::Migration(format!(
+            "Database schema version {} is newer than the supported version {}",
+            version, SCHEMA_VERSION
+        )));
+    }
+    if version == SCHEMA_VERSION {
+        return Ok(None);
+    }
+
+    let backup_file = Path::new(database_file).with_extension(format!("v{}.backup.json", version));
+    fs::copy(database_file, &backup_file)?;
+    writeln!(out, "Backed up db to {}", backup_file.display())?;
+    if let Some(file) = backups.save(db_wrapper, "migration")? {
+        writeln!(out, "Backed up db to {}", file.display())?;
+    }
+
+    // A failed migration leaves the db as it was
+    db_wrapper.begin();
+    match migrate_all(db_wrapper, version, out) {
+        Ok(_) => db_wrapper
+            .commit()
+            .map_err(|e| RtcError::Migration(format!("Error: {}", e)))?,
+        Err(e) => {
+            let _ = db_wrapper.rollback();
+            return Err(e);
+        }
+    }
+    Ok(Some(version))
+}
+
+fn migrate_all<T: DbDriver>(
+    db_wrapper: &mut DatabaseWrapper<T>,
+    version: u32,
+    out: &mut dyn Write,
+) -> RtcResult<()> {
+    separate_archive(db_wrapper)?;
+    for (from, (description, migration)) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        writeln!(
+            out,
+            "Migrating db from schema version {} to {}: {}",
+            from,
+            from + 1,
+            description
+        )?;
+        migrate_items(db_wrapper, *migration)?;
+    }
+
+    set_version(db_wrapper, SCHEMA_VERSION)
+}
+
+/// Moves the archive, which older schema versions kept in the database, into its own file
+fn separate_archive<T: DbDriver>(db_wrapper: &mut DatabaseWrapper<T>) -> RtcResult<()> {
+    let to_error = |e| RtcError::Migration(format!("Error: {}", e));
+    let key = db_wrapper.key_archive().clone();
+    let archive = match db_wrapper.driver.get::<Map<String, Value>>(&key) {
+        Some(a) => a,
+        None => return Ok(()),
+    };
+
+    for (id, item) in &archive {
+        db_wrapper.archive.set(id, item).map_err(to_error)?;
+    }
+    db_wrapper.driver.del(&key).map_err(to_error)?;
+    Ok(())
+}
+
+/// Applies `migration` to every stored item, including archived, trashed and journaled ones
+fn migrate_items<T: DbDriver>(
+    db_wrapper: &mut DatabaseWrapper<T>,
+    migration: ItemMigration,
+) -> RtcResult<()> {
+    for key in db_wrapper.archive.keys() {
+        if let Some(mut value) = db_wrapper.archive.get::<Value>(&key) {
+            if let Value::Object(item) = &mut value {
+                migration(item);
+            }
+            db_wrapper
+                .archive
+                .set(&key, &value)
+                .map_err(|e| RtcError::Migration(format!("Error: {}", e)))?;
+        }
+    }
+    for key in db_wrapper.driver.keys() {
+        let mut value = match db_wrapper.driver.get::<Value>(&key) {
+            Some(v) => v,
+            None => continue,
+        };
+
+        if !migrate_record(db_wrapper, &key, &mut value, migration) {
+            continue;
+        }
+        db_wrapper
+            .driver
+            .set(&key, &value)
+            .map_err(|e| RtcError::Migration(format!("Error: {}", e)))?;
+    }
+    Ok(())
+}
+
+/// Upgrades records that are not stored, e.g. those of a backup, from schema `version`
+pub(crate) fn migrate_records<T: DbDriver>(
+    db_wrapper: &DatabaseWrapper<T>,
+    records: &mut BTreeMap<String, Value>,
+    version: u32,
+) -> RtcResult<()> {
+    if version > SCHEMA_VERSION {
+        return Err(RtcError::Migration(format!(
+            "Schema version {} is newer than the supported version {}",
+            version, SCHEMA_VERSION
+        )));
+    }
+
+    for (_, migration) in MIGRATIONS.iter().skip(version as usize) {
+        for (key, value) in records.iter_mut() {
+            migrate_record(db_wrapper, key, value, *migration);
+        }
+    }
+    records.insert(
+        db_wrapper.key_schema_version().clone(),
+        Value::from(SCHEMA_VERSION),
+    );
+    Ok(())
+}
+
+/// Applies `migration` to the items in the record stored under `key`.
+/// Returns whether the record holds items.
+fn migrate_record<T: DbDriver>(
+    db_wrapper: &DatabaseWrapper<T>,
+    key: &str,
+    value: &mut Value,
+    migration: ItemMigration,
+) -> bool {
+    let apply = |value: &mut Value| {
+        if let Value::Object(item) = value {
+            migration(item);
+        }
+    };
+
+    if key.parse::<usize>().is_ok() {
+        apply(value);
+    } else if key == db_wrapper.key_archive() {
+        value
+            .as_object_mut()
+            .into_iter()
+            .flat_map(|m| m.values_mut())
+            .for_each(apply);
+    } else if key == db_wrapper.key_trash() {
+        value
+            .as_object_mut()
+            .into_iter()
+            .flat_map(|m| m.values_mut())
+            .filter_map(|trashed| trashed.get_mut("item"))
+            .for_each(apply);
+    } else if key == db_wrapper.key_journal() {
+        for stack in &["undo", "redo"] {
+            value
+                .get_mut(stack)
+                .and_then(Value::as_array_mut)
+                .into_iter()
+                .flatten()
+                .filter_map(|entry| entry.get_mut("changes").and_then(Value::as_array_mut))
+                .flatten()
+                .filter_map(Value::as_object_mut)
+                // A change holds either an item or its states before and after
+                .flat_map(|change| change.values_mut())
+                .for_each(|inner| {
+                    if inner.get("before").is_some() {
+                        for state in &["before", "after"] {
+                            if let Some(v) = inner.get_mut(*state) {
+                                apply(v);
+                            }
+                        }
+                    } else {
+                        apply(inner);
+                    }
+                });
+        }
+    } else {
+        return false;
+    }
+    true
+}
+
+fn set_version<T: DbDriver>(db_wrapper: &mut DatabaseWrapper<T>, version: u32) -> RtcResult<()> {
+    let key = db_wrapper.key_schema_version().clone();
+    db_wrapper
+        .driver
+        .set(&key, &version)
+        .map_err(|e| RtcError::Migration(format!("Error: {}", e)))
+}
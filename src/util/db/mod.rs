@@ -1,2 +1,5 @@
+pub(crate) mod backup;
 pub(crate) mod database_wrapper;
+pub(crate) mod file_lock;
+pub(crate) mod migration;
 pub(crate) mod pickle_db_impl;
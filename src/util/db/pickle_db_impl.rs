@@ -1,25 +1,158 @@
+use crate::{DbDriver, RtcError, RtcResult};
 use pickledb::{PickleDb, PickleDbDumpPolicy, SerializationMethod};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+use std::io::Write;
+use std::path::Path;
 
-pub fn init_pickle_db(filename: &str) -> PickleDb {
-    // Try loading existing DB from a file
-    let attempt_db_load = PickleDb::load(
-        filename,
-        PickleDbDumpPolicy::AutoDump,
-        SerializationMethod::Json,
-    );
-
-    // If no existing db, create new one
-    let db = if let Ok(d) = attempt_db_load {
-        println!("Using existing db at {}", filename);
-        d
-    } else {
-        println!("Creating new db at {}", filename);
-        PickleDb::new(
+/// Previous values of the keys changed in a transaction, `None` for keys that did not exist
+type UndoLog = Vec<(String, Option<Value>)>;
+
+/// [`PickleDb`] that writes its file only when changes are committed
+pub struct PickleDbDriver {
+    db: PickleDb,
+    /// One log per open transaction, innermost last
+    transactions: Vec<UndoLog>,
+}
+
+impl PickleDbDriver {
+    /// Takes over `db`, which must not dump by itself
+    pub fn new(db: PickleDb) -> PickleDbDriver {
+        PickleDbDriver {
+            db,
+            transactions: vec![],
+        }
+    }
+
+    fn log_previous(&mut self, key: &str) {
+        let previous = self.db.get::<Value>(key);
+        if let Some(log) = self.transactions.last_mut() {
+            log.push((String::from(key), previous));
+        }
+    }
+
+    /// Outside of transactions, every change is kept right away
+    fn dump_unless_in_transaction(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.transactions.is_empty() {
+            self.db.dump()?;
+        }
+        Ok(())
+    }
+}
+
+/// Loads the db in `filename`, or creates a new one if there is no such file. A file that can not
+/// be loaded is an error, so that it is never replaced by an empty db.
+pub fn init_pickle_db(filename: &str, out: &mut dyn Write) -> RtcResult<PickleDbDriver> {
+    if !Path::new(filename).exists() {
+        writeln!(out, "Creating new db at {}", filename)?;
+        return Ok(PickleDbDriver::new(PickleDb::new(
             filename,
-            PickleDbDumpPolicy::AutoDump,
+            PickleDbDumpPolicy::DumpUponRequest,
             SerializationMethod::Json,
-        )
-    };
+        )));
+    }
+
+    let db = PickleDb::load(
+        filename,
+        PickleDbDumpPolicy::DumpUponRequest,
+        SerializationMethod::Json,
+    )
+    .map_err(|e| RtcError::Load(format!("{}: {}", filename, e)))?;
+    writeln!(out, "Using existing db at {}", filename)?;
+    Ok(PickleDbDriver::new(db))
+}
+
+impl DbDriver for PickleDbDriver {
+    fn get<V: for<'de> Deserialize<'de>>(&self, key: &str) -> Option<V> {
+        self.db.get::<V>(key)
+    }
+
+    fn get_all<V: for<'de> Deserialize<'de>>(&self) -> Vec<V> {
+        self.db
+            .iter()
+            // Could also use .filter and .is_some or filter_map to map Options to values,
+            // and get rid of Nones
+            // https://stackoverflow.com/a/30590082/4644044
+            .flat_map(|kv| kv.get_value::<V>())
+            .collect()
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.db.get_all()
+    }
+
+    fn set<V: Serialize>(&mut self, key: &str, value: &V) -> Result<(), Box<dyn Error>> {
+        self.log_previous(key);
+        match self.db.set(key, value) {
+            Ok(_) => self.dump_unless_in_transaction(),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    fn del(&mut self, key: &str) -> Result<bool, Box<dyn Error>> {
+        self.log_previous(key);
+        match self.db.rem(key) {
+            Ok(x) => {
+                self.dump_unless_in_transaction()?;
+                Ok(x)
+            }
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    fn stage<V: Serialize>(&mut self, key: &str, value: &V) -> Result<(), Box<dyn Error>> {
+        self.db.set(key, value)?;
+        Ok(())
+    }
+
+    fn n_keys(&self) -> usize {
+        self.db.total_keys()
+    }
+
+    fn error_if_db_empty(&self, msg: &str) -> Result<(), RtcError> {
+        // Items are stored under their numeric ID, everything else is metadata
+        if !self.keys().iter().any(|k| k.parse::<usize>().is_ok()) {
+            Err(RtcError::NoItems(String::from(msg)))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn begin(&mut self) {
+        self.transactions.push(UndoLog::new());
+    }
+
+    fn commit(&mut self) -> Result<(), Box<dyn Error>> {
+        let log = self
+            .transactions
+            .pop()
+            .ok_or("Commit without open transaction")?;
+
+        match self.transactions.last_mut() {
+            // The outer transaction may still roll these changes back
+            Some(outer) => outer.extend(log),
+            None if !log.is_empty() => self.db.dump()?,
+            None => (),
+        }
+        Ok(())
+    }
+
+    fn rollback(&mut self) -> Result<(), Box<dyn Error>> {
+        let log = self
+            .transactions
+            .pop()
+            .ok_or("Rollback without open transaction")?;
 
-    db
+        // Restore the oldest value of every key last
+        for (key, previous) in log.into_iter().rev() {
+            match previous {
+                Some(value) => self.db.set(&key, &value)?,
+                None => {
+                    self.db.rem(&key)?;
+                }
+            }
+        }
+        Ok(())
+    }
 }
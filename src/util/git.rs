@@ -0,0 +1,132 @@
+use crate::{RtcError, RtcResult};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Marks hooks installed by rtc, so that installing again replaces them but leaves others alone
+const HOOK_MARKER: &str = "# Installed by rtc git install-hooks";
+/// Words before an item reference that mark it as done, as in `closes rtc#12`
+const CLOSING_WORDS: [&str; 9] = [
+    "close", "closes", "closed", "fix", "fixes", "fixed", "resolve", "resolves", "resolved",
+];
+const REFERENCE_PREFIX: &str = "rtc#";
+
+/// An item mentioned in a commit message
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reference {
+    pub id: usize,
+    /// The item is done with the commit
+    pub closes: bool,
+}
+
+/// Runs `git` in `repo`, returning its trimmed output
+fn git(repo: &Path, args: &[&str]) -> RtcResult<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .map_err(|e| RtcError::Git(format!("could not run git, {}", e)))?;
+
+    if !output.status.success() {
+        return Err(RtcError::Git(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+pub fn current_branch(repo: &Path) -> RtcResult<String> {
+    let branch = git(repo, &["rev-parse", "--abbrev-ref", "HEAD"])?;
+    if branch == "HEAD" {
+        return Err(RtcError::Git(String::from(
+            "HEAD is detached, not on a branch",
+        )));
+    }
+    Ok(branch)
+}
+
+/// Hash and message of the commit at `HEAD`
+pub fn last_commit(repo: &Path) -> RtcResult<(String, String)> {
+    let hash = git(repo, &["rev-parse", "HEAD"])?;
+    let message = git(repo, &["log", "-1", "--format=%B"])?;
+    Ok((hash, message))
+}
+
+/// Installs a `post-commit` hook running `command`, which is the path of this binary.
+/// Hooks that were not installed by rtc are not replaced.
+pub fn install_hook(repo: &Path, command: &Path) -> RtcResult<PathBuf> {
+    // Also respects `core.hooksPath` and worktrees
+    let hooks = PathBuf::from(git(repo, &["rev-parse", "--git-path", "hooks"])?);
+    let hooks = if hooks.is_relative() {
+        repo.join(hooks)
+    } else {
+        hooks
+    };
+    let hook = hooks.join("post-commit");
+
+    if let Ok(existing) = fs::read_to_string(&hook) {
+        if !existing.contains(HOOK_MARKER) {
+            return Err(RtcError::Git(format!(
+                "{} already exists, add '{} git post-commit' to it instead",
+                hook.display(),
+                command.display()
+            )));
+        }
+    }
+
+    fs::create_dir_all(&hooks)?;
+    // A failing hook must not get in the way of committing
+    fs::write(
+        &hook,
+        format!(
+            "#!/bin/sh\n{}\n\"{}\" git post-commit || true\n",
+            HOOK_MARKER,
+            command.display()
+        ),
+    )?;
+    make_executable(&hook)?;
+    Ok(hook)
+}
+
+#[cfg(unix)]
+fn make_executable(file: &Path) -> RtcResult<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(file, fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_executable(_file: &Path) -> RtcResult<()> {
+    Ok(())
+}
+
+/// Finds references like `rtc#12` in `message`, which close the item when preceded by a word
+/// like `closes` or `fixes`. Items referenced more than once close if any reference does.
+pub fn parse_references(message: &str) -> Vec<Reference> {
+    let mut references: Vec<Reference> = vec![];
+    let mut previous = "";
+
+    for word in message.split_whitespace() {
+        // References may be in parentheses or lists, as in `(rtc#12, rtc#13)`
+        let id = word
+            .trim_start_matches(|c: char| !c.is_alphanumeric())
+            .strip_prefix(REFERENCE_PREFIX)
+            .map(|rest| {
+                rest.split(|c: char| !c.is_ascii_digit())
+                    .next()
+                    .unwrap_or("")
+            })
+            .and_then(|digits| digits.parse::<usize>().ok());
+        if let Some(id) = id {
+            let closes =
+                CLOSING_WORDS.contains(&&previous.trim_end_matches(':').to_lowercase()[..]);
+            match references.iter_mut().find(|r| r.id == id) {
+                Some(r) => r.closes |= closes,
+                None => references.push(Reference { id, closes }),
+            }
+        }
+        previous = word;
+    }
+    references
+}
@@ -0,0 +1,166 @@
+use crate::util::audit_log::AuditAction;
+use crate::TodoItem;
+use chrono::serde::ts_milliseconds;
+use chrono::{DateTime, Utc};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Number of operations that can be undone
+const MAX_ENTRIES: usize = 100;
+
+/// A change made to a single item, holding enough state to be reverted
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum Change {
+    Added(TodoItem),
+    /// Moved to the trash
+    Deleted(TodoItem),
+    /// Brought back from the trash
+    Restored(TodoItem),
+    /// Moved to the archive
+    Archived(TodoItem),
+    Updated {
+        before: TodoItem,
+        after: TodoItem,
+    },
+}
+
+impl Change {
+    /// ID of the changed item
+    pub(crate) fn id(&self) -> usize {
+        match self {
+            Change::Added(item)
+            | Change::Deleted(item)
+            | Change::Restored(item)
+            | Change::Archived(item)
+            | Change::Updated { after: item, .. } => *item.id(),
+        }
+    }
+
+    /// The live item before and after the change, `None` where there was none
+    pub(crate) fn states(&self) -> (Option<&TodoItem>, Option<&TodoItem>) {
+        match self {
+            Change::Added(item) | Change::Restored(item) => (None, Some(item)),
+            Change::Deleted(item) | Change::Archived(item) => (Some(item), None),
+            Change::Updated { before, after } => (Some(before), Some(after)),
+        }
+    }
+
+    pub(crate) fn audit_action(&self) -> AuditAction {
+        match self {
+            Change::Added(_) => AuditAction::Create,
+            Change::Deleted(_) => AuditAction::Delete,
+            Change::Restored(_) => AuditAction::Restore,
+            Change::Archived(_) => AuditAction::Archive,
+            Change::Updated { .. } => AuditAction::Update,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Added(item) => write!(
+                f,
+                "add of item '{}' ('{}')",
+                item.id().to_string().blue(),
+                item.name().green()
+            ),
+            Change::Deleted(item) => write!(
+                f,
+                "delete of item '{}' ('{}')",
+                item.id().to_string().blue(),
+                item.name().green()
+            ),
+            Change::Restored(item) => write!(
+                f,
+                "restore of item '{}' ('{}')",
+                item.id().to_string().blue(),
+                item.name().green()
+            ),
+            Change::Archived(item) => write!(
+                f,
+                "archive of item '{}' ('{}')",
+                item.id().to_string().blue(),
+                item.name().green()
+            ),
+            Change::Updated { before, after } => write!(
+                f,
+                "update of item '{}' ('{}'), status '{}' -> '{}'",
+                after.id().to_string().blue(),
+                after.name().green(),
+                before.status.to_string().yellow(),
+                after.status.to_string().yellow()
+            ),
+        }
+    }
+}
+
+/// The changes of a single operation, which are undone and redone together
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JournalEntry {
+    /// In the order they were made
+    pub changes: Vec<Change>,
+    #[serde(with = "ts_milliseconds")]
+    timestamp: DateTime<Utc>,
+}
+
+impl JournalEntry {
+    pub(crate) fn new(changes: Vec<Change>) -> JournalEntry {
+        JournalEntry {
+            changes,
+            timestamp: Utc::now(),
+        }
+    }
+
+    pub fn timestamp(&self) -> &DateTime<Utc> {
+        &self.timestamp
+    }
+}
+
+impl fmt::Display for JournalEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(first) = self.changes.first() {
+            write!(f, "{}", first)?;
+        }
+        if self.changes.len() > 1 {
+            write!(
+                f,
+                " and {} more changes",
+                (self.changes.len() - 1).to_string().blue()
+            )?;
+        }
+        write!(f, " at '{}'", self.timestamp.to_string().blue())
+    }
+}
+
+/// Undo and redo stacks of operations, most recent last
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub(crate) struct Journal {
+    undo: Vec<JournalEntry>,
+    redo: Vec<JournalEntry>,
+}
+
+impl Journal {
+    /// Records a new operation, which makes everything undone so far unredoable unless it
+    /// `keeps_redo`
+    pub(crate) fn record(&mut self, changes: Vec<Change>, keeps_redo: bool) {
+        self.undo.push(JournalEntry::new(changes));
+        if !keeps_redo {
+            self.redo.clear();
+        }
+
+        if self.undo.len() > MAX_ENTRIES {
+            let excess = self.undo.len() - MAX_ENTRIES;
+            self.undo.drain(..excess);
+        }
+    }
+
+    pub(crate) fn undo_stack(&mut self) -> &mut Vec<JournalEntry> {
+        &mut self.undo
+    }
+
+    pub(crate) fn redo_stack(&mut self) -> &mut Vec<JournalEntry> {
+        &mut self.redo
+    }
+}
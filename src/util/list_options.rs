@@ -0,0 +1,166 @@
+use crate::{RtcError, RtcResult, TodoItem};
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+/// Fields of a [`TodoItem`] that listings can be sorted by
+#[derive(Debug, Clone, Copy)]
+pub enum SortField {
+    Id,
+    Name,
+    Status,
+    Created,
+    Completed,
+    Modified,
+    Due,
+    Priority,
+}
+
+impl FromStr for SortField {
+    type Err = RtcError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &s.to_lowercase()[..] {
+            "id" => Ok(SortField::Id),
+            "name" => Ok(SortField::Name),
+            "status" => Ok(SortField::Status),
+            "created" | "creation_date" => Ok(SortField::Created),
+            "completed" | "completed_at" => Ok(SortField::Completed),
+            "modified" | "modified_at" => Ok(SortField::Modified),
+            "due" => Ok(SortField::Due),
+            "priority" => Ok(SortField::Priority),
+            _ => Err(RtcError::Parse(format!("Unknown sort field '{}'", s))),
+        }
+    }
+}
+
+/// A single sort key, e.g. `name` or `created:desc`
+#[derive(Debug, Clone, Copy)]
+pub struct SortKey {
+    field: SortField,
+    descending: bool,
+}
+
+impl SortKey {
+    fn compare(self, a: &TodoItem, b: &TodoItem) -> Ordering {
+        let ordering = match self.field {
+            SortField::Id => a.id().cmp(b.id()),
+            SortField::Name => a.name().to_lowercase().cmp(&b.name().to_lowercase()),
+            SortField::Status => a.status.cmp(&b.status),
+            SortField::Created => a.creation_date().cmp(b.creation_date()),
+            SortField::Modified => a.last_modified().cmp(b.last_modified()),
+            SortField::Completed => return self.compare_set(a.completed_at(), b.completed_at()),
+            SortField::Due => return self.compare_set(a.due, b.due),
+            // `A` is the highest priority, so it comes first
+            SortField::Priority => return self.compare_set(a.priority, b.priority),
+        };
+        self.directed(ordering)
+    }
+
+    /// Compares optional values, with items that have none sorted last in either direction
+    fn compare_set<V: Ord>(self, a: Option<V>, b: Option<V>) -> Ordering {
+        match (a, b) {
+            (Some(x), Some(y)) => self.directed(x.cmp(&y)),
+            (x, y) => y.is_some().cmp(&x.is_some()),
+        }
+    }
+
+    fn directed(self, ordering: Ordering) -> Ordering {
+        if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = RtcError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        let field = parts.next().unwrap_or_default().parse::<SortField>()?;
+        let descending = match parts.next().map(str::to_lowercase).as_deref() {
+            None | Some("asc") => false,
+            Some("desc") => true,
+            Some(d) => return Err(RtcError::Parse(format!("Unknown sort direction '{}'", d))),
+        };
+        Ok(SortKey { field, descending })
+    }
+}
+
+/// Sorting and pagination applied to the results of a listing operation,
+/// and whether archived items are part of those results
+#[derive(Debug, Clone, Default)]
+pub struct ListOptions {
+    sort_keys: Vec<SortKey>,
+    limit: Option<usize>,
+    offset: usize,
+    reverse: bool,
+    include_archived: bool,
+}
+
+impl ListOptions {
+    /// Parses the raw CLI values of `--sort`, `--limit`, `--offset` and `--reverse`
+    pub fn new(
+        sort_keys: &[String],
+        limit: Option<&str>,
+        offset: Option<&str>,
+        reverse: bool,
+    ) -> RtcResult<ListOptions> {
+        let sort_keys = sort_keys
+            .iter()
+            // Allow both `--sort name created` and `--sort name,created`
+            .flat_map(|s| s.split(','))
+            .filter(|s| !s.is_empty())
+            .map(str::parse::<SortKey>)
+            .collect::<RtcResult<Vec<SortKey>>>()?;
+
+        Ok(ListOptions {
+            sort_keys,
+            limit: limit.map(ListOptions::parse_number).transpose()?,
+            offset: offset
+                .map(ListOptions::parse_number)
+                .transpose()?
+                .unwrap_or(0),
+            reverse,
+            include_archived: false,
+        })
+    }
+
+    pub fn with_archived(mut self, include_archived: bool) -> ListOptions {
+        self.include_archived = include_archived;
+        self
+    }
+
+    pub fn include_archived(&self) -> bool {
+        self.include_archived
+    }
+
+    fn parse_number(s: &str) -> RtcResult<usize> {
+        s.parse::<usize>()
+            .map_err(|e| RtcError::Parse(format!("'{}', {}", s, e)))
+    }
+
+    /// Sorts by all sort keys in order, falling back to ascending ID
+    pub(crate) fn sort(&self, items: &mut [TodoItem]) {
+        items.sort_by(|a, b| {
+            self.sort_keys
+                .iter()
+                .fold(Ordering::Equal, |acc, key| {
+                    acc.then_with(|| key.compare(a, b))
+                })
+                .then_with(|| a.id().cmp(b.id()))
+        });
+
+        if self.reverse {
+            items.reverse();
+        }
+    }
+
+    /// Returns the page of `items` selected by offset and limit
+    pub(crate) fn paginate(&self, items: Vec<TodoItem>) -> Vec<TodoItem> {
+        items
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+}
@@ -1,3 +1,11 @@
+pub(crate) mod audit_log;
+pub(crate) mod cli;
+pub(crate) mod code_scan;
 pub(crate) mod config;
 pub(crate) mod db;
+pub(crate) mod git;
+pub(crate) mod journal;
+pub(crate) mod list_options;
+pub(crate) mod settings;
 pub(crate) mod todo_item;
+pub(crate) mod transfer_options;
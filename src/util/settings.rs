@@ -0,0 +1,29 @@
+use crate::{RtcError, RtcResult};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// User settings, read from `config.json` in the config directory
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct Settings {
+    /// Done items completed more than this many days ago are archived on every run
+    pub auto_archive_days: Option<i64>,
+    /// Name recorded in the audit log, defaults to `$USER`
+    pub user: Option<String>,
+    /// Automatic backups kept before destructive operations, defaults to 5, `0` turns them off
+    pub backups_to_keep: Option<usize>,
+}
+
+impl Settings {
+    /// Loads settings from `path`, using defaults if the file does not exist
+    pub fn load(path: &Path) -> RtcResult<Settings> {
+        if !path.exists() {
+            return Ok(Settings::default());
+        }
+
+        let contents = fs::read_to_string(path)?;
+        serde_json::from_str(&contents)
+            .map_err(|e| RtcError::Parse(format!("'{}', {}", path.display(), e)))
+    }
+}
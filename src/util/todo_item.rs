@@ -1,12 +1,13 @@
 use crate::RtcError;
-use chrono::serde::ts_milliseconds;
+use chrono::serde::{ts_milliseconds, ts_milliseconds_option};
 use chrono::{DateTime, Utc};
 use colored::*;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fmt;
 use std::str::FromStr;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Status {
     Done,
     Open,
@@ -37,6 +38,25 @@ pub struct TodoItem {
     pub status: Status,
     #[serde(with = "ts_milliseconds")]
     creation_date: DateTime<Utc>,
+    #[serde(default, with = "ts_milliseconds_option")]
+    completed_at: Option<DateTime<Utc>>,
+    #[serde(default, with = "ts_milliseconds_option")]
+    modified_at: Option<DateTime<Utc>>,
+    /// From `A` (highest) to `Z`
+    #[serde(default)]
+    pub priority: Option<char>,
+    #[serde(default)]
+    pub projects: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Other `key:value` attributes, e.g. from todo.txt
+    #[serde(default)]
+    pub extensions: BTreeMap<String, String>,
+    #[serde(default, with = "ts_milliseconds_option")]
+    pub due: Option<DateTime<Utc>>,
+    /// Identifies the item across other tools, e.g. Taskwarrior
+    #[serde(default)]
+    pub uuid: Option<String>,
 }
 
 impl TodoItem {
@@ -46,9 +66,35 @@ impl TodoItem {
             name: String::from(name),
             status: Status::Open,
             creation_date: Utc::now(),
+            completed_at: None,
+            modified_at: None,
+            priority: None,
+            projects: vec![],
+            tags: vec![],
+            extensions: BTreeMap::new(),
+            due: None,
+            uuid: None,
         }
     }
 
+    /// Moves an imported item to the ID it is stored under
+    pub(crate) fn with_id(mut self, id: usize) -> TodoItem {
+        self.id = id;
+        self
+    }
+
+    /// Sets the dates of an imported item, which keeps the status it was imported with
+    pub(crate) fn set_dates(
+        &mut self,
+        creation_date: DateTime<Utc>,
+        completed_at: Option<DateTime<Utc>>,
+        modified_at: Option<DateTime<Utc>>,
+    ) {
+        self.creation_date = creation_date;
+        self.completed_at = completed_at;
+        self.modified_at = modified_at;
+    }
+
     pub fn id(&self) -> &usize {
         &self.id
     }
@@ -61,10 +107,29 @@ impl TodoItem {
         &self.creation_date
     }
 
-    pub(crate) fn print_multiple(items: &[TodoItem]) {
-        for item in items {
-            println!("{}", item);
+    pub fn completed_at(&self) -> Option<&DateTime<Utc>> {
+        self.completed_at.as_ref()
+    }
+
+    pub fn modified_at(&self) -> Option<&DateTime<Utc>> {
+        self.modified_at.as_ref()
+    }
+
+    /// When the item was last changed, or created if it never was
+    pub fn last_modified(&self) -> &DateTime<Utc> {
+        self.modified_at.as_ref().unwrap_or(&self.creation_date)
+    }
+
+    /// Sets the status, keeping track of when the item was completed and modified
+    pub(crate) fn set_status(&mut self, status: Status) {
+        let now = Utc::now();
+        match (&self.status, &status) {
+            (Status::Open, Status::Done) => self.completed_at = Some(now),
+            (Status::Done, Status::Open) => self.completed_at = None,
+            _ => (),
         }
+        self.status = status;
+        self.modified_at = Some(now);
     }
 }
 
@@ -80,6 +145,67 @@ impl fmt::Display for TodoItem {
                 Status::Done => self.status.to_string().green(),
             },
             self.creation_date.to_string().blue(),
+        )?;
+        if let Some(c) = self.completed_at {
+            write!(f, ", completed_at: '{}'", c.to_string().blue())?;
+        }
+        if let Some(m) = self.modified_at {
+            write!(f, ", modified_at: '{}'", m.to_string().blue())?;
+        }
+        if let Some(p) = self.priority {
+            write!(f, ", priority: '{}'", p.to_string().blue())?;
+        }
+        if !self.projects.is_empty() {
+            write!(f, ", projects: '{}'", self.projects.join(", ").blue())?;
+        }
+        if !self.tags.is_empty() {
+            write!(f, ", tags: '{}'", self.tags.join(", ").blue())?;
+        }
+        if let Some(d) = self.due {
+            write!(f, ", due: '{}'", d.to_string().blue())?;
+        }
+        for (key, value) in &self.extensions {
+            write!(f, ", {}: '{}'", key, value.blue())?;
+        }
+        if let Some(u) = &self.uuid {
+            write!(f, ", uuid: '{}'", u.blue())?;
+        }
+        Ok(())
+    }
+}
+
+/// An item that was deleted and can still be restored
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TrashedItem {
+    item: TodoItem,
+    #[serde(with = "ts_milliseconds")]
+    deleted_at: DateTime<Utc>,
+}
+
+impl TrashedItem {
+    pub(crate) fn new(item: TodoItem) -> TrashedItem {
+        TrashedItem {
+            item,
+            deleted_at: Utc::now(),
+        }
+    }
+
+    pub fn item(&self) -> &TodoItem {
+        &self.item
+    }
+
+    pub fn deleted_at(&self) -> &DateTime<Utc> {
+        &self.deleted_at
+    }
+}
+
+impl fmt::Display for TrashedItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}, deleted_at: '{}'",
+            self.item,
+            self.deleted_at.to_string().blue()
         )
     }
 }
@@ -0,0 +1,58 @@
+use crate::{Format, GroupBy, RtcResult};
+use std::str::FromStr;
+
+/// How `import` reads a file and adds its items
+#[derive(Debug, Clone, Default)]
+pub struct ImportOptions {
+    /// Column layout of CSV files, e.g. `Task=name,State=status`
+    pub columns: Option<String>,
+    /// The file has no header row
+    pub no_header: bool,
+    /// Keep the IDs the file holds instead of assigning new ones
+    pub keep_ids: bool,
+    /// Only show what would be imported
+    pub dry_run: bool,
+    /// Update the items whose IDs are in the database instead of adding them again
+    pub sync: bool,
+}
+
+impl ImportOptions {
+    /// The format named `name`, read the way these options say
+    pub fn format(&self, name: &str) -> RtcResult<Format> {
+        layout(Format::from_str(name)?, &self.columns, self.no_header)
+    }
+}
+
+/// Which items `export` writes, and how
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    /// Column layout of CSV files, e.g. `Task=name,State=status`
+    pub columns: Option<String>,
+    /// Leave out the header row
+    pub no_header: bool,
+    /// Headings to group items under, in formats that have them
+    pub group_by: Option<GroupBy>,
+    /// Filter arguments like `tag=launch` that exported items must match
+    pub filters: Vec<String>,
+}
+
+impl ExportOptions {
+    /// The format named `name`, written the way these options say
+    pub fn format(&self, name: &str) -> RtcResult<Format> {
+        let format = layout(Format::from_str(name)?, &self.columns, self.no_header)?;
+        Ok(match self.group_by {
+            Some(group_by) => format.grouped_by(group_by),
+            None => format,
+        })
+    }
+}
+
+fn layout(mut format: Format, columns: &Option<String>, no_header: bool) -> RtcResult<Format> {
+    if let Some(mapping) = columns {
+        format = format.with_columns(mapping)?;
+    }
+    if no_header {
+        format = format.without_header();
+    }
+    Ok(format)
+}
@@ -1,10 +1,17 @@
 use rust_todo_cli::{Config, Operation};
 use std::fs;
+use std::path::Path;
 
-pub fn create_config(operation: Operation, args: Vec<String>) -> Config {
-    Config::new_for_testing(operation, args, "testcase.json")
+pub fn create_config(operation: Operation, args: Vec<String>, db_name: &str) -> Config {
+    Config::new_for_testing(operation, args, db_name)
 }
 
-pub fn remove_db() -> std::io::Result<()> {
-    fs::remove_file("testcase.json")
+pub fn remove_db(db_name: &str) -> std::io::Result<()> {
+    // Files kept next to the database may or may not exist
+    let _ = fs::remove_file(Path::new(db_name).with_extension("archive.json"));
+    let _ = fs::remove_file(Path::new(db_name).with_extension("history.jsonl"));
+    let _ = fs::remove_file(Path::new(db_name).with_extension("quarantine.json"));
+    let _ = fs::remove_file(Path::new(db_name).with_extension("lock"));
+    let _ = fs::remove_dir_all(Path::new(db_name).with_extension("backups"));
+    fs::remove_file(db_name)
 }
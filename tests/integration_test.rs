@@ -1,53 +1,2379 @@
-use rust_todo_cli;
-use rust_todo_cli::{Operation, RtcError, RunReturn};
+use chrono::{Duration, Utc};
+use fs2::FileExt;
+use pickledb::{PickleDb, PickleDbDumpPolicy, SerializationMethod};
+use rust_todo_cli::{
+    build_app, AuditAction, Change, Config, DbDriver, ExportOptions, GroupBy, ImportOptions,
+    ListOptions, Operation, PickleDbDriver, Problem, RtcError, RunReturn, Settings, Status,
+    TodoItem, SCHEMA_VERSION,
+};
+use serde_json::{json, Value};
+use std::fs;
+use std::iter;
+use std::process::Command;
+use std::str::FromStr;
+use std::thread;
 
 mod common;
 
 #[test]
 fn test_happy_path() -> Result<(), RtcError> {
-    setup_db_file();
+    let db = "testcase.json";
+    setup_db_file(db);
 
     // Add
-    do_op_and_assert(Operation::Add, vec!["my todo item 1"], Assertion::Number(1))?;
-    do_op_and_assert(Operation::Add, vec!["my todo item 2"], Assertion::Number(2))?;
-    do_op_and_assert(Operation::Add, vec!["my todo item 3"], Assertion::Number(3))?;
-    do_op_and_assert(Operation::Add, vec!["my todo item 4"], Assertion::Number(4))?;
+    do_op_and_assert(
+        db,
+        Operation::Add,
+        vec!["my todo item 1"],
+        Assertion::Number(1),
+    )?;
+    do_op_and_assert(
+        db,
+        Operation::Add,
+        vec!["my todo item 2"],
+        Assertion::Number(2),
+    )?;
+    do_op_and_assert(
+        db,
+        Operation::Add,
+        vec!["my todo item 3"],
+        Assertion::Number(3),
+    )?;
+    do_op_and_assert(
+        db,
+        Operation::Add,
+        vec!["my todo item 4"],
+        Assertion::Number(4),
+    )?;
+
+    // Get all
+    do_op_and_assert(db, Operation::GetAll, vec![], Assertion::Number(4))?;
+
+    // Delete
+    do_op_and_assert(db, Operation::Delete, vec!["2"], Assertion::Boolean(true))?;
+
+    // Filter by name
+    do_op_and_assert(
+        db,
+        Operation::Filter,
+        vec!["name=my todo item"],
+        Assertion::Number(3),
+    )?;
+    do_op_and_assert(
+        db,
+        Operation::Filter,
+        vec!["name=item 3"],
+        Assertion::Number(1),
+    )?;
+    do_op_and_assert(
+        db,
+        Operation::Filter,
+        vec!["name=this item does not exist"],
+        Assertion::Number(0),
+    )?;
+
+    // Update
+    do_op_and_assert(
+        db,
+        Operation::Update,
+        vec!["3", "done"],
+        Assertion::Boolean(true),
+    )?;
+
+    // Filter by other criteria
+    do_op_and_assert(
+        db,
+        Operation::Filter,
+        vec!["status=done"],
+        Assertion::Number(1),
+    )?;
+    do_op_and_assert(
+        db,
+        Operation::Filter,
+        vec!["status=open"],
+        Assertion::Number(2),
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn test_sort_and_paginate() -> Result<(), RtcError> {
+    let db = "testcase_sort.json";
+    setup_db_file(db);
+
+    for name in &["banana", "cherry", "apple", "date"] {
+        do_op_and_assert(db, Operation::Add, vec![name], Assertion::Unchecked)?;
+    }
+    do_op_and_assert(
+        db,
+        Operation::Update,
+        vec!["2", "done"],
+        Assertion::Boolean(true),
+    )?;
+
+    let names = |options: ListOptions| -> Result<(Vec<String>, usize), RtcError> {
+        let config =
+            common::create_config(Operation::GetAll, vec![], db).with_list_options(options);
+        match rust_todo_cli::run(&config)? {
+            RunReturn::Filter(items, total) => {
+                Ok((items.iter().map(|it| it.name().clone()).collect(), total))
+            }
+            x => panic!("Unexpected return {:?}", x),
+        }
+    };
+    let strings = |v: &[&str]| v.iter().map(|it| it.to_string()).collect::<Vec<String>>();
+
+    // Default order is by ID
+    let (page, total) = names(ListOptions::default())?;
+    assert_eq!(page, strings(&["banana", "cherry", "apple", "date"]));
+    assert_eq!(total, 4);
+
+    let (page, _) = names(ListOptions::new(&strings(&["name"]), None, None, false)?)?;
+    assert_eq!(page, strings(&["apple", "banana", "cherry", "date"]));
+
+    let (page, _) = names(ListOptions::new(
+        &strings(&["name:desc"]),
+        None,
+        None,
+        false,
+    )?)?;
+    assert_eq!(page, strings(&["date", "cherry", "banana", "apple"]));
+
+    // Multiple keys, status first (Done before Open), then name
+    let (page, _) = names(ListOptions::new(
+        &strings(&["status,name"]),
+        None,
+        None,
+        false,
+    )?)?;
+    assert_eq!(page, strings(&["cherry", "apple", "banana", "date"]));
+
+    let (page, _) = names(ListOptions::new(&[], None, None, true)?)?;
+    assert_eq!(page, strings(&["date", "apple", "cherry", "banana"]));
+
+    // Pagination reports the total separately from the page
+    let (page, total) = names(ListOptions::new(
+        &strings(&["name"]),
+        Some("2"),
+        Some("1"),
+        false,
+    )?)?;
+    assert_eq!(page, strings(&["banana", "cherry"]));
+    assert_eq!(total, 4);
+
+    assert!(ListOptions::new(&strings(&["colour"]), None, None, false).is_err());
+    assert!(ListOptions::new(&strings(&["name:up"]), None, None, false).is_err());
+    assert!(ListOptions::new(&[], Some("-1"), None, false).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_sort_by_due_and_priority() -> Result<(), RtcError> {
+    let db = "testcase_sort_due.json";
+    let todo_file = "testcase_sort_due.txt";
+    setup_db_file(db);
+    fs::write(
+        todo_file,
+        "(B) bravo due:2021-03-02\ncharlie\n(A) alpha due:2021-03-05\ndelta due:2021-03-01\n",
+    )?;
+    let config = common::create_config(
+        Operation::Import,
+        vec![String::from("todotxt"), String::from(todo_file)],
+        db,
+    );
+    rust_todo_cli::run(&config)?;
+
+    let names = |sort: &str| -> Result<Vec<String>, RtcError> {
+        let options = ListOptions::new(&[String::from(sort)], None, None, false)?;
+        let config =
+            common::create_config(Operation::GetAll, vec![], db).with_list_options(options);
+        match rust_todo_cli::run(&config)? {
+            RunReturn::Filter(items, _) => Ok(items.iter().map(|it| it.name().clone()).collect()),
+            x => panic!("Unexpected return {:?}", x),
+        }
+    };
+
+    // Items without a value come last in either direction
+    assert_eq!(names("priority")?, ["alpha", "bravo", "charlie", "delta"]);
+    assert_eq!(
+        names("priority:desc")?,
+        ["bravo", "alpha", "charlie", "delta"]
+    );
+    assert_eq!(names("due")?, ["delta", "bravo", "alpha", "charlie"]);
+    assert_eq!(names("due:desc")?, ["alpha", "bravo", "delta", "charlie"]);
+
+    fs::remove_file(todo_file)?;
+    Ok(())
+}
+
+#[test]
+fn test_views() -> Result<(), RtcError> {
+    let db = "testcase_views.json";
+    setup_db_file(db);
+
+    do_op_and_assert(
+        db,
+        Operation::Add,
+        vec!["standup notes"],
+        Assertion::Unchecked,
+    )?;
+    do_op_and_assert(
+        db,
+        Operation::Add,
+        vec!["standup call"],
+        Assertion::Unchecked,
+    )?;
+    do_op_and_assert(db, Operation::Add, vec!["groceries"], Assertion::Unchecked)?;
+    do_op_and_assert(
+        db,
+        Operation::Update,
+        vec!["2", "done"],
+        Assertion::Boolean(true),
+    )?;
+
+    let run = |operation: Operation, args: Vec<&str>| {
+        let config = common::create_config(
+            operation,
+            args.into_iter().map(|it| it.to_string()).collect(),
+            db,
+        );
+        rust_todo_cli::run(&config)
+    };
+
+    match run(
+        Operation::SaveView,
+        vec!["standup", "name=standup", "status=open"],
+    )? {
+        RunReturn::ViewSaved(name) => assert_eq!(name, "standup"),
+        x => panic!("Unexpected return {:?}", x),
+    }
+    run(Operation::SaveView, vec!["all", "name="])?;
+    assert!(run(Operation::SaveView, vec!["broken", "status"]).is_err());
+
+    do_op_and_assert(
+        db,
+        Operation::RunView,
+        vec!["standup"],
+        Assertion::Number(1),
+    )?;
+    do_op_and_assert(db, Operation::RunView, vec!["all"], Assertion::Number(3))?;
+    assert!(run(Operation::RunView, vec!["missing"]).is_err());
+
+    match run(Operation::ListViews, vec![])? {
+        RunReturn::Views(views) => {
+            assert_eq!(
+                views.keys().collect::<Vec<&String>>(),
+                vec!["all", "standup"]
+            );
+            assert_eq!(views["standup"], "name=standup and status=open");
+        }
+        x => panic!("Unexpected return {:?}", x),
+    }
+
+    // Views are not items
+    do_op_and_assert(db, Operation::GetAll, vec![], Assertion::Number(3))?;
+
+    match run(Operation::DeleteView, vec!["all"])? {
+        RunReturn::ViewDeletion(deleted) => assert!(deleted),
+        x => panic!("Unexpected return {:?}", x),
+    }
+    assert!(run(Operation::RunView, vec!["all"]).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_view_query() -> Result<(), RtcError> {
+    let db = "testcase_view_query.json";
+    let todo_file = "testcase_view_query.txt";
+    setup_db_file(db);
+    fs::write(
+        todo_file,
+        "Prepare standup @team\nx Book room @team\nBuy milk\nListen to rock and roll\n",
+    )?;
+    do_op_and_assert(
+        db,
+        Operation::Import,
+        vec!["todotxt", todo_file],
+        Assertion::Unchecked,
+    )?;
+    fs::remove_file(todo_file)?;
+
+    let matches = build_app().get_matches_from(vec![
+        "rtc",
+        "view",
+        "save",
+        "standup",
+        "status:open and tag:team",
+    ]);
+    let parsed = Config::new(&matches)?;
+    assert!(matches!(parsed.operation(), Operation::SaveView));
+    do_op_and_assert(
+        db,
+        Operation::SaveView,
+        parsed.args().iter().map(String::as_str).collect(),
+        Assertion::Unchecked,
+    )?;
+    do_op_and_assert(
+        db,
+        Operation::RunView,
+        vec!["standup"],
+        Assertion::Number(1),
+    )?;
+
+    match rust_todo_cli::run(&common::create_config(Operation::ListViews, vec![], db))? {
+        RunReturn::Views(views) => assert_eq!(views["standup"], "status:open and tag:team"),
+        x => panic!("Unexpected return {:?}", x),
+    }
+
+    // Values may contain "and" as long as no condition follows it
+    for (query, n) in &[
+        ("name:rock and roll", 1),
+        ("name:'rock and roll' and status:open", 1),
+        ("name:rock and roll and status:done", 0),
+    ] {
+        do_op_and_assert(
+            db,
+            Operation::SaveView,
+            vec!["music", query],
+            Assertion::Unchecked,
+        )?;
+        do_op_and_assert(db, Operation::RunView, vec!["music"], Assertion::Number(*n))?;
+    }
+
+    // Queries are checked when saved
+    assert!(matches!(
+        rust_todo_cli::run(&common::create_config(
+            Operation::SaveView,
+            vec![
+                String::from("broken"),
+                String::from("status:open and color:red")
+            ],
+            db,
+        )),
+        Err(RtcError::Parse(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_undo_redo() -> Result<(), RtcError> {
+    let db = "testcase_undo.json";
+    setup_db_file(db);
+
+    let run = |operation: Operation, args: Vec<&str>| {
+        let config = common::create_config(
+            operation,
+            args.into_iter().map(|it| it.to_string()).collect(),
+            db,
+        );
+        rust_todo_cli::run(&config)
+    };
+
+    do_op_and_assert(db, Operation::Add, vec!["item 1"], Assertion::Number(1))?;
+    do_op_and_assert(db, Operation::Add, vec!["item 2"], Assertion::Number(2))?;
+    do_op_and_assert(
+        db,
+        Operation::Update,
+        vec!["1", "done"],
+        Assertion::Boolean(true),
+    )?;
+    do_op_and_assert(db, Operation::Delete, vec!["2"], Assertion::Boolean(true))?;
+
+    match run(Operation::UndoList, vec![])? {
+        RunReturn::Journal(entries) => {
+            assert_eq!(entries.len(), 4);
+            assert!(matches!(entries[0].changes[..], [Change::Deleted(_)]));
+        }
+        x => panic!("Unexpected return {:?}", x),
+    }
+
+    // Undo the delete and the update
+    match run(Operation::Undo, vec!["2"])? {
+        RunReturn::Undo(entries) => assert_eq!(entries.len(), 2),
+        x => panic!("Unexpected return {:?}", x),
+    }
+    do_op_and_assert(
+        db,
+        Operation::Filter,
+        vec!["status=open"],
+        Assertion::Number(2),
+    )?;
+
+    // Redo the update only
+    run(Operation::Redo, vec![])?;
+    do_op_and_assert(
+        db,
+        Operation::Filter,
+        vec!["status=done"],
+        Assertion::Number(1),
+    )?;
+    do_op_and_assert(db, Operation::GetAll, vec![], Assertion::Number(2))?;
+
+    // A new operation discards what is left to redo
+    do_op_and_assert(db, Operation::Add, vec!["item 3"], Assertion::Number(3))?;
+    match run(Operation::RedoList, vec![])? {
+        RunReturn::Journal(entries) => assert!(entries.is_empty()),
+        x => panic!("Unexpected return {:?}", x),
+    }
+
+    // Undoing more than was recorded stops at the beginning, IDs are not reused
+    match run(Operation::Undo, vec!["10"])? {
+        RunReturn::Undo(entries) => assert_eq!(entries.len(), 4),
+        x => panic!("Unexpected return {:?}", x),
+    }
+    assert!(run(Operation::GetAll, vec![]).is_err());
+    run(Operation::Redo, vec!["10"])?;
+    do_op_and_assert(db, Operation::GetAll, vec![], Assertion::Number(3))?;
+    do_op_and_assert(db, Operation::Add, vec!["item 4"], Assertion::Number(4))?;
+
+    assert!(run(Operation::Undo, vec!["many"]).is_err());
+
+    // Changes made since, e.g. by another tool, are not overwritten
+    do_op_and_assert(
+        db,
+        Operation::Update,
+        vec!["4", "done"],
+        Assertion::Boolean(true),
+    )?;
+    run(Operation::Undo, vec![])?;
+    let mut raw = load_raw_db(db);
+    let mut item = raw.get::<Value>("4").unwrap();
+    item["name"] = json!("edited elsewhere");
+    raw.set("4", &item).unwrap();
+    raw.dump().unwrap();
+    assert!(matches!(
+        run(Operation::Redo, vec![]),
+        Err(RtcError::Update(_))
+    ));
+    assert!(matches!(
+        run(Operation::Undo, vec![]),
+        Err(RtcError::Update(_))
+    ));
+    let item = load_raw_db(db).get::<Value>("4").unwrap();
+    assert_eq!(item["name"], json!("edited elsewhere"));
+    assert_eq!(item["status"], json!("Open"));
+
+    Ok(())
+}
+
+#[test]
+fn test_trash_and_restore() -> Result<(), RtcError> {
+    let db = "testcase_trash.json";
+    setup_db_file(db);
+
+    let run = |operation: Operation, args: Vec<&str>| {
+        let config = common::create_config(
+            operation,
+            args.into_iter().map(|it| it.to_string()).collect(),
+            db,
+        );
+        rust_todo_cli::run(&config)
+    };
+    let trash_len = || match run(Operation::ListTrash, vec![])? {
+        RunReturn::Trash(items) => Ok::<usize, RtcError>(items.len()),
+        x => panic!("Unexpected return {:?}", x),
+    };
+
+    do_op_and_assert(db, Operation::Add, vec!["item 1"], Assertion::Number(1))?;
+    do_op_and_assert(db, Operation::Add, vec!["item 2"], Assertion::Number(2))?;
+    do_op_and_assert(db, Operation::Delete, vec!["1"], Assertion::Boolean(true))?;
+
+    // Trashed items are hidden from getall and filter
+    assert_eq!(trash_len()?, 1);
+    do_op_and_assert(db, Operation::GetAll, vec![], Assertion::Number(1))?;
+    do_op_and_assert(
+        db,
+        Operation::Filter,
+        vec!["name=item 1"],
+        Assertion::Number(0),
+    )?;
+    assert!(run(Operation::Delete, vec!["1"]).is_err());
+
+    match run(Operation::Restore, vec!["1"])? {
+        RunReturn::Restoration(restored) => assert!(restored),
+        x => panic!("Unexpected return {:?}", x),
+    }
+    assert_eq!(trash_len()?, 0);
+    do_op_and_assert(db, Operation::GetAll, vec![], Assertion::Number(2))?;
+    assert!(run(Operation::Restore, vec!["1"]).is_err());
+
+    // Undoing a restore puts the item back into the trash
+    run(Operation::Undo, vec![])?;
+    assert_eq!(trash_len()?, 1);
+
+    // Recently deleted items survive emptying with a cutoff, IDs are never reused
+    match run(Operation::EmptyTrash, vec!["30d"])? {
+        RunReturn::TrashEmptied(n) => assert_eq!(n, 0),
+        x => panic!("Unexpected return {:?}", x),
+    }
+    match run(Operation::EmptyTrash, vec![])? {
+        RunReturn::TrashEmptied(n) => assert_eq!(n, 1),
+        x => panic!("Unexpected return {:?}", x),
+    }
+    assert_eq!(trash_len()?, 0);
+    for invalid in &["30 days", "30é", "é", "", "-1d", "99999999999999w"] {
+        assert!(matches!(
+            run(Operation::EmptyTrash, vec![invalid]),
+            Err(RtcError::Parse(_))
+        ));
+    }
+    do_op_and_assert(db, Operation::Add, vec!["item 3"], Assertion::Number(3))?;
+
+    Ok(())
+}
+
+#[test]
+fn test_archive() -> Result<(), RtcError> {
+    let db = "testcase_archive.json";
+    setup_db_file(db);
+
+    let run = |operation: Operation, args: Vec<&str>| {
+        let config = common::create_config(
+            operation,
+            args.into_iter().map(|it| it.to_string()).collect(),
+            db,
+        );
+        rust_todo_cli::run(&config)
+    };
+    let count = |operation: Operation, args: Vec<&str>| {
+        let config = common::create_config(
+            operation,
+            args.into_iter().map(|it| it.to_string()).collect(),
+            db,
+        )
+        .with_list_options(ListOptions::default().with_archived(true));
+        match rust_todo_cli::run(&config)? {
+            RunReturn::Filter(items, _) => Ok::<usize, RtcError>(items.len()),
+            x => panic!("Unexpected return {:?}", x),
+        }
+    };
+
+    for name in &["item 1", "item 2", "item 3"] {
+        do_op_and_assert(db, Operation::Add, vec![name], Assertion::Unchecked)?;
+    }
+    do_op_and_assert(
+        db,
+        Operation::Update,
+        vec!["1", "done"],
+        Assertion::Boolean(true),
+    )?;
+    do_op_and_assert(
+        db,
+        Operation::Update,
+        vec!["2", "done"],
+        Assertion::Boolean(true),
+    )?;
+
+    // Nothing was created before 2000
+    match run(Operation::Archive, vec!["2000-01-01"])? {
+        RunReturn::Archived(ids) => assert!(ids.is_empty()),
+        x => panic!("Unexpected return {:?}", x),
+    }
+    match run(Operation::Archive, vec![])? {
+        RunReturn::Archived(ids) => assert_eq!(ids, vec![1, 2]),
+        x => panic!("Unexpected return {:?}", x),
+    }
+
+    do_op_and_assert(db, Operation::GetAll, vec![], Assertion::Number(1))?;
+    do_op_and_assert(
+        db,
+        Operation::Filter,
+        vec!["status=done"],
+        Assertion::Number(0),
+    )?;
+    assert_eq!(count(Operation::GetAll, vec![])?, 3);
+    assert_eq!(count(Operation::Filter, vec!["status=done"])?, 2);
+    assert!(run(Operation::Update, vec!["1", "open"]).is_err());
+
+    // Auto-archiving from the settings happens on every run
+    do_op_and_assert(
+        db,
+        Operation::Update,
+        vec!["3", "done"],
+        Assertion::Boolean(true),
+    )?;
+    let settings = Settings {
+        auto_archive_days: Some(0),
+        ..Settings::default()
+    };
+    let config = common::create_config(Operation::GetAll, vec![], db).with_settings(settings);
+    assert!(rust_todo_cli::run(&config).is_err());
+    assert_eq!(count(Operation::GetAll, vec![])?, 3);
+
+    // The archive is kept in its own file
+    assert_eq!(load_raw_db("testcase_archive.archive.json").total_keys(), 3);
+    assert!(load_raw_db(db).get::<Value>("archive").is_none());
+
+    // Undo brings back what was archived, then reverts the update that let it be archived
+    do_op_and_assert(db, Operation::Undo, vec![], Assertion::Unchecked)?;
+    do_op_and_assert(
+        db,
+        Operation::Filter,
+        vec!["status=done"],
+        Assertion::Number(1),
+    )?;
+    assert_eq!(count(Operation::GetAll, vec![])?, 3);
+    assert_eq!(load_raw_db("testcase_archive.archive.json").total_keys(), 2);
+    do_op_and_assert(db, Operation::Undo, vec![], Assertion::Unchecked)?;
+    do_op_and_assert(
+        db,
+        Operation::Filter,
+        vec!["status=open"],
+        Assertion::Number(1),
+    )?;
+    assert_eq!(count(Operation::GetAll, vec![])?, 3);
+
+    // IDs of archived items are not reused
+    do_op_and_assert(db, Operation::Add, vec!["item 4"], Assertion::Number(4))?;
+
+    // Auto-archiving on a read-only command keeps what can be redone
+    do_op_and_assert(
+        db,
+        Operation::Update,
+        vec!["4", "done"],
+        Assertion::Boolean(true),
+    )?;
+    do_op_and_assert(db, Operation::Delete, vec!["3"], Assertion::Boolean(true))?;
+    run(Operation::Undo, vec![])?;
+    do_op_and_assert(db, Operation::GetAll, vec![], Assertion::Number(2))?;
+    rust_todo_cli::run(&config)?;
+    assert_eq!(count(Operation::GetAll, vec![])?, 4);
+    match run(Operation::Redo, vec![])? {
+        RunReturn::Redo(entries) => assert_eq!(entries.len(), 1),
+        x => panic!("Unexpected return {:?}", x),
+    }
+    assert!(run(Operation::GetAll, vec![]).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_audit_log() -> Result<(), RtcError> {
+    let db = "testcase_audit.json";
+    setup_db_file(db);
+
+    let run = |operation: Operation, args: Vec<&str>| {
+        let config = common::create_config(
+            operation,
+            args.into_iter().map(|it| it.to_string()).collect(),
+            db,
+        )
+        .with_settings(Settings {
+            user: Some(String::from("tester")),
+            ..Settings::default()
+        });
+        rust_todo_cli::run(&config)
+    };
+
+    run(Operation::Add, vec!["item 1"])?;
+    run(Operation::Add, vec!["item 2"])?;
+    run(Operation::Update, vec!["1", "done"])?;
+    run(Operation::Delete, vec!["1"])?;
+    run(Operation::Undo, vec![])?;
+
+    match run(Operation::Log, vec!["3"])? {
+        RunReturn::Log(entries) => {
+            let actions = entries.iter().map(|e| e.action).collect::<Vec<_>>();
+            assert_eq!(
+                actions,
+                vec![AuditAction::Update, AuditAction::Delete, AuditAction::Undo]
+            );
+            assert!(entries.iter().all(|e| e.user == "tester"));
+        }
+        x => panic!("Unexpected return {:?}", x),
+    }
+
+    match run(Operation::ShowHistory, vec!["1"])? {
+        RunReturn::Show(item, history) => {
+            assert_eq!(item.unwrap().status, Status::Done);
+            assert_eq!(history.len(), 4);
+            assert_eq!(history[0].action, AuditAction::Create);
+
+            // Only the status and its timestamps changed in the update
+            let update = &history[1];
+            let fields = update
+                .changes
+                .iter()
+                .map(|c| &c.field[..])
+                .collect::<Vec<_>>();
+            assert_eq!(fields, vec!["completed_at", "modified_at", "status"]);
+            assert_eq!(update.changes[0].old, None);
+            assert_eq!(update.changes[2].old, Some(json!("Open")));
+            assert_eq!(update.changes[2].new, Some(json!("Done")));
+        }
+        x => panic!("Unexpected return {:?}", x),
+    }
+
+    match run(Operation::Show, vec!["2"])? {
+        RunReturn::Show(item, history) => {
+            assert_eq!(item.unwrap().name(), "item 2");
+            assert!(history.is_empty());
+        }
+        x => panic!("Unexpected return {:?}", x),
+    }
+    assert!(run(Operation::Show, vec!["3"]).is_err());
+
+    // Purged items still have their history
+    run(Operation::Delete, vec!["2"])?;
+    run(Operation::EmptyTrash, vec![])?;
+    match run(Operation::ShowHistory, vec!["2"])? {
+        RunReturn::Show(item, history) => {
+            assert!(item.is_none());
+            assert_eq!(history.last().unwrap().action, AuditAction::Purge);
+
+            // Fields that are not set are left out
+            let created = history[0]
+                .changes
+                .iter()
+                .map(|c| &c.field[..])
+                .collect::<Vec<_>>();
+            assert_eq!(created, vec!["creation_date", "id", "name", "status"]);
+        }
+        x => panic!("Unexpected return {:?}", x),
+    }
+
+    // Changes that are rolled back are not logged
+    let n_entries = || match run(Operation::Log, vec!["100"])? {
+        RunReturn::Log(entries) => Ok::<usize, RtcError>(entries.len()),
+        x => panic!("Unexpected return {:?}", x),
+    };
+    let logged = n_entries()?;
+    let script = "testcase_audit.script";
+    fs::write(script, "-a ghost one\n-a ghost two\n-u 99 done\n")?;
+    assert!(run(Operation::Batch, vec![script]).is_err());
+    fs::remove_file(script)?;
+    assert_eq!(n_entries()?, logged);
+
+    Ok(())
+}
+
+#[test]
+fn test_completion_timestamps() -> Result<(), RtcError> {
+    let db = "testcase_timestamps.json";
+    setup_db_file(db);
+
+    let run = |operation: Operation, args: Vec<&str>| {
+        let config = common::create_config(
+            operation,
+            args.into_iter().map(|it| it.to_string()).collect(),
+            db,
+        );
+        match rust_todo_cli::run(&config)? {
+            RunReturn::Filter(items, _) => Ok::<Vec<TodoItem>, RtcError>(items),
+            _ => Ok(vec![]),
+        }
+    };
+    let today = Utc::now().format("%Y-%m-%d").to_string();
+    let tomorrow = (Utc::now() + Duration::days(1))
+        .format("%Y-%m-%d")
+        .to_string();
+    let completed_after = format!("completed_after={}", today);
+    let completed_before = format!("completed_before={}", tomorrow);
+
+    run(Operation::Add, vec!["item 1"])?;
+    run(Operation::Add, vec!["item 2"])?;
+
+    let items = run(Operation::GetAll, vec![])?;
+    assert!(items.iter().all(|i| i.completed_at().is_none()));
+    assert!(items.iter().all(|i| i.modified_at().is_none()));
+
+    run(Operation::Update, vec!["1", "done"])?;
+    let items = run(Operation::Filter, vec![&completed_after])?;
+    assert_eq!(items.len(), 1);
+    assert!(items[0].completed_at().is_some());
+    assert_eq!(items[0].modified_at(), items[0].completed_at());
+    assert_eq!(run(Operation::Filter, vec![&completed_before])?.len(), 1);
+    assert_eq!(
+        run(
+            Operation::Filter,
+            vec![&format!("completed_after={}", tomorrow)]
+        )?
+        .len(),
+        0
+    );
+
+    // Reopening clears the completion date but still counts as a modification
+    run(Operation::Update, vec!["1", "open"])?;
+    assert_eq!(run(Operation::Filter, vec![&completed_after])?.len(), 0);
+    let items = run(Operation::Filter, vec!["name=item 1"])?;
+    assert!(items[0].completed_at().is_none());
+    assert!(items[0].modified_at().is_some());
+
+    // Items that were never modified count as modified when created
+    assert_eq!(
+        run(
+            Operation::Filter,
+            vec![&format!("modified_since={}", today)]
+        )?
+        .len(),
+        2
+    );
+    assert_eq!(
+        run(
+            Operation::Filter,
+            vec![&format!("modified_since={}", tomorrow)]
+        )?
+        .len(),
+        0
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_migration_from_v0() -> Result<(), RtcError> {
+    let db = "testcase_migration.json";
+    let backup = "testcase_migration.v0.backup.json";
+    setup_db_file(db);
+    let _ = fs::remove_file(backup);
+    fs::copy("tests/fixtures/schema_v0.json", db)?;
+
+    // Old items are all still there
+    do_op_and_assert(db, Operation::GetAll, vec![], Assertion::Number(2))?;
+    do_op_and_assert(
+        db,
+        Operation::Filter,
+        vec!["status=done"],
+        Assertion::Number(1),
+    )?;
+
+    // The original file was backed up before migrating
+    assert_eq!(
+        fs::read_to_string(backup)?,
+        fs::read_to_string("tests/fixtures/schema_v0.json")?
+    );
+
+    let raw = load_raw_db(db);
+    assert_eq!(raw.get::<u32>("schema_version"), Some(SCHEMA_VERSION));
+    let item = raw.get::<Value>("2").unwrap();
+    assert_eq!(item["completed_at"], Value::Null);
+    assert_eq!(item["tags"], json!([]));
+    assert_eq!(item["name"], json!("write report"));
+
+    // IDs continue where they left off
+    do_op_and_assert(db, Operation::Add, vec!["new item"], Assertion::Number(4))?;
+
+    fs::remove_file(backup)?;
+    Ok(())
+}
+
+#[test]
+fn test_migration_of_all_namespaces() -> Result<(), RtcError> {
+    let db = "testcase_migration_ns.json";
+    let backup = "testcase_migration_ns.v0.backup.json";
+    setup_db_file(db);
+    let _ = fs::remove_file(backup);
+    fs::copy("tests/fixtures/schema_v0_namespaces.json", db)?;
+
+    do_op_and_assert(db, Operation::GetAll, vec![], Assertion::Number(2))?;
+
+    let raw = load_raw_db(db);
+    let has_dates =
+        |item: &Value| item.get("completed_at").is_some() && item.get("modified_at").is_some();
+    assert!(has_dates(&raw.get::<Value>("1").unwrap()));
+    assert!(has_dates(&raw.get::<Value>("trash").unwrap()["2"]["item"]));
+    // The archive was moved into its own file
+    assert!(raw.get::<Value>("archive").is_none());
+    let archive = load_raw_db("testcase_migration_ns.archive.json");
+    assert!(has_dates(&archive.get::<Value>("3").unwrap()));
+    let journal = raw.get::<Value>("journal").unwrap();
+    assert!(has_dates(
+        &journal["undo"][0]["changes"][0]["Updated"]["before"]
+    ));
+    assert!(has_dates(
+        &journal["undo"][0]["changes"][0]["Updated"]["after"]
+    ));
+    assert_eq!(
+        raw.get::<Value>("views").unwrap(),
+        json!({"open": "status:open"})
+    );
+
+    // Migrated items still work everywhere
+    do_op_and_assert(db, Operation::Restore, vec!["2"], Assertion::Unchecked)?;
+    do_op_and_assert(db, Operation::Undo, vec!["2"], Assertion::Unchecked)?;
+    do_op_and_assert(
+        db,
+        Operation::Filter,
+        vec!["status=open"],
+        Assertion::Number(2),
+    )?;
+
+    fs::remove_file(backup)?;
+    Ok(())
+}
+
+#[test]
+fn test_newer_schema_is_rejected() -> Result<(), RtcError> {
+    let db = "testcase_migration_newer.json";
+    setup_db_file(db);
+
+    do_op_and_assert(db, Operation::Add, vec!["item"], Assertion::Number(1))?;
+    let mut raw = load_raw_db(db);
+    raw.set("schema_version", &(SCHEMA_VERSION + 1)).unwrap();
+    raw.dump().unwrap();
+
+    let config = common::create_config(Operation::GetAll, vec![], db);
+    assert!(matches!(
+        rust_todo_cli::run(&config),
+        Err(RtcError::Migration(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_unloadable_db_is_kept() -> Result<(), RtcError> {
+    let db = "testcase_unloadable.json";
+    setup_db_file(db);
+
+    // Reading a new db does not create its file
+    let config = common::create_config(Operation::GetAll, vec![], db);
+    assert!(matches!(
+        rust_todo_cli::run(&config),
+        Err(RtcError::NoItems(_))
+    ));
+    assert!(fs::metadata(db).is_err());
+
+    do_op_and_assert(db, Operation::Add, vec!["item"], Assertion::Number(1))?;
+    assert_eq!(
+        load_raw_db(db).get::<u32>("schema_version"),
+        Some(SCHEMA_VERSION)
+    );
+
+    let contents = fs::read_to_string(db)?;
+    let truncated = &contents[..contents.len() / 2];
+    fs::write(db, truncated)?;
+    for operation in [Operation::GetAll, Operation::Add] {
+        let config = common::create_config(operation, vec![String::from("item")], db);
+        assert!(matches!(
+            rust_todo_cli::run(&config),
+            Err(RtcError::Load(_))
+        ));
+    }
+    assert_eq!(fs::read_to_string(db)?, truncated);
 
-    // Get all
-    do_op_and_assert(Operation::GetAll, vec![], Assertion::Number(4))?;
+    Ok(())
+}
 
-    // Delete
-    do_op_and_assert(Operation::Delete, vec!["2"], Assertion::Boolean(true))?;
+#[test]
+fn test_bulk_operations() -> Result<(), RtcError> {
+    let db = "testcase_bulk.json";
+    setup_db_file(db);
+    let run_bulk = |operation: Operation, args: Vec<&str>| {
+        let config =
+            common::create_config(operation, args.into_iter().map(String::from).collect(), db)
+                .with_assume_yes();
+        match rust_todo_cli::run(&config)? {
+            RunReturn::Bulk(results) => Ok::<_, RtcError>(
+                results
+                    .into_iter()
+                    .map(|(id, result)| (id, result.is_ok()))
+                    .collect::<Vec<(usize, bool)>>(),
+            ),
+            x => panic!("Unexpected return {:?}", x),
+        }
+    };
 
-    // Filter by name
+    for name in &["a", "b", "report 1", "report 2", "e", "f"] {
+        do_op_and_assert(db, Operation::Add, vec![name], Assertion::Unchecked)?;
+    }
+
+    // Single IDs keep working as before
+    do_op_and_assert(db, Operation::Delete, vec!["1"], Assertion::Boolean(true))?;
+
+    // IDs that can not be deleted do not stop the others
+    assert_eq!(
+        run_bulk(Operation::Delete, vec!["1", "5-6"])?,
+        vec![(1, false), (5, true), (6, true)]
+    );
+    do_op_and_assert(db, Operation::GetAll, vec![], Assertion::Number(3))?;
+
+    assert_eq!(
+        run_bulk(Operation::Update, vec!["2,3", "done"])?,
+        vec![(2, true), (3, true)]
+    );
     do_op_and_assert(
+        db,
         Operation::Filter,
-        vec!["name=my todo item"],
+        vec!["status=done"],
+        Assertion::Number(2),
+    )?;
+
+    assert_eq!(
+        run_bulk(Operation::DoneWhere, vec!["name:report and status:open"])?,
+        vec![(4, true)]
+    );
+    assert_eq!(run_bulk(Operation::DoneWhere, vec!["status:open"])?, vec![]);
+    assert!(matches!(
+        run_bulk(Operation::DoneWhere, vec!["owner:lorenz"]),
+        Err(RtcError::Parse(_))
+    ));
+    assert!(matches!(
+        run_bulk(Operation::Delete, vec!["4-2"]),
+        Err(RtcError::Parse(_))
+    ));
+
+    // A single undo reverts every item of a bulk operation
+    assert_eq!(
+        run_bulk(Operation::Update, vec!["2-4", "open"])?,
+        vec![(2, true), (3, true), (4, true)]
+    );
+    let config = common::create_config(Operation::Undo, vec![], db);
+    match rust_todo_cli::run(&config)? {
+        RunReturn::Undo(entries) => {
+            assert_eq!(entries.len(), 1);
+            assert_eq!(entries[0].changes.len(), 3);
+        }
+        x => panic!("Unexpected return {:?}", x),
+    }
+    do_op_and_assert(
+        db,
+        Operation::Filter,
+        vec!["status=done"],
         Assertion::Number(3),
     )?;
-    do_op_and_assert(Operation::Filter, vec!["name=item 3"], Assertion::Number(1))?;
+
+    // Ranges stand for the IDs in them that exist, however wide they are
+    assert_eq!(
+        run_bulk(Operation::Update, vec!["3-18446744073709551615", "open"])?,
+        vec![(3, true), (4, true)]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_batch() -> Result<(), RtcError> {
+    let db = "testcase_batch.json";
+    let script = "testcase_batch.script";
+    setup_db_file(db);
+    let run_batch = |operation: Operation, lines: &str| {
+        fs::write(script, lines)?;
+        let config = common::create_config(operation, vec![String::from(script)], db);
+        rust_todo_cli::run(&config)
+    };
+
+    let result = run_batch(
+        Operation::Batch,
+        "# Comments and empty lines are skipped\n\
+         -a first item\n\
+         \n\
+         -a \"second  item\" 'with quotes'\n\
+         -u 1 done\n\
+         done 2\n",
+    )?;
+    match result {
+        RunReturn::Batch(results) => {
+            assert_eq!(
+                results.iter().map(|(n, _)| *n).collect::<Vec<usize>>(),
+                vec![2, 4, 5, 6]
+            );
+            assert!(matches!(results[1].1, Ok(RunReturn::Addition(2))));
+        }
+        x => panic!("Unexpected return {:?}", x),
+    }
     do_op_and_assert(
+        db,
         Operation::Filter,
-        vec!["name=this item does not exist"],
-        Assertion::Number(0),
+        vec!["name=second  item with quotes", "status=done"],
+        Assertion::Number(1),
     )?;
 
-    // Update
+    // The first failing line stops the batch and keeps none of its changes
+    let result = run_batch(Operation::Batch, "-a third\n-u 9 done\n-a fourth\n");
+    assert!(matches!(result, Err(RtcError::Batch(_))));
+    do_op_and_assert(db, Operation::GetAll, vec![], Assertion::Number(2))?;
+
+    let result = run_batch(
+        Operation::BatchContinueOnError,
+        "-a third\n-u 9 done\n--unknown\nbatch\n-a fourth\n",
+    )?;
+    match result {
+        RunReturn::Batch(results) => assert_eq!(
+            results
+                .iter()
+                .map(|(_, r)| r.is_ok())
+                .collect::<Vec<bool>>(),
+            vec![true, false, false, false, true]
+        ),
+        x => panic!("Unexpected return {:?}", x),
+    }
+    do_op_and_assert(db, Operation::GetAll, vec![], Assertion::Number(4))?;
+    // IDs of the rolled back batch are not used up
+    do_op_and_assert(db, Operation::Add, vec!["fifth"], Assertion::Number(5))?;
+
+    fs::remove_file(script)?;
+    Ok(())
+}
+
+#[test]
+fn test_todotxt() -> Result<(), RtcError> {
+    let db = "testcase_todotxt.json";
+    let exported = "testcase_todotxt.txt";
+    setup_db_file(db);
+    let run = |operation: Operation, args: Vec<&str>| {
+        let config =
+            common::create_config(operation, args.into_iter().map(String::from).collect(), db);
+        rust_todo_cli::run(&config)
+    };
+
+    match run(
+        Operation::Import,
+        vec!["todotxt", "tests/fixtures/todo.txt"],
+    )? {
+        RunReturn::Imported(ids) => assert_eq!(ids, vec![1, 2, 3, 4]),
+        x => panic!("Unexpected return {:?}", x),
+    }
+    // Importing again adds nothing
+    match run(
+        Operation::Import,
+        vec!["todotxt", "tests/fixtures/todo.txt"],
+    )? {
+        RunReturn::Imported(ids) => assert!(ids.is_empty()),
+        x => panic!("Unexpected return {:?}", x),
+    }
+
+    let item = match run(Operation::Show, vec!["2"])? {
+        RunReturn::Show(Some(item), _) => item,
+        x => panic!("Unexpected return {:?}", x),
+    };
+    assert_eq!(item.name(), "write report");
+    assert_eq!(item.status, Status::Done);
+    assert_eq!(item.priority, Some('C'));
+    assert_eq!(item.projects, vec!["work", "q1"]);
+    assert!(item.extensions.is_empty());
+    assert_eq!(item.creation_date().to_string(), "2021-02-20 00:00:00 UTC");
+    assert_eq!(
+        item.completed_at().unwrap().to_string(),
+        "2021-03-04 00:00:00 UTC"
+    );
+
+    let item = match run(Operation::Show, vec!["1"])? {
+        RunReturn::Show(Some(item), _) => item,
+        x => panic!("Unexpected return {:?}", x),
+    };
+    assert_eq!(item.tags, vec!["phone"]);
+    assert_eq!(item.due.unwrap().to_string(), "2021-03-05 00:00:00 UTC");
+    assert!(item.extensions.is_empty());
+
+    // Exporting gives back the same lines
+    run(Operation::Export, vec!["todotxt", exported])?;
+    assert_eq!(
+        fs::read_to_string(exported)?,
+        fs::read_to_string("tests/fixtures/todo.txt")?
+    );
+
+    // Items without dates are created now, completed ones without creation date when completed
+    fs::write(exported, "Buy milk\nx 2021-03-03 Call grandma\n")?;
+    run(Operation::Import, vec!["todo.txt", exported])?;
+    match run(Operation::Export, vec!["todotxt"])? {
+        RunReturn::Exported(text) => {
+            let today = Utc::now().format("%Y-%m-%d").to_string();
+            assert!(text.contains(&format!("\n{} Buy milk\n", today)));
+            assert!(text.ends_with("\nx 2021-03-03 2021-03-03 Call grandma\n"));
+        }
+        x => panic!("Unexpected return {:?}", x),
+    }
+
+    fs::remove_file(exported)?;
+    Ok(())
+}
+
+#[test]
+fn test_taskwarrior() -> Result<(), RtcError> {
+    let db = "testcase_taskwarrior.json";
+    let fixture = "tests/fixtures/taskwarrior.json";
+    setup_db_file(db);
+    let run = |operation: Operation, args: Vec<&str>| {
+        let config =
+            common::create_config(operation, args.into_iter().map(String::from).collect(), db);
+        rust_todo_cli::run(&config)
+    };
+
+    // Deleted tasks are skipped
+    match run(Operation::Import, vec!["taskwarrior", fixture])? {
+        RunReturn::Imported(ids) => assert_eq!(ids, vec![1, 2]),
+        x => panic!("Unexpected return {:?}", x),
+    }
+
+    let item = match run(Operation::Show, vec!["1"])? {
+        RunReturn::Show(Some(item), _) => item,
+        x => panic!("Unexpected return {:?}", x),
+    };
+    assert_eq!(item.name(), "Call mom");
+    assert_eq!(item.status, Status::Open);
+    assert_eq!(item.priority, Some('A'));
+    assert_eq!(item.projects, vec!["family"]);
+    assert_eq!(item.tags, vec!["phone", "weekend"]);
+    assert_eq!(item.due.unwrap().to_string(), "2021-03-05 00:00:00 UTC");
+    assert_eq!(
+        item.uuid.as_deref(),
+        Some("6d4b1c2e-4f0a-4c57-9a3e-2b7f0c8d9e11")
+    );
+
+    let item = match run(Operation::Show, vec!["2"])? {
+        RunReturn::Show(Some(item), _) => item,
+        x => panic!("Unexpected return {:?}", x),
+    };
+    assert_eq!(item.status, Status::Done);
+    assert_eq!(
+        item.completed_at().unwrap().to_string(),
+        "2021-03-04 17:00:00 UTC"
+    );
+    assert_eq!(item.extensions.get("estimate").unwrap(), "2h");
+
+    // Exporting gives back the same tasks, without what Taskwarrior computes itself
+    let exported = match run(Operation::Export, vec!["taskwarrior"])? {
+        RunReturn::Exported(text) => serde_json::from_str::<Value>(&text).unwrap(),
+        x => panic!("Unexpected return {:?}", x),
+    };
+    let mut expected = serde_json::from_str::<Value>(&fs::read_to_string(fixture)?).unwrap();
+    let expected = expected.as_array_mut().unwrap();
+    expected.pop();
+    for task in expected.iter_mut() {
+        let task = task.as_object_mut().unwrap();
+        task.remove("id");
+        task.remove("urgency");
+    }
+    assert_eq!(&exported, &Value::Array(expected.clone()));
+
+    // Older versions export one task per line
+    let lines = "testcase_taskwarrior.lines";
+    fs::write(
+        lines,
+        "{\"description\":\"a\",\"status\":\"pending\"}\n\
+         {\"description\":\"b\",\"status\":\"waiting\"},\n",
+    )?;
+    match run(Operation::Import, vec!["taskwarrior", lines])? {
+        RunReturn::Imported(ids) => assert_eq!(ids, vec![3, 4]),
+        x => panic!("Unexpected return {:?}", x),
+    }
+
+    // Extensions named like attributes are left out, so exported tasks can be imported again
+    fs::write(lines, "Call dad due:someday status:later estimate:1h\n")?;
+    run(Operation::Import, vec!["todotxt", lines])?;
+    let exported = match run(Operation::Export, vec!["taskwarrior"])? {
+        RunReturn::Exported(text) => text,
+        x => panic!("Unexpected return {:?}", x),
+    };
+    let copy = "testcase_taskwarrior_copy.json";
+    setup_db_file(copy);
+    fs::write(lines, &exported)?;
+    let config = common::create_config(
+        Operation::Import,
+        vec![String::from("taskwarrior"), String::from(lines)],
+        copy,
+    );
+    match rust_todo_cli::run(&config)? {
+        RunReturn::Imported(ids) => assert_eq!(ids, vec![1, 2, 3, 4, 5]),
+        x => panic!("Unexpected return {:?}", x),
+    }
+    let config = common::create_config(Operation::Show, vec![String::from("5")], copy);
+    match rust_todo_cli::run(&config)? {
+        RunReturn::Show(Some(item), _) => {
+            assert_eq!(item.name(), "Call dad");
+            assert_eq!(item.status, Status::Open);
+            assert_eq!(item.due, None);
+            assert_eq!(item.extensions.len(), 1);
+            assert_eq!(item.extensions.get("estimate").unwrap(), "1h");
+        }
+        x => panic!("Unexpected return {:?}", x),
+    }
+
+    fs::remove_file(lines)?;
+    Ok(())
+}
+
+#[test]
+fn test_ical() -> Result<(), RtcError> {
+    let db = "testcase_ical.json";
+    let db_copy = "testcase_ical_copy.json";
+    let exported = "testcase_ical.ics";
+    setup_db_file(db);
+    setup_db_file(db_copy);
+    let run = |db: &str, operation: Operation, args: Vec<&str>| {
+        let config =
+            common::create_config(operation, args.into_iter().map(String::from).collect(), db);
+        rust_todo_cli::run(&config)
+    };
+
+    // Events and alarms are not items
+    match run(
+        db,
+        Operation::Import,
+        vec!["ical", "tests/fixtures/todo.ics"],
+    )? {
+        RunReturn::Imported(ids) => assert_eq!(ids, vec![1, 2]),
+        x => panic!("Unexpected return {:?}", x),
+    }
+
+    let item = match run(db, Operation::Show, vec!["1"])? {
+        RunReturn::Show(Some(item), _) => item,
+        x => panic!("Unexpected return {:?}", x),
+    };
+    assert_eq!(
+        item.name(),
+        "Buy milk, eggs and a very long list of other things that does not fit on one line"
+    );
+    assert_eq!(item.status, Status::Open);
+    assert_eq!(item.priority, Some('A'));
+    assert_eq!(item.tags, vec!["errands", "home"]);
+    assert_eq!(item.due.unwrap().to_string(), "2021-03-05 00:00:00 UTC");
+    assert_eq!(
+        item.modified_at().unwrap().to_string(),
+        "2021-03-02 08:00:00 UTC"
+    );
+
+    let item = match run(db, Operation::Show, vec!["2"])? {
+        RunReturn::Show(Some(item), _) => item,
+        x => panic!("Unexpected return {:?}", x),
+    };
+    assert_eq!(item.status, Status::Done);
+    assert_eq!(item.priority, None);
+    assert_eq!(item.projects, vec!["work"]);
+    assert_eq!(item.uuid.as_deref(), Some("rtc-2"));
+    assert_eq!(
+        item.completed_at().unwrap().to_string(),
+        "2021-03-04 17:00:00 UTC"
+    );
+
+    // Exported files are folded and import into the same items
+    run(db, Operation::Export, vec!["ical", exported])?;
+    let text = fs::read_to_string(exported)?;
+    assert!(text.split("\r\n").all(|line| line.len() <= 75));
+    assert!(text.contains("\r\nSUMMARY:Buy milk\\, eggs"));
+    run(db_copy, Operation::Import, vec!["ics", exported])?;
+    match run(db_copy, Operation::Export, vec!["ical"])? {
+        RunReturn::Exported(copy) => assert_eq!(copy, text),
+        x => panic!("Unexpected return {:?}", x),
+    }
+
+    fs::remove_file(exported)?;
+    Ok(())
+}
+
+#[test]
+fn test_markdown() -> Result<(), RtcError> {
+    let db = "testcase_markdown.json";
+    let db_copy = "testcase_markdown_copy.json";
+    let exported = "testcase_markdown.md";
+    setup_db_file(db);
+    setup_db_file(db_copy);
+    let run = |db: &str, operation: Operation, args: Vec<&str>| {
+        let config =
+            common::create_config(operation, args.into_iter().map(String::from).collect(), db);
+        rust_todo_cli::run(&config)
+    };
+    let export = |db: &str, args: Vec<&str>| -> Result<String, RtcError> {
+        match run(db, Operation::Export, args)? {
+            RunReturn::Exported(text) => Ok(text),
+            x => panic!("Unexpected return {:?}", x),
+        }
+    };
+    let grouped = |group_by: GroupBy, filters: &[&str]| -> Result<String, RtcError> {
+        let options = ExportOptions {
+            group_by: Some(group_by),
+            filters: filters.iter().map(|f| f.to_string()).collect(),
+            ..ExportOptions::default()
+        };
+        let config = common::create_config(Operation::Export, vec![String::from("markdown")], db)
+            .with_export_options(options);
+        match rust_todo_cli::run(&config)? {
+            RunReturn::Exported(text) => Ok(text),
+            x => panic!("Unexpected return {:?}", x),
+        }
+    };
+
+    // Headings, text and malformed checkboxes are skipped, nested and numbered items are not
+    match run(
+        db,
+        Operation::Import,
+        vec!["markdown", "tests/fixtures/todo.md"],
+    )? {
+        RunReturn::Imported(ids) => assert_eq!(ids, vec![1, 2, 3, 4]),
+        x => panic!("Unexpected return {:?}", x),
+    }
+
+    let item = match run(db, Operation::Show, vec!["1"])? {
+        RunReturn::Show(Some(item), _) => item,
+        x => panic!("Unexpected return {:?}", x),
+    };
+    assert_eq!(item.name(), "Call mom");
+    assert_eq!(item.priority, Some('A'));
+    assert_eq!(item.projects, vec!["family"]);
+    assert_eq!(item.tags, vec!["phone"]);
+    assert_eq!(item.due.unwrap().to_string(), "2021-03-05 00:00:00 UTC");
+
+    // Issue references stay in the name
+    let item = match run(db, Operation::Show, vec!["2"])? {
+        RunReturn::Show(Some(item), _) => item,
+        x => panic!("Unexpected return {:?}", x),
+    };
+    assert_eq!(item.name(), "write report for #42");
+    assert_eq!(item.status, Status::Done);
+    assert_eq!(item.tags, vec!["q1"]);
+    assert!(item.completed_at().is_some());
+
+    let text = export(db, vec!["markdown"])?;
+    assert_eq!(
+        text,
+        "- [ ] (A) Call mom +family #phone due:2021-03-05\n\
+         - [x] write report for #42 +work #q1\n\
+         - [ ] proof read see https://example.com/style\n\
+         - [x] get milk estimate:2h\n"
+    );
+    fs::write(exported, &text)?;
+    run(db_copy, Operation::Import, vec!["md", exported])?;
+    assert_eq!(export(db_copy, vec!["markdown"])?, text);
+
+    // Filtered and grouped
+    assert_eq!(
+        grouped(GroupBy::Tag, &["status=open"])?,
+        "## phone\n\n\
+         - [ ] (A) Call mom +family #phone due:2021-03-05\n\
+         \n\
+         ## Untagged\n\n\
+         - [ ] proof read see https://example.com/style\n"
+    );
+    assert_eq!(
+        grouped(GroupBy::Status, &[])?,
+        "## Open\n\n\
+         - [ ] (A) Call mom +family #phone due:2021-03-05\n\
+         - [ ] proof read see https://example.com/style\n\
+         \n\
+         ## Done\n\n\
+         - [x] write report for #42 +work #q1\n\
+         - [x] get milk estimate:2h\n"
+    );
+    assert!(grouped(GroupBy::Due, &["project=family"])?
+        .starts_with("## 2021-03-05\n\n- [ ] (A) Call mom"));
+    assert!(matches!(GroupBy::from_str("week"), Err(RtcError::Parse(_))));
+
+    fs::remove_file(exported)?;
+    Ok(())
+}
+
+#[test]
+fn test_csv() -> Result<(), RtcError> {
+    let db = "testcase_csv.json";
+    let db_copy = "testcase_csv_copy.json";
+    let exported = "testcase_csv.csv";
+    setup_db_file(db);
+    setup_db_file(db_copy);
+    let run = |db: &str, operation: Operation, args: Vec<&str>| {
+        let config =
+            common::create_config(operation, args.into_iter().map(String::from).collect(), db);
+        rust_todo_cli::run(&config)
+    };
+    let import_with = |db: &str, file: &str, options: ImportOptions| {
+        let args = vec![String::from("csv"), String::from(file)];
+        let config =
+            common::create_config(Operation::Import, args, db).with_import_options(options);
+        rust_todo_cli::run(&config)
+    };
+    let imported = |db: &str, file: &str, options: ImportOptions| -> Result<Vec<usize>, RtcError> {
+        match import_with(db, file, options)? {
+            RunReturn::Imported(ids) => Ok(ids),
+            x => panic!("Unexpected return {:?}", x),
+        }
+    };
+    let export = |db: &str, options: ExportOptions| -> Result<String, RtcError> {
+        let config = common::create_config(Operation::Export, vec![String::from("csv")], db)
+            .with_export_options(options);
+        match rust_todo_cli::run(&config)? {
+            RunReturn::Exported(text) => Ok(text),
+            x => panic!("Unexpected return {:?}", x),
+        }
+    };
+    let fixture = "tests/fixtures/tasks.csv";
+    let mapping = Some(String::from(
+        "ID=id,Task=name,State=status,Owner=owner,Due Date=due,Labels=tags",
+    ));
+    let keep_ids = ImportOptions {
+        columns: mapping.clone(),
+        keep_ids: true,
+        ..ImportOptions::default()
+    };
+
+    // A dry run shows the IDs items would get, but neither keeps them nor uses up new IDs
+    assert_eq!(
+        imported(
+            db,
+            fixture,
+            ImportOptions {
+                dry_run: true,
+                ..keep_ids.clone()
+            }
+        )?,
+        vec![7, 12]
+    );
+    assert_eq!(
+        imported(
+            db_copy,
+            fixture,
+            ImportOptions {
+                columns: mapping.clone(),
+                ..ImportOptions::default()
+            }
+        )?,
+        vec![1, 2]
+    );
+    assert_eq!(imported(db, fixture, keep_ids.clone())?, vec![7, 12]);
+    // New IDs come after kept ones
+    do_op_and_assert(db, Operation::Add, vec!["next"], Assertion::Number(13))?;
+
+    let item = match run(db, Operation::Show, vec!["7"])? {
+        RunReturn::Show(Some(item), _) => item,
+        x => panic!("Unexpected return {:?}", x),
+    };
+    assert_eq!(item.name(), "Write launch email, draft 2");
+    assert_eq!(item.status, Status::Open);
+    assert_eq!(item.tags, vec!["marketing", "launch"]);
+    assert_eq!(item.due.unwrap().to_string(), "2021-03-05 00:00:00 UTC");
+    // Unmapped columns are skipped, mapped ones without a field of their own are extensions
+    assert_eq!(item.extensions.len(), 1);
+    assert_eq!(item.extensions.get("owner").unwrap(), "alice");
+
+    let item = match run(db, Operation::Show, vec!["12"])? {
+        RunReturn::Show(Some(item), _) => item,
+        x => panic!("Unexpected return {:?}", x),
+    };
+    assert_eq!(item.name(), "Review \"pricing\" page");
+    assert_eq!(item.status, Status::Done);
+
+    // IDs that are in use, also in the trash, or repeated are rejected
+    assert!(matches!(
+        import_with(db, fixture, keep_ids.clone()),
+        Err(RtcError::Creation(_))
+    ));
+    do_op_and_assert(db, Operation::Delete, vec!["13"], Assertion::Unchecked)?;
+    fs::write(exported, "id,name\n13,deleted\n")?;
+    assert!(matches!(
+        import_with(
+            db,
+            exported,
+            ImportOptions {
+                keep_ids: true,
+                ..ImportOptions::default()
+            }
+        ),
+        Err(RtcError::Creation(_))
+    ));
+    fs::write(exported, "id,name\n20,first\n20,second\n")?;
+    assert!(matches!(
+        import_with(
+            db,
+            exported,
+            ImportOptions {
+                keep_ids: true,
+                ..ImportOptions::default()
+            }
+        ),
+        Err(RtcError::Creation(_))
+    ));
+
+    // All fields and quoting survive a round trip
+    let text = export(db, ExportOptions::default())?;
+    assert!(text.starts_with(
+        "id,name,status,priority,projects,tags,due,created,completed,modified,uuid,owner\n"
+    ));
+    fs::write(exported, &text)?;
+    setup_db_file(db_copy);
+    imported(
+        db_copy,
+        exported,
+        ImportOptions {
+            keep_ids: true,
+            ..ImportOptions::default()
+        },
+    )?;
+    assert_eq!(export(db_copy, ExportOptions::default())?, text);
+
+    assert_eq!(
+        export(
+            db,
+            ExportOptions {
+                columns: Some(String::from("Task=name,State=status")),
+                no_header: true,
+                filters: vec![String::from("tag=launch")],
+                ..ExportOptions::default()
+            }
+        )?,
+        "\"Write launch email, draft 2\",open\n\"Review \"\"pricing\"\" page\",done\n"
+    );
+    fs::write(exported, "Buy milk,done\n")?;
+    assert_eq!(
+        imported(
+            db,
+            exported,
+            ImportOptions {
+                columns: Some(String::from("name,status")),
+                no_header: true,
+                ..ImportOptions::default()
+            }
+        )?,
+        vec![14]
+    );
+
+    // Flags of the command line end up in the options
+    let matches = build_app().get_matches_from(vec![
+        "rtc",
+        "import",
+        "--format",
+        "csv",
+        "tasks.csv",
+        "--columns",
+        "name,status",
+        "--no-header",
+        "--keep-ids",
+    ]);
+    let config = Config::new(&matches)?;
+    assert_eq!(config.args(), &vec!["csv", "tasks.csv"]);
+    let options = config.import_options();
+    assert_eq!(options.columns.as_deref(), Some("name,status"));
+    assert!(options.no_header && options.keep_ids && !options.dry_run && !options.sync);
+    let matches = build_app().get_matches_from(vec![
+        "rtc",
+        "export",
+        "--format",
+        "markdown",
+        "--group-by",
+        "tag",
+        "--filter",
+        "status=open",
+    ]);
+    let config = Config::new(&matches)?;
+    assert_eq!(config.args(), &vec!["markdown"]);
+    assert!(matches!(
+        config.export_options().group_by,
+        Some(GroupBy::Tag)
+    ));
+    assert_eq!(config.export_options().filters, vec!["status=open"]);
+
+    fs::remove_file(exported)?;
+    Ok(())
+}
+
+#[test]
+fn test_org() -> Result<(), RtcError> {
+    let db = "testcase_org.json";
+    let exported = "testcase_org.org";
+    setup_db_file(db);
+    let run = |operation: Operation, args: Vec<&str>| {
+        let config =
+            common::create_config(operation, args.into_iter().map(String::from).collect(), db);
+        rust_todo_cli::run(&config)
+    };
+    let imported = |file: &str, options: ImportOptions| -> Result<Vec<usize>, RtcError> {
+        let args = vec![String::from("org"), String::from(file)];
+        let config =
+            common::create_config(Operation::Import, args, db).with_import_options(options);
+        match rust_todo_cli::run(&config)? {
+            RunReturn::Imported(ids) => Ok(ids),
+            x => panic!("Unexpected return {:?}", x),
+        }
+    };
+    let export = || -> Result<String, RtcError> {
+        match run(Operation::Export, vec!["org"])? {
+            RunReturn::Exported(text) => Ok(text),
+            x => panic!("Unexpected return {:?}", x),
+        }
+    };
+    let show = |id: &str| -> Result<TodoItem, RtcError> {
+        match run(Operation::Show, vec![id])? {
+            RunReturn::Show(Some(item), _) => Ok(item),
+            x => panic!("Unexpected return {:?}", x),
+        }
+    };
+
+    // Headlines without a keyword are sections, nested ones are items too
+    assert_eq!(
+        imported("tests/fixtures/todo.org", ImportOptions::default())?,
+        vec![1, 2, 3]
+    );
+    let item = show("1")?;
+    assert_eq!(item.name(), "Call mom");
+    assert_eq!(item.priority, Some('A'));
+    assert_eq!(item.tags, vec!["phone"]);
+    assert_eq!(item.projects, vec!["family"]);
+    assert_eq!(item.due.unwrap().to_string(), "2021-03-05 00:00:00 UTC");
+    let item = show("2")?;
+    assert_eq!(item.status, Status::Done);
+    assert_eq!(
+        item.completed_at().unwrap().to_string(),
+        "2021-03-01 17:30:00 UTC"
+    );
+    assert_eq!(item.extensions.get("scheduled").unwrap(), "2021-02-26");
+    assert_eq!(item.extensions.get("estimate").unwrap(), "2h");
+
+    let text = export()?;
+    assert!(text.starts_with("* TODO [#A] Call mom :phone:\n  DEADLINE: <2021-03-05 Fri>\n"));
+    assert!(text.contains(
+        "* DONE write report :q1:\n  CLOSED: [2021-03-01 Mon 17:30] SCHEDULED: <2021-02-26 Fri>\n"
+    ));
+    assert!(text.contains("  :RTC_ID: 2\n"));
+    assert!(text.contains("  :ESTIMATE: 2h\n"));
+
+    // Items edited in the file are updated by their ID, new ones are added
+    let edited =
+        text.replace("* TODO proof read", "* DONE proof read") + "* TODO [#B] plan next release\n";
+    fs::write(exported, &edited)?;
+    let sync = ImportOptions {
+        sync: true,
+        ..ImportOptions::default()
+    };
+    let dry_run = ImportOptions {
+        dry_run: true,
+        ..sync.clone()
+    };
+    assert_eq!(imported(exported, dry_run)?, vec![3, 4]);
+    assert_eq!(show("3")?.status, Status::Open);
+
+    assert_eq!(imported(exported, sync.clone())?, vec![3, 4]);
+    let item = show("3")?;
+    assert_eq!(item.status, Status::Done);
+    assert!(item.completed_at().is_some());
+    assert_eq!(show("4")?.priority, Some('B'));
+    // Unchanged items are left alone, and not added again without syncing either
+    fs::write(exported, export()?)?;
+    assert_eq!(imported(exported, sync)?, Vec::<usize>::new());
+    assert_eq!(
+        imported(exported, ImportOptions::default())?,
+        Vec::<usize>::new()
+    );
+
+    fs::remove_file(exported)?;
+    Ok(())
+}
+
+#[test]
+fn test_scan() -> Result<(), RtcError> {
+    let db = "testcase_scan.json";
+    let tree = "testcase_scan_tree";
+    setup_db_file(db);
+    let _ = fs::remove_dir_all(tree);
+    fs::create_dir_all(format!("{}/src", tree))?;
+    fs::create_dir_all(format!("{}/scripts", tree))?;
+    fs::create_dir_all(format!("{}/target", tree))?;
+    let run = |operation: Operation, args: Vec<&str>| {
+        let config =
+            common::create_config(operation, args.into_iter().map(String::from).collect(), db);
+        rust_todo_cli::run(&config)
+    };
+    let scan = || -> Result<[Vec<usize>; 3], RtcError> {
+        match run(Operation::Scan, vec![tree])? {
+            RunReturn::Scanned(added, moved, closed) => Ok([added, moved, closed]),
+            x => panic!("Unexpected return {:?}", x),
+        }
+    };
+    let show = |id: &str| -> Result<TodoItem, RtcError> {
+        match run(Operation::Show, vec![id])? {
+            RunReturn::Show(Some(item), _) => Ok(item),
+            x => panic!("Unexpected return {:?}", x),
+        }
+    };
+    let none = Vec::<usize>::new;
+
+    let main = [
+        "fn main() {",
+        "    // TODO: handle errors",
+        "    let retries = 3; // FIXME(anna): read from config",
+        "    /* XXX */",
+        "    // todo and TODOS are no markers, and neither is a mentioned // TODO",
+        "    let TODO_LIST = \"// TODO: in a string\"; let c = '\"'; // TODO_LIST is no marker",
+        "}",
+    ];
+    fs::write(format!("{}/src/main.rs", tree), main.join("\n"))?;
+    fs::write(
+        format!("{}/scripts/build.sh", tree),
+        "# TODO handle errors\n",
+    )?;
+    fs::write(format!("{}/target/gen.rs", tree), "// TODO: ignored\n")?;
+    fs::write(format!("{}/notes.txt", tree), "TODO: not code\n")?;
+    fs::write(format!("{}/.gitignore", tree), "target/\n")?;
+
+    // Files are scanned in order of their paths
+    assert_eq!(scan()?, [vec![1, 2, 3, 4], none(), none()]);
+    let item = show("2")?;
+    assert_eq!(item.name(), "handle errors");
+    assert_eq!(item.tags, vec!["todo"]);
+    assert_eq!(item.extensions.get("source").unwrap(), "src/main.rs#L2");
+    let item = show("3")?;
+    assert_eq!(item.name(), "read from config");
+    assert_eq!(item.tags, vec!["fixme"]);
+    assert_eq!(show("4")?.name(), "XXX");
+    assert_eq!(scan()?, [none(), none(), none()]);
+
+    // Items follow their comments, and the ones of removed comments are done
+    let mut edited = vec!["use std::fs;"];
+    edited.extend(main.iter().filter(|line| !line.contains("XXX")));
+    fs::write(format!("{}/src/main.rs", tree), edited.join("\n"))?;
+    assert_eq!(scan()?, [none(), vec![2, 3], vec![4]]);
+    assert_eq!(
+        show("2")?.extensions.get("source").unwrap(),
+        "src/main.rs#L3"
+    );
+    assert_eq!(show("4")?.status, Status::Done);
+    assert_eq!(scan()?, [none(), none(), none()]);
+
+    // Deleted items are not added again while their comment is there
+    run(Operation::Delete, vec!["1"])?;
+    assert_eq!(scan()?, [none(), none(), none()]);
+
+    // Sources survive todo.txt
+    let exported = "testcase_scan.txt";
+    let copy = "testcase_scan_copy.json";
+    setup_db_file(copy);
+    run(Operation::Export, vec!["todotxt", exported])?;
+    let config = common::create_config(
+        Operation::Import,
+        vec![String::from("todotxt"), String::from(exported)],
+        copy,
+    );
+    rust_todo_cli::run(&config)?;
+    let config = common::create_config(Operation::Show, vec![String::from("1")], copy);
+    match rust_todo_cli::run(&config)? {
+        RunReturn::Show(Some(item), _) => {
+            assert_eq!(item.extensions, show("2")?.extensions);
+            assert_eq!(item.extensions.get("source").unwrap(), "src/main.rs#L3");
+        }
+        x => panic!("Unexpected return {:?}", x),
+    }
+    fs::remove_file(exported)?;
+
+    fs::remove_dir_all(tree)?;
+    Ok(())
+}
+
+#[test]
+fn test_git() -> Result<(), Box<dyn std::error::Error>> {
+    let db = "testcase_git.json";
+    let repo = "testcase_git_repo";
+    setup_db_file(db);
+    let _ = fs::remove_dir_all(repo);
+    fs::create_dir_all(repo)?;
+    let run = |operation: Operation, args: Vec<&str>| {
+        let config =
+            common::create_config(operation, args.into_iter().map(String::from).collect(), db);
+        rust_todo_cli::run(&config)
+    };
+    // Installed hooks would run this test binary, so commits skip them
+    let git = |args: &[&str]| -> Result<String, Box<dyn std::error::Error>> {
+        let output = Command::new("git")
+            .args([
+                "-C",
+                repo,
+                "-c",
+                "user.name=test",
+                "-c",
+                "user.email=test@example.com",
+            ])
+            .args(["-c", "core.hooksPath=/dev/null"])
+            .args(args)
+            .output()?;
+        assert!(output.status.success(), "{:?}", output);
+        Ok(String::from_utf8(output.stdout)?.trim().to_string())
+    };
+    let show = |id: &str| -> Result<TodoItem, RtcError> {
+        match run(Operation::Show, vec![id])? {
+            RunReturn::Show(Some(item), _) => Ok(item),
+            x => panic!("Unexpected return {:?}", x),
+        }
+    };
+
+    git(&["init", "-q"])?;
+    git(&["commit", "-q", "--allow-empty", "-m", "Initial commit"])?;
+    git(&["checkout", "-q", "-b", "feature/parser"])?;
+    for name in &["parser", "docs"] {
+        do_op_and_assert(db, Operation::Add, vec![name], Assertion::Unchecked)?;
+    }
+
+    assert!(matches!(
+        run(Operation::Start, vec!["1", repo])?,
+        RunReturn::Update(true)
+    ));
+    assert_eq!(
+        show("1")?.extensions.get("branch").unwrap(),
+        "feature/parser"
+    );
+    assert!(matches!(
+        run(Operation::Start, vec!["1", repo])?,
+        RunReturn::Update(false)
+    ));
+    assert!(matches!(
+        run(Operation::Start, vec!["9", repo]),
+        Err(RtcError::ItemNotFound(_))
+    ));
+
+    git(&[
+        "commit",
+        "-q",
+        "--allow-empty",
+        "-m",
+        "Parse nested lists\n\nCloses rtc#1, see rtc#2 and rtc#99",
+    ])?;
+    let hash = git(&["rev-parse", "HEAD"])?;
+    // Unknown items are skipped, and linking twice changes nothing
+    for expected in &[vec![1, 2], vec![]] {
+        match run(Operation::PostCommit, vec![repo])? {
+            RunReturn::Linked(ids) => assert_eq!(&ids, expected),
+            x => panic!("Unexpected return {:?}", x),
+        }
+    }
+    let item = show("1")?;
+    assert_eq!(item.status, Status::Done);
+    assert_eq!(item.extensions.get("commits"), Some(&hash));
+    let item = show("2")?;
+    assert_eq!(item.status, Status::Open);
+    assert_eq!(item.extensions.get("commits"), Some(&hash));
+
+    // Starting a done item reopens it
+    run(Operation::Start, vec!["1", repo])?;
+    assert_eq!(show("1")?.status, Status::Open);
+
+    let hook = match run(Operation::InstallHooks, vec![repo])? {
+        RunReturn::HooksInstalled(hook) => hook,
+        x => panic!("Unexpected return {:?}", x),
+    };
+    assert!(hook.ends_with(".git/hooks/post-commit"));
+    assert!(fs::read_to_string(&hook)?.contains("\" git post-commit || true"));
+    // Installing again replaces the hook, but hooks of others are left alone
+    run(Operation::InstallHooks, vec![repo])?;
+    fs::write(&hook, "#!/bin/sh\necho other\n")?;
+    assert!(matches!(
+        run(Operation::InstallHooks, vec![repo]),
+        Err(RtcError::Git(_))
+    ));
+
+    fs::remove_dir_all(repo)?;
+    Ok(())
+}
+
+#[test]
+fn test_backup_and_restore() -> Result<(), RtcError> {
+    let db = "testcase_backup.json";
+    let db_merge = "testcase_backup_merge.json";
+    let backup = "testcase_backup.backup.json";
+    setup_db_file(db);
+    setup_db_file(db_merge);
+    let run = |db: &str, operation: Operation, args: Vec<&str>| {
+        let config =
+            common::create_config(operation, args.into_iter().map(String::from).collect(), db);
+        rust_todo_cli::run(&config)
+    };
+    let restored = |db: &str, operation: Operation, file: &str| -> Result<Vec<usize>, RtcError> {
+        match run(db, operation, vec![file])? {
+            RunReturn::BackupRestored(ids) => Ok(ids),
+            x => panic!("Unexpected return {:?}", x),
+        }
+    };
+
+    for name in &["first", "second", "third"] {
+        do_op_and_assert(db, Operation::Add, vec![name], Assertion::Unchecked)?;
+    }
+    do_op_and_assert(db, Operation::Delete, vec!["3"], Assertion::Unchecked)?;
+    run(db, Operation::SaveView, vec!["open", "status=open"])?;
+    match run(db, Operation::Backup, vec![backup])? {
+        RunReturn::BackedUp(file) => assert_eq!(file.to_str(), Some(backup)),
+        x => panic!("Unexpected return {:?}", x),
+    }
+
+    // The backup documents its format and holds every record as it is stored
+    let document = serde_json::from_str::<Value>(&fs::read_to_string(backup)?).unwrap();
+    assert_eq!(document["format"], json!("rust-todo-cli backup"));
+    assert_eq!(document["version"], json!(1));
+    assert_eq!(document["schema_version"], json!(SCHEMA_VERSION));
+    assert_eq!(document["records"]["total_creations"], json!(3));
+    assert_eq!(document["records"]["2"]["name"], json!("second"));
+    assert!(document["records"]["trash"]["3"].is_object());
+
+    // Replacing brings back the exact state, after taking an automatic backup
+    do_op_and_assert(db, Operation::Add, vec!["fourth"], Assertion::Number(4))?;
     do_op_and_assert(
+        db,
         Operation::Update,
-        vec!["3", "done"],
-        Assertion::Boolean(true),
+        vec!["1", "done"],
+        Assertion::Unchecked,
+    )?;
+    assert_eq!(restored(db, Operation::RestoreBackup, backup)?, vec![1, 2]);
+    do_op_and_assert(
+        db,
+        Operation::Filter,
+        vec!["status=open"],
+        Assertion::Number(2),
     )?;
+    assert!(matches!(
+        run(db, Operation::Restore, vec!["3"])?,
+        RunReturn::Restoration(true)
+    ));
+    do_op_and_assert(db, Operation::Add, vec!["fourth"], Assertion::Number(4))?;
+    let auto_backups = fs::read_dir("testcase_backup.backups")?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().into_string().unwrap())
+        .collect::<Vec<String>>();
+    assert_eq!(auto_backups.len(), 1);
+    assert!(auto_backups[0].starts_with("auto-") && auto_backups[0].ends_with("-restore.json"));
 
-    // Filter by other criteria
-    do_op_and_assert(Operation::Filter, vec!["status=done"], Assertion::Number(1))?;
-    do_op_and_assert(Operation::Filter, vec!["status=open"], Assertion::Number(2))?;
+    // Merging adds items under new IDs if theirs are taken, and only once
+    do_op_and_assert(
+        db_merge,
+        Operation::Add,
+        vec!["other"],
+        Assertion::Number(1),
+    )?;
+    assert_eq!(
+        restored(db_merge, Operation::MergeBackup, backup)?,
+        vec![4, 2]
+    );
+    assert_eq!(
+        restored(db_merge, Operation::MergeBackup, backup)?,
+        Vec::<usize>::new()
+    );
+    do_op_and_assert(db_merge, Operation::GetAll, vec![], Assertion::Number(3))?;
+    assert!(matches!(
+        run(db_merge, Operation::Restore, vec!["3"])?,
+        RunReturn::Restoration(true)
+    ));
+    match run(db_merge, Operation::ListViews, vec![])? {
+        RunReturn::Views(views) => assert!(views.contains_key("open")),
+        x => panic!("Unexpected return {:?}", x),
+    }
+    do_op_and_assert(db_merge, Operation::Add, vec!["new"], Assertion::Number(5))?;
+
+    // Backups of older schema versions are upgraded, those of newer backup versions rejected
+    fs::write(
+        backup,
+        json!({
+            "format": "rust-todo-cli backup",
+            "version": 1,
+            "created_at": 1614556800000_i64,
+            "schema_version": 0,
+            "records": {
+                "1": {"id": 1, "name": "old", "status": "Open", "creation_date": 1614556800000_i64},
+                "total_creations": 1
+            }
+        })
+        .to_string(),
+    )?;
+    assert_eq!(restored(db, Operation::RestoreBackup, backup)?, vec![1]);
+    let raw = load_raw_db(db);
+    assert_eq!(raw.get::<u32>("schema_version"), Some(SCHEMA_VERSION));
+    assert_eq!(raw.get::<Value>("1").unwrap()["tags"], json!([]));
+
+    fs::write(
+        backup,
+        json!({"format": "rust-todo-cli backup", "version": 2, "created_at": 0,
+               "schema_version": 3, "records": {}})
+        .to_string(),
+    )?;
+    assert!(matches!(
+        run(db, Operation::RestoreBackup, vec![backup]),
+        Err(RtcError::Parse(_))
+    ));
+
+    // Backups are named explicitly, so that any file name works and --merge is never ignored
+    let parse = |args: Vec<&str>| {
+        let matches = build_app()
+            .get_matches_from_safe(iter::once("rtc").chain(args))
+            .map_err(|e| RtcError::Parse(e.to_string()))?;
+        Config::new(&matches)
+    };
+    let config = parse(vec!["restore", "--backup", "20261019", "--merge"])?;
+    assert!(matches!(config.operation(), Operation::MergeBackup));
+    assert_eq!(config.args(), &vec![String::from("20261019")]);
+    assert!(matches!(
+        parse(vec!["restore", "5"])?.operation(),
+        Operation::Restore
+    ));
+    assert!(parse(vec!["restore", "5", "--merge"]).is_err());
+    assert!(parse(vec!["restore", "5", "--backup", "file.json"]).is_err());
+
+    fs::remove_file(backup)?;
+    Ok(())
+}
+
+#[test]
+fn test_backup_rotation() -> Result<(), RtcError> {
+    let db = "testcase_backup_rotation.json";
+    let backups = "testcase_backup_rotation.backups";
+    setup_db_file(db);
+    let settings = Settings {
+        backups_to_keep: Some(2),
+        ..Settings::default()
+    };
+    let run = |operation: Operation, args: Vec<&str>| {
+        let config =
+            common::create_config(operation, args.into_iter().map(String::from).collect(), db)
+                .with_settings(settings.clone())
+                .with_assume_yes();
+        rust_todo_cli::run(&config)
+    };
+
+    for name in &["first", "second", "third", "fourth"] {
+        run(Operation::Add, vec![name])?;
+    }
+    // Only operations that may lose data are backed up
+    assert!(fs::read_dir(backups).is_err());
+    run(Operation::Delete, vec!["1-2"])?;
+    run(Operation::Delete, vec!["3", "4"])?;
+    run(Operation::EmptyTrash, vec![])?;
+
+    let mut names = fs::read_dir(backups)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().into_string().unwrap())
+        .collect::<Vec<String>>();
+    names.sort();
+    assert_eq!(names.len(), 2);
+    assert!(names[0].ends_with("-delete.json"));
+    assert!(names[1].ends_with("-empty-trash.json"));
+
+    // The newest backup still holds the trashed items
+    let newest = fs::read_to_string(format!("{}/{}", backups, names[1]))?;
+    let document = serde_json::from_str::<Value>(&newest).unwrap();
+    assert_eq!(document["records"]["trash"].as_object().unwrap().len(), 4);
+    Ok(())
+}
+
+#[test]
+fn test_doctor() -> Result<(), RtcError> {
+    let db = "testcase_doctor.json";
+    let quarantine = "testcase_doctor.quarantine.json";
+    setup_db_file(db);
+
+    for name in &["first", "second", "third"] {
+        do_op_and_assert(db, Operation::Add, vec![name], Assertion::Unchecked)?;
+    }
+    do_op_and_assert(db, Operation::Delete, vec!["3"], Assertion::Unchecked)?;
+    do_op_and_assert(db, Operation::Doctor, vec![], Assertion::Number(0))?;
+
+    let mut raw = load_raw_db(db);
+    let first = raw.get::<Value>("1").unwrap();
+    raw.set("4", &json!({"name": "no id"})).unwrap();
+    raw.set("7", &first).unwrap();
+    raw.set("stray", &1).unwrap();
+    // The trashed item reappears as a live one
+    let mut third = raw.get::<Value>("trash").unwrap()["3"]["item"].clone();
+    third["id"] = json!(3);
+    raw.set("3", &third).unwrap();
+    raw.set("total_creations", &2).unwrap();
+    raw.dump().unwrap();
+
+    let config = common::create_config(Operation::Doctor, vec![], db);
+    match rust_todo_cli::run(&config)? {
+        RunReturn::Doctor(problems) => {
+            assert_eq!(problems.len(), 5);
+            assert!(problems
+                .iter()
+                .any(|p| matches!(p, Problem::Undeserializable(k) if k == "4")));
+            assert!(problems
+                .iter()
+                .any(|p| matches!(p, Problem::IdMismatch { key, id: 1 } if key == "7")));
+            assert!(problems
+                .iter()
+                .any(|p| matches!(p, Problem::DuplicateId { key, id: 3 } if key == "trash/3")));
+            assert!(problems
+                .iter()
+                .any(|p| matches!(p, Problem::UnknownKey(k) if k == "stray")));
+            assert!(problems.iter().any(|p| matches!(
+                p,
+                Problem::Counter {
+                    counter: Some(2),
+                    highest: 7
+                }
+            )));
+        }
+        x => panic!("Unexpected return {:?}", x),
+    }
+    // Without fix nothing is changed
+    assert!(fs::metadata(quarantine).is_err());
+
+    do_op_and_assert(db, Operation::DoctorFix, vec![], Assertion::Number(5))?;
+    let quarantined = serde_json::from_str::<Value>(&fs::read_to_string(quarantine)?).unwrap();
+    assert_eq!(quarantined["4"], json!({"name": "no id"}));
+    assert_eq!(quarantined["7"], first);
+    assert_eq!(quarantined["stray"], json!(1));
+    assert_eq!(quarantined["trash/3"]["item"]["name"], json!("third"));
+
+    do_op_and_assert(db, Operation::Doctor, vec![], Assertion::Number(0))?;
+    do_op_and_assert(db, Operation::GetAll, vec![], Assertion::Number(3))?;
+    assert_eq!(load_raw_db(db).get::<Value>("trash"), Some(json!({})));
+    // New items do not reuse the IDs of quarantined ones
+    do_op_and_assert(db, Operation::Add, vec!["new"], Assertion::Number(8))?;
+
+    // A quarantine file that can not be read is kept, and a new one is started
+    fs::write(quarantine, "{cut off")?;
+    let mut raw = load_raw_db(db);
+    raw.set("stray again", &2).unwrap();
+    raw.dump().unwrap();
+    do_op_and_assert(db, Operation::DoctorFix, vec![], Assertion::Number(1))?;
+    assert_eq!(fs::read_to_string(quarantine)?, "{cut off");
+    let new_quarantines = fs::read_dir(".")?
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name.starts_with("testcase_doctor.quarantine.") && name != quarantine)
+        .collect::<Vec<String>>();
+    assert_eq!(new_quarantines.len(), 1);
+    let quarantined =
+        serde_json::from_str::<Value>(&fs::read_to_string(&new_quarantines[0])?).unwrap();
+    assert_eq!(quarantined["stray again"], json!(2));
+    fs::remove_file(&new_quarantines[0])?;
+
+    Ok(())
+}
+
+#[test]
+fn test_doctor_on_unloadable_db() -> Result<(), RtcError> {
+    let db = "testcase_doctor_truncated.json";
+    let fixture = "tests/fixtures/truncated.json";
+    setup_db_file(db);
+    for entry in fs::read_dir(".")? {
+        let name = entry?.file_name().into_string().unwrap_or_default();
+        if name.starts_with("testcase_doctor_truncated.quarantine.") {
+            fs::remove_file(name)?;
+        }
+    }
+    fs::copy(fixture, db)?;
+
+    let config = common::create_config(Operation::Doctor, vec![], db);
+    match rust_todo_cli::run(&config)? {
+        RunReturn::Doctor(problems) => {
+            assert_eq!(problems.len(), 1);
+            assert!(matches!(&problems[0], Problem::Unloadable(_)));
+        }
+        x => panic!("Unexpected return {:?}", x),
+    }
+    assert_eq!(fs::read_to_string(db)?, fs::read_to_string(fixture)?);
+
+    // The file is moved aside as it is
+    do_op_and_assert(db, Operation::DoctorFix, vec![], Assertion::Number(1))?;
+    assert!(fs::metadata(db).is_err());
+    let raw_files = fs::read_dir(".")?
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .filter(|name| name.starts_with("testcase_doctor_truncated.quarantine."))
+        .collect::<Vec<String>>();
+    assert_eq!(raw_files.len(), 1);
+    assert!(raw_files[0].ends_with(".raw"));
+    assert_eq!(
+        fs::read_to_string(&raw_files[0])?,
+        fs::read_to_string(fixture)?
+    );
+
+    do_op_and_assert(db, Operation::Add, vec!["item"], Assertion::Number(1))?;
+    do_op_and_assert(db, Operation::Doctor, vec![], Assertion::Number(0))?;
+
+    fs::remove_file(&raw_files[0])?;
+    Ok(())
+}
+
+#[test]
+fn test_concurrent_runs_are_serialized() -> Result<(), RtcError> {
+    let db = "testcase_lock.json";
+    setup_db_file(db);
+
+    // Another process holds the lock
+    let lock = fs::File::create("testcase_lock.lock")?;
+    lock.lock_exclusive()?;
+
+    let adder = thread::spawn(move || {
+        (0..10)
+            .map(|_| {
+                let config = common::create_config(Operation::Add, vec![String::from("x")], db);
+                match rust_todo_cli::run(&config) {
+                    Ok(RunReturn::Addition(id)) => id,
+                    x => panic!("Unexpected return {:?}", x),
+                }
+            })
+            .collect::<Vec<usize>>()
+    });
+
+    thread::sleep(std::time::Duration::from_millis(200));
+    assert!(fs::metadata(db).is_err(), "db was loaded while locked");
+    lock.unlock()?;
+
+    // Runs from a second thread interleave with the first, but never share IDs
+    let mut ids = (0..10)
+        .map(|_| {
+            let config = common::create_config(Operation::Add, vec![String::from("y")], db);
+            match rust_todo_cli::run(&config) {
+                Ok(RunReturn::Addition(id)) => id,
+                x => panic!("Unexpected return {:?}", x),
+            }
+        })
+        .collect::<Vec<usize>>();
+    ids.extend(adder.join().unwrap());
+    ids.sort_unstable();
+    assert_eq!(ids, (1..=20).collect::<Vec<usize>>());
+
+    Ok(())
+}
+
+#[test]
+fn test_transactions() -> Result<(), Box<dyn std::error::Error>> {
+    let db = "testcase_transactions.json";
+    setup_db_file(db);
+    let new_driver = || {
+        PickleDbDriver::new(PickleDb::new(
+            db,
+            PickleDbDumpPolicy::DumpUponRequest,
+            SerializationMethod::Json,
+        ))
+    };
+
+    // Changes outside of transactions are written right away
+    let mut driver = new_driver();
+    driver.set("a", &1)?;
+    assert_eq!(load_raw_db(db).get::<i32>("a"), Some(1));
+
+    driver.begin();
+    driver.set("a", &2)?;
+    driver.set("b", &2)?;
+    assert_eq!(load_raw_db(db).get::<i32>("a"), Some(1));
+
+    // Rolling back a nested transaction keeps the outer one's changes
+    driver.begin();
+    driver.set("a", &3)?;
+    driver.del("b")?;
+    driver.set("c", &3)?;
+    driver.rollback()?;
+    assert_eq!(driver.get::<i32>("a"), Some(2));
+    assert_eq!(driver.get::<i32>("b"), Some(2));
+    assert_eq!(driver.get::<i32>("c"), None);
+
+    // Committing a nested transaction only writes once the outer one commits
+    driver.begin();
+    driver.set("d", &4)?;
+    driver.commit()?;
+    assert_eq!(load_raw_db(db).get::<i32>("d"), None);
+    driver.commit()?;
+    let raw = load_raw_db(db);
+    assert_eq!(raw.get::<i32>("a"), Some(2));
+    assert_eq!(raw.get::<i32>("d"), Some(4));
+
+    driver.begin();
+    driver.set("a", &5)?;
+    driver.del("d")?;
+    driver.rollback()?;
+    assert_eq!(driver.get::<i32>("a"), Some(2));
+    assert_eq!(driver.get::<i32>("d"), Some(4));
+    assert!(driver.commit().is_err());
 
     Ok(())
 }
 
-fn setup_db_file() {
-    match common::remove_db() {
+fn load_raw_db(db_name: &str) -> PickleDb {
+    PickleDb::load(
+        db_name,
+        PickleDbDumpPolicy::DumpUponRequest,
+        SerializationMethod::Json,
+    )
+    .unwrap()
+}
+
+fn setup_db_file(db_name: &str) {
+    match common::remove_db(db_name) {
         Ok(_) => println!("db removed"),
         Err(_) => println!("no db to remove"),
     }
@@ -58,9 +2384,11 @@ fn setup_db_file() {
 enum Assertion {
     Number(usize),
     Boolean(bool),
+    Unchecked,
 }
 
 fn do_op_and_assert(
+    db_name: &str,
     operation: Operation,
     args: Vec<&str>,
     assertion: Assertion,
@@ -68,8 +2396,14 @@ fn do_op_and_assert(
     let config = common::create_config(
         operation,
         args.into_iter().map(|it| it.to_string()).collect(),
+        db_name,
     );
 
+    if let Assertion::Unchecked = assertion {
+        rust_todo_cli::run(&config)?;
+        return Ok(());
+    }
+
     let run_result = rust_todo_cli::run(&config)?;
 
     match run_result {
@@ -94,7 +2428,7 @@ fn do_op_and_assert(
                 _ => panic!("Wrong assertion type"),
             }
         ),
-        RunReturn::Filter(res) => assert_eq!(
+        RunReturn::Filter(res, _) => assert_eq!(
             res.len(),
             match assertion {
                 Assertion::Number(x) => x,
@@ -108,6 +2442,14 @@ fn do_op_and_assert(
                 _ => panic!("Wrong assertion type"),
             }
         ),
+        RunReturn::Doctor(res) => assert_eq!(
+            res.len(),
+            match assertion {
+                Assertion::Number(x) => x,
+                _ => panic!("Wrong assertion type"),
+            }
+        ),
+        x => panic!("Unexpected return {:?}", x),
     }
 
     Ok(())
@@ -0,0 +1,244 @@
+#![cfg(unix)]
+
+use rust_todo_cli::{call_daemon, run_via_daemon, Config, Daemon, Operation, RunReturn, Stdio};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+mod common;
+
+/// Sends a raw line and returns the response
+fn send(socket: &Path, line: &str) -> Value {
+    let mut stream = UnixStream::connect(socket).unwrap();
+    writeln!(stream, "{}", line).unwrap();
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response).unwrap();
+    serde_json::from_str(&response).unwrap()
+}
+
+/// Client of a stdio session, keeping notifications that arrive while waiting for a response
+struct StdioClient {
+    stream: UnixStream,
+    reader: BufReader<UnixStream>,
+    notifications: VecDeque<Value>,
+}
+
+impl StdioClient {
+    fn read(&mut self) -> Value {
+        let mut line = String::new();
+        self.reader.read_line(&mut line).unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+
+    fn call(&mut self, method: &str, params: Value) -> Value {
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        writeln!(self.stream, "{}", request).unwrap();
+        loop {
+            let message = self.read();
+            if message["method"] == json!("changed") {
+                self.notifications.push_back(message);
+            } else {
+                return message;
+            }
+        }
+    }
+
+    fn changed(&mut self) -> Value {
+        let mut notification = match self.notifications.pop_front() {
+            Some(notification) => notification,
+            None => self.read(),
+        };
+        assert_eq!(notification["method"], json!("changed"));
+        notification["params"].take()
+    }
+}
+
+#[test]
+fn test_daemon() {
+    let db = "testcase_daemon.json";
+    let _ = common::remove_db(db);
+    let session = Config::session(Some(db)).unwrap();
+    let socket = session.socket_file();
+    let daemon = Daemon::bind(&session).unwrap();
+    let serving = thread::spawn(move || daemon.serve());
+    assert!(Daemon::bind(&session).is_err());
+
+    let pong = call_daemon(&socket, "ping", Value::Null).unwrap();
+    assert!(pong["pid"].is_number());
+
+    let args = |args: &[&str]| json!({ "args": args });
+    let result = call_daemon(&socket, "run", args(&["-a", "buy", "milk"])).unwrap();
+    assert!(result["output"].as_str().unwrap().contains("Created item"));
+    let item = call_daemon(&socket, "show", json!({ "id": 1 })).unwrap();
+    assert_eq!(item["name"], json!("buy milk"));
+
+    // Relative paths are those of the client, while the daemon stays where it is
+    let dir = env::temp_dir().join(format!("testcase_daemon_{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let current_dir = env::current_dir().unwrap();
+    let params = json!({ "args": ["export", "--format", "todotxt", "todo.txt"], "cwd": dir });
+    call_daemon(&socket, "run", params).unwrap();
+    assert!(dir.join("todo.txt").exists());
+    assert_eq!(env::current_dir().unwrap(), current_dir);
+    fs::remove_dir_all(&dir).unwrap();
+
+    // Rewrites are picked up even if they keep the size and modification time of the file
+    let modified = fs::metadata(db).unwrap().modified().unwrap();
+    let contents = fs::read_to_string(db).unwrap();
+    fs::write(db, contents.replace("buy milk", "buy silk")).unwrap();
+    File::options()
+        .write(true)
+        .open(db)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+    let item = call_daemon(&socket, "show", json!({ "id": 1 })).unwrap();
+    assert_eq!(item["name"], json!("buy silk"));
+
+    // Changes of other processes are picked up
+    let config = common::create_config(Operation::Add, vec![String::from("write report")], db);
+    rust_todo_cli::run(&config).unwrap();
+    let list = call_daemon(&socket, "list", json!({ "filters": ["status=open"] })).unwrap();
+    assert_eq!(list["total"], json!(2));
+    let list = call_daemon(&socket, "list", json!({ "query": "name:report" })).unwrap();
+    assert_eq!(list["items"][0]["id"], json!(2));
+
+    assert!(call_daemon(&socket, "run", args(&["show", "9"])).is_err());
+    assert!(call_daemon(&socket, "run", args(&["daemon"])).is_err());
+    assert!(call_daemon(&socket, "show", json!({ "id": "one" })).is_err());
+
+    let response = send(&socket, r#"{"jsonrpc": "2.0", "id": 7, "method": "fly"}"#);
+    assert_eq!(response["id"], json!(7));
+    assert_eq!(response["error"]["code"], json!(-32601));
+    let response = send(&socket, "{");
+    assert_eq!(response["error"]["code"], json!(-32700));
+    let response = send(&socket, r#"{"jsonrpc": "1.0", "id": 8, "method": "ping"}"#);
+    assert_eq!(response["error"]["code"], json!(-32600));
+
+    // Commands run on the daemon can neither ask for confirmation nor read stdin
+    call_daemon(&socket, "run", args(&["-a", "call", "mom"])).unwrap();
+    call_daemon(&socket, "run", args(&["-a", "call", "dad"])).unwrap();
+    call_daemon(&socket, "run", args(&["-d", "3-4"])).unwrap();
+    let list = call_daemon(&socket, "list", Value::Null).unwrap();
+    assert_eq!(list["total"], json!(2));
+    assert!(call_daemon(&socket, "run", args(&["batch", "-"])).is_err());
+
+    // Commands reading stdin or asking for confirmation run in their own process
+    let config = common::create_config(
+        Operation::Import,
+        vec![String::from("json"), String::from("-")],
+        db,
+    );
+    assert!(run_via_daemon(&config, &[]).is_none());
+    let config = common::create_config(
+        Operation::Delete,
+        vec![String::from("1"), String::from("2")],
+        db,
+    );
+    assert!(run_via_daemon(&config, &[]).is_none());
+    let config = common::create_config(Operation::Done, vec![String::from("1")], db);
+    let args = ["done", "1"].map(String::from);
+    assert!(run_via_daemon(&config, &args).unwrap().is_ok());
+    let item = call_daemon(&socket, "show", json!({ "id": 1 })).unwrap();
+    assert_eq!(item["status"], json!("Done"));
+
+    call_daemon(&socket, "shutdown", Value::Null).unwrap();
+    assert!(matches!(
+        serving.join().unwrap(),
+        Ok(RunReturn::DaemonStopped)
+    ));
+    assert!(!socket.exists());
+    assert!(run_via_daemon(&config, &args).is_none());
+    common::remove_db(db).unwrap();
+}
+
+#[test]
+fn test_stdio() {
+    let db = "testcase_stdio.json";
+    let _ = common::remove_db(db);
+    let stdio = Stdio::new(&Config::session(Some(db)).unwrap()).unwrap();
+    let (stream, server) = UnixStream::pair().unwrap();
+    let input = BufReader::new(server.try_clone().unwrap());
+    let serving = thread::spawn(move || stdio.serve(input, server));
+
+    // Waiting for a notification that never comes fails instead of hanging
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    let mut client = StdioClient {
+        reader: BufReader::new(stream.try_clone().unwrap()),
+        stream,
+        notifications: VecDeque::new(),
+    };
+
+    let list = client.call("subscribe", json!({ "filters": ["status=open"] }));
+    assert_eq!(list["result"], json!({ "items": [], "total": 0 }));
+    let item = client.call("add", json!({ "name": "buy milk" }))["result"].take();
+    assert_eq!(item["id"], json!(1));
+    assert_eq!(client.changed()["total"], json!(1));
+
+    // Changes of other processes are sent as well
+    let config = common::create_config(Operation::Add, vec![String::from("write report")], db);
+    rust_todo_cli::run(&config).unwrap();
+    assert_eq!(client.changed()["total"], json!(2));
+
+    let item = client.call("update", json!({ "id": 1, "status": "done" }))["result"].take();
+    assert_eq!(item["status"], json!("Done"));
+    let list = client.changed();
+    assert_eq!(list["items"][0]["name"], json!("write report"));
+    assert_eq!(list["total"], json!(1));
+
+    let found = client.call("search", json!({ "text": "MILK buy" }))["result"].take();
+    assert_eq!(found["items"][0]["id"], json!(1));
+    assert_eq!(found["total"], json!(1));
+    let found = client.call("search", json!({ "text": "milk report" }))["result"].take();
+    assert_eq!(found["total"], json!(0));
+
+    client.call("unsubscribe", Value::Null);
+    assert_eq!(
+        client.call("delete", json!({ "id": 2 }))["result"],
+        Value::Null
+    );
+    let item = client.call("show", json!({ "id": 2 }))["result"].take();
+    assert_eq!(item["name"], json!("write report"));
+
+    let response = client.call("show", json!({ "id": 9 }));
+    assert_eq!(response["error"]["code"], json!(-32000));
+    let response = client.call("add", json!({ "name": " " }));
+    assert_eq!(response["error"]["code"], json!(-32602));
+    let response = client.call("update", json!({ "id": 1, "status": "later" }));
+    assert_eq!(response["error"]["code"], json!(-32000));
+    let list = client.call("list", Value::Null)["result"].take();
+    assert_eq!(list["items"][0]["id"], json!(1));
+    assert_eq!(list["total"], json!(1));
+    let response = client.call("fly", Value::Null);
+    assert_eq!(response["error"]["code"], json!(-32601));
+
+    // Bulk operations are not confirmed, and stdin is not read, as both belong to the protocol
+    client.call("add", json!({ "name": "call mom" }));
+    client.call("add", json!({ "name": "call dad" }));
+    let response = client.call("run", json!({ "args": ["-d", "3-4"] }));
+    let output = response["result"]["output"].as_str().unwrap();
+    assert!(!output.contains("Continue?"));
+    let list = client.call("list", Value::Null)["result"].take();
+    assert_eq!(list["total"], json!(1));
+    let response = client.call("run", json!({ "args": ["batch", "-"] }));
+    assert_eq!(response["error"]["code"], json!(-32602));
+    let args = ["import", "--format", "todotxt", "-"];
+    let response = client.call("run", json!({ "args": args }));
+    assert_eq!(response["error"]["code"], json!(-32602));
+
+    assert_eq!(client.call("shutdown", Value::Null)["result"], Value::Null);
+    assert!(matches!(
+        serving.join().unwrap(),
+        Ok(RunReturn::StdioClosed)
+    ));
+    common::remove_db(db).unwrap();
+}
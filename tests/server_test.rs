@@ -0,0 +1,136 @@
+use rust_todo_cli::{Config, Server};
+use serde_json::{json, Value};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::thread;
+
+// Not every helper is used by these tests
+#[allow(dead_code)]
+mod common;
+
+/// Serves `db` on a free local port
+fn start_server(db: &str) -> SocketAddr {
+    let _ = common::remove_db(db);
+    let server = Server::bind("127.0.0.1:0", Config::session(Some(db)).unwrap()).unwrap();
+    let addr = server.local_addr().unwrap();
+    thread::spawn(move || server.serve());
+    addr
+}
+
+/// Sends a request and returns the status, the headers in lower case and the JSON body
+fn request(addr: SocketAddr, method: &str, path: &str, body: &str) -> (u16, String, Value) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        body.len(),
+        body
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split(' ').nth(1).unwrap().parse::<u16>().unwrap();
+    let body = if body.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_str(body).unwrap()
+    };
+    (status, head.to_lowercase(), body)
+}
+
+#[test]
+fn test_items_crud() {
+    let db = "testcase_server_crud.json";
+    let addr = start_server(db);
+
+    let (status, _, list) = request(addr, "GET", "/items", "");
+    assert_eq!(status, 200);
+    assert_eq!(list, json!({ "items": [], "total": 0 }));
+
+    let (status, head, item) = request(addr, "POST", "/items", r#"{"name": "buy milk"}"#);
+    assert_eq!(status, 201);
+    assert!(head.contains("location: /items/1\r\n"));
+    assert!(head.contains("content-type: application/json"));
+    assert_eq!(item["id"], json!(1));
+    assert_eq!(item["name"], json!("buy milk"));
+    assert_eq!(item["status"], json!("Open"));
+    request(addr, "POST", "/items", r#"{"name": "write report"}"#);
+
+    let (status, _, item) = request(addr, "GET", "/items/2", "");
+    assert_eq!(status, 200);
+    assert_eq!(item["name"], json!("write report"));
+
+    let (status, _, item) = request(addr, "PATCH", "/items/1", r#"{"status": "done"}"#);
+    assert_eq!(status, 200);
+    assert_eq!(item["status"], json!("Done"));
+    assert!(item["completed_at"].is_number());
+
+    let (status, _, list) = request(addr, "GET", "/items", "");
+    assert_eq!(status, 200);
+    assert_eq!(list["total"], json!(2));
+    let (_, _, list) = request(addr, "GET", "/items?status=open", "");
+    assert_eq!(list["items"][0]["name"], json!("write report"));
+    assert_eq!(list["total"], json!(1));
+    let (_, _, list) = request(
+        addr,
+        "GET",
+        "/items?query=name%3Amilk+and+status%3Adone",
+        "",
+    );
+    assert_eq!(list["items"][0]["id"], json!(1));
+    let (_, _, list) = request(addr, "GET", "/items?sort=name:desc&limit=1", "");
+    assert_eq!(list["items"].as_array().unwrap().len(), 1);
+    assert_eq!(list["items"][0]["id"], json!(2));
+
+    let (status, _, body) = request(addr, "DELETE", "/items/2", "");
+    assert_eq!(status, 204);
+    assert_eq!(body, Value::Null);
+    let (status, _, _) = request(addr, "DELETE", "/items/2", "");
+    assert_eq!(status, 404);
+    // Deleted items are gone from the API
+    let (status, _, body) = request(addr, "GET", "/items/2", "");
+    assert_eq!(status, 404);
+    assert!(body["error"].as_str().unwrap().contains("trash"));
+
+    request(addr, "DELETE", "/items/1", "");
+    let (status, _, list) = request(addr, "GET", "/items", "");
+    assert_eq!(status, 200);
+    assert_eq!(list, json!({ "items": [], "total": 0 }));
+
+    common::remove_db(db).unwrap();
+}
+
+#[test]
+fn test_error_statuses() {
+    let db = "testcase_server_errors.json";
+    let addr = start_server(db);
+    request(addr, "POST", "/items", r#"{"name": "buy milk"}"#);
+
+    let cases = [
+        ("GET", "/items/7", "", 404),
+        ("GET", "/items/abc", "", 404),
+        ("PATCH", "/items/7", r#"{"status": "done"}"#, 404),
+        ("GET", "/tasks", "", 404),
+        ("POST", "/items", "not json", 400),
+        ("POST", "/items", r#"{"name": "x", "owner": "anna"}"#, 400),
+        ("POST", "/items", r#"{"name": " "}"#, 400),
+        ("PATCH", "/items/1", r#"{"status": "later"}"#, 400),
+        ("GET", "/items?color=red", "", 400),
+        ("GET", "/items?query=name", "", 400),
+        ("GET", "/items?limit=many", "", 400),
+        ("PUT", "/items/1", "", 405),
+    ];
+    for (method, path, body, expected) in &cases {
+        let (status, _, response) = request(addr, method, path, body);
+        assert_eq!(status, *expected, "{} {}", method, path);
+        assert!(response["error"].is_string(), "{} {}", method, path);
+    }
+    let (_, head, _) = request(addr, "DELETE", "/items", "");
+    assert!(head.contains("allow: get, post\r\n"));
+
+    common::remove_db(db).unwrap();
+}